@@ -0,0 +1,77 @@
+//! Compare linked list node capacities on the access patterns of the
+//! allocator. Run with `cargo bench --bench node_capacity`.
+
+use criterion::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+};
+use pdb::linked_list::LinkedList;
+use std::hint::black_box;
+
+const LEN: usize = 1 << 15;
+
+/// Build a list with inserts interleaved to scatter nodes over the heap
+fn scattered<const N: usize>() -> LinkedList<usize, N> {
+    let mut ll: LinkedList<usize, N> = (1..LEN).collect();
+    let mut c = ll.cursor_mut();
+    c.insert_after(0);
+    while c.next() && c.next() {
+        c.insert_after(0);
+    }
+    ll
+}
+
+/// Scanning, as done by FreeList on allocation
+fn scan<const N: usize>(c: &mut Criterion) {
+    let mut ll = scattered::<N>();
+    let mut g = c.benchmark_group("scan");
+    g.throughput(Throughput::Elements(ll.len() as u64));
+    g.bench_function(BenchmarkId::from_parameter(N), |b| {
+        b.iter(|| black_box(ll.iter_mut().map(|v| *v).sum::<usize>()))
+    });
+}
+
+/// Inserting and removing referenced values, as done by LRUMap
+fn shift<const N: usize>(c: &mut Criterion) {
+    let mut g = c.benchmark_group("shift");
+    g.bench_function(BenchmarkId::from_parameter(N), |b| {
+        b.iter_batched_ref(
+            || {
+                let mut ll = scattered::<N>();
+                let mut refs = Vec::new();
+                let mut c = ll.cursor_mut();
+                refs.push(c.reference().unwrap());
+                while c.next() {
+                    refs.push(c.reference().unwrap());
+                }
+                (ll, refs)
+            },
+            |(ll, refs)| {
+                for r in refs.iter().step_by(7) {
+                    let mut c = unsafe { r.cursor_mut(ll) };
+                    c.insert_before(0);
+                    c.insert_after(0);
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    scan::<1>,
+    scan::<2>,
+    scan::<4>,
+    scan::<8>,
+    scan::<16>,
+    scan::<32>,
+    scan::<64>,
+    shift::<1>,
+    shift::<2>,
+    shift::<4>,
+    shift::<8>,
+    shift::<16>,
+    shift::<32>,
+    shift::<64>,
+);
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+//! XChaCha20-Poly1305 authenticated encryption as specified in
+//! draft-irtf-cfrg-xchacha for encrypting pages written to disk. The extended
+//! nonce is large enough to be generated randomly.
+
+use chacha20poly1305::{
+    aead::AeadInOut, Key, KeyInit, Tag, XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::{convert::TryFrom, fmt, io};
+
+/// Size of a key in bytes
+pub const KEY_SIZE: usize = 32;
+
+/// Size of a nonce in bytes
+pub const NONCE_SIZE: usize = 24;
+
+/// Size of an authentication tag in bytes
+pub const TAG_SIZE: usize = 16;
+
+/// Binds keys derived with `derive_key()` to their use
+const KDF_INFO: &[u8] = b"pdb spill file key";
+
+/// Key for encrypting pages written to disk
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey(pub(super) [u8; KEY_SIZE]);
+
+impl EncryptionKey {
+    /// Construct a key from 32 bytes of secret key material
+    #[inline]
+    pub fn new(key: [u8; KEY_SIZE]) -> Self {
+        Self(key)
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never leak the key into logs
+        write!(f, "EncryptionKey(..)")
+    }
+}
+
+/// Encrypt `data` in place and return its authentication tag.
+///
+/// A nonce must never be reused with the same key.
+pub fn seal(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    data: &mut [u8],
+) -> [u8; TAG_SIZE] {
+    XChaCha20Poly1305::new(&Key::from(*key))
+        .encrypt_inout_detached(&XNonce::from(*nonce), aad, data.into())
+        // Only fails for messages longer than 256 GB
+        .expect("page too large to encrypt")
+        .into()
+}
+
+/// Verify the authentication tag of `data` and decrypt it in place.
+/// Returns false and leaves `data` unchanged, if verification fails.
+pub fn open(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    data: &mut [u8],
+    expected: &[u8],
+) -> bool {
+    let tag = match Tag::try_from(expected) {
+        Ok(tag) => tag,
+        Err(_) => return false,
+    };
+    XChaCha20Poly1305::new(&Key::from(*key))
+        .decrypt_inout_detached(&XNonce::from(*nonce), aad, data.into(), &tag)
+        .is_ok()
+}
+
+/// Derive a subkey unique to `salt` from a key with HKDF-SHA256
+pub fn derive_key(key: &[u8; KEY_SIZE], salt: &[u8]) -> [u8; KEY_SIZE] {
+    let mut out = [0; KEY_SIZE];
+    Hkdf::<Sha256>::new(Some(salt), key)
+        .expand(KDF_INFO, &mut out)
+        .expect("key size within HKDF output limit");
+    out
+}
+
+/// Fill a buffer with random bytes from the operating system
+pub fn random_bytes(buf: &mut [u8]) -> io::Result<()> {
+    getrandom::fill(buf).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn key() -> [u8; KEY_SIZE] {
+        let mut key = [0; KEY_SIZE];
+        for (i, b) in key.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        key
+    }
+
+    #[test]
+    fn derived_keys() {
+        let k = derive_key(&key(), b"salt 1");
+        assert_eq!(k, derive_key(&key(), b"salt 1"));
+        assert_ne!(k, derive_key(&key(), b"salt 2"));
+        assert_ne!(k, derive_key(&[0; KEY_SIZE], b"salt 1"));
+        assert_ne!(k, key());
+    }
+
+    #[test]
+    fn random() {
+        let mut a = [0; 32];
+        let mut b = [0; 32];
+        random_bytes(&mut a).unwrap();
+        random_bytes(&mut b).unwrap();
+        assert_ne!(a, b);
+    }
+
+    // draft-irtf-cfrg-xchacha-03 appendix A.3.1
+    #[test]
+    fn aead() {
+        let key = hex(
+            "808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f",
+        );
+        let key: [u8; KEY_SIZE] = key[..].try_into().unwrap();
+        let nonce = hex("404142434445464748494a4b4c4d4e4f5051525354555657");
+        let nonce: [u8; NONCE_SIZE] = nonce[..].try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let plain: &[u8] = b"Ladies and Gentlemen of the class of '99: If I \
+            could offer you only one tip for the future, sunscreen would be it.";
+
+        let mut data = plain.to_vec();
+        let tag = seal(&key, &nonce, &aad, &mut data);
+        assert_eq!(
+            data,
+            hex("bd6d179d3e83d43b9576579493c0e939572a1700252bfaccbed2902c21396cbb
+                 731c7f1b0b4aa6440bf3a82f4eda7e39ae64c6708c54c216cb96b72e1213b452
+                 2f8c9ba40db5d945b11b69b982c1bb9e3f3fac2bc369488f76b2383565d3fff9
+                 21f9664c97637da9768812f615c68b13b52e")
+        );
+        assert_eq!(tag[..], hex("c0875924c1c7987947deafd8780acf49")[..]);
+
+        assert!(!open(&key, &nonce, b"other", &mut data, &tag));
+        assert!(open(&key, &nonce, &aad, &mut data, &tag));
+        assert_eq!(data, plain);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    ptr::null_mut,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Size of a huge page in bytes
+pub const HUGE_PAGE_SIZE: usize = 2 << 20;
+
+/// Huge page backed memory region, that page buffers are carved from.
+///
+/// Slots are handed out sequentially and never reused by the arena itself.
+/// Buffers carved from the arena are expected to be recycled by the allocator's
+/// free page pool instead. The region is unmapped, once the arena and all
+/// buffers referencing it are dropped.
+pub struct Arena {
+    /// Start of the mapped region. Aligned to `HUGE_PAGE_SIZE`.
+    ptr: *mut u8,
+
+    /// Size of each carved slot
+    slot_size: usize,
+
+    /// Number of slots already carved
+    carved: AtomicUsize,
+}
+
+impl Arena {
+    /// Map a new arena for carving `slot_size` sized slots.
+    /// Returns None, if the operating system could not provide the memory.
+    ///
+    /// Tries explicitly reserved huge pages first and falls back to transparent
+    /// huge pages.
+    pub fn new(slot_size: usize) -> Option<Self> {
+        debug_assert!(slot_size <= HUGE_PAGE_SIZE);
+        let ptr = unsafe { Self::map_hugetlb() }
+            .or_else(|| unsafe { Self::map_transparent() })?;
+        Some(Self {
+            ptr,
+            slot_size,
+            carved: AtomicUsize::new(0),
+        })
+    }
+
+    /// Map memory from the reserved huge page pool
+    unsafe fn map_hugetlb() -> Option<*mut u8> {
+        let ptr = libc::mmap(
+            null_mut(),
+            HUGE_PAGE_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB,
+            -1,
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            None
+        } else {
+            Some(ptr as *mut u8)
+        }
+    }
+
+    /// Map regular memory aligned to a huge page boundary and advise the
+    /// kernel to back it with transparent huge pages
+    unsafe fn map_transparent() -> Option<*mut u8> {
+        // Over-allocate to be able to align the region
+        let ptr = libc::mmap(
+            null_mut(),
+            2 * HUGE_PAGE_SIZE,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+
+        // Trim the unaligned head and tail
+        let start = ptr as usize;
+        let aligned = (start + HUGE_PAGE_SIZE - 1) & !(HUGE_PAGE_SIZE - 1);
+        if aligned != start {
+            libc::munmap(ptr, aligned - start);
+        }
+        let tail = aligned + HUGE_PAGE_SIZE;
+        let end = start + 2 * HUGE_PAGE_SIZE;
+        if tail != end {
+            libc::munmap(tail as *mut libc::c_void, end - tail);
+        }
+
+        // Only a hint. The region is still usable without huge pages.
+        libc::madvise(
+            aligned as *mut libc::c_void,
+            HUGE_PAGE_SIZE,
+            libc::MADV_HUGEPAGE,
+        );
+        Some(aligned as *mut u8)
+    }
+
+    /// Carve the next unused slot out of the arena.
+    /// Returns None, if the arena is exhausted.
+    ///
+    /// Slots are zeroed on first use.
+    pub fn carve(&self) -> Option<*mut u8> {
+        let i = self
+            .carved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |i| {
+                ((i + 1) * self.slot_size <= HUGE_PAGE_SIZE).then_some(i + 1)
+            })
+            .ok()?;
+        Some(unsafe { self.ptr.add(i * self.slot_size) })
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, HUGE_PAGE_SIZE);
+        }
+    }
+}
+
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
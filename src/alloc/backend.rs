@@ -0,0 +1,204 @@
+//! Sources of page buffer memory selected per platform
+
+use std::{alloc::Layout, ptr::null_mut};
+
+/// Alignment of all page buffers in bytes.
+///
+/// Keeps pages cache line and I/O block aligned, as required for O_DIRECT
+/// writes.
+pub const BUFFER_ALIGN: usize = 4 << 10;
+
+/// Allocates and frees zeroed page-sized buffers
+pub trait Backend {
+    /// Allocate a zeroed buffer of `size` bytes.
+    /// Returns null, if the allocation failed.
+    fn allocate_zeroed(size: usize) -> *mut u8;
+
+    /// Free a buffer previously returned by `allocate_zeroed()` with the same
+    /// `size`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must not be null and not be used after this call.
+    unsafe fn deallocate(ptr: *mut u8, size: usize);
+}
+
+/// Allocates `BUFFER_ALIGN` aligned buffers through the Rust global
+/// allocator. Available on all platforms.
+#[cfg_attr(any(miri, feature = "debug-alloc"), allow(dead_code))]
+pub struct Std;
+
+#[cfg_attr(any(miri, feature = "debug-alloc"), allow(dead_code))]
+impl Std {
+    #[inline]
+    fn layout(size: usize) -> Option<Layout> {
+        Layout::from_size_align(size, BUFFER_ALIGN).ok()
+    }
+}
+
+impl Backend for Std {
+    fn allocate_zeroed(size: usize) -> *mut u8 {
+        match Self::layout(size) {
+            Some(l) if size != 0 => unsafe { std::alloc::alloc_zeroed(l) },
+            _ => null_mut(),
+        }
+    }
+
+    unsafe fn deallocate(ptr: *mut u8, size: usize) {
+        std::alloc::dealloc(ptr, Self::layout(size).unwrap());
+    }
+}
+
+/// Allocates buffers as plain heap allocated slices surrounded by guard
+/// regions, that are verified on free, and poisons freed memory.
+///
+/// Slow, but lets Miri and sanitizers track buffer memory like any other Rust
+/// allocation and catches out of bounds writes missed by them. Available on all
+/// platforms.
+pub struct Debug;
+
+/// Unit of `Debug` allocations keeping buffers `BUFFER_ALIGN` aligned
+#[repr(C, align(4096))]
+struct Block([u8; BUFFER_ALIGN]);
+
+impl Debug {
+    /// Fill value of guard regions
+    const GUARD: u8 = 0xab;
+
+    /// Fill value of freed memory
+    pub const POISON: u8 = 0xdd;
+
+    /// Returns the number of blocks backing a buffer of `size` bytes
+    /// including a leading and trailing guard block
+    #[inline]
+    fn blocks(size: usize) -> usize {
+        size.div_ceil(BUFFER_ALIGN) + 2
+    }
+}
+
+impl Backend for Debug {
+    fn allocate_zeroed(size: usize) -> *mut u8 {
+        if size == 0 {
+            return null_mut();
+        }
+        let blocks: Box<[Block]> = (0..Self::blocks(size))
+            .map(|_| Block([Self::GUARD; BUFFER_ALIGN]))
+            .collect();
+        unsafe {
+            let ptr = (Box::into_raw(blocks) as *mut u8).add(BUFFER_ALIGN);
+            ptr.write_bytes(0, size);
+            ptr
+        }
+    }
+
+    unsafe fn deallocate(ptr: *mut u8, size: usize) {
+        let n = Self::blocks(size);
+        let base = ptr.sub(BUFFER_ALIGN);
+        let (head, rest) =
+            std::slice::from_raw_parts_mut(base, n * BUFFER_ALIGN)
+                .split_at_mut(BUFFER_ALIGN);
+        let (data, tail) = rest.split_at_mut(size);
+        assert!(
+            head.iter().all(|b| *b == Self::GUARD),
+            "write before start of buffer {:p}",
+            ptr
+        );
+        assert!(
+            tail.iter().all(|b| *b == Self::GUARD),
+            "write past end of buffer {:p}",
+            ptr
+        );
+        data.fill(Self::POISON);
+
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            base as *mut Block,
+            n,
+        )));
+    }
+}
+
+/// Fails allocations injected with `sim::inject()` and delegates everything
+/// else to `B`. Only available in tests.
+#[cfg(test)]
+pub struct Faulty<B>(std::marker::PhantomData<B>);
+
+#[cfg(test)]
+impl<B: Backend> Backend for Faulty<B> {
+    fn allocate_zeroed(size: usize) -> *mut u8 {
+        if super::sim::triggered(super::sim::Fault::Malloc) {
+            return null_mut();
+        }
+        B::allocate_zeroed(size)
+    }
+
+    unsafe fn deallocate(ptr: *mut u8, size: usize) {
+        B::deallocate(ptr, size)
+    }
+}
+
+/// Backend page buffer memory comes from on the target platform
+#[cfg(not(any(miri, feature = "debug-alloc")))]
+type Native = Std;
+
+/// Backend page buffer memory comes from on the target platform
+#[cfg(any(miri, feature = "debug-alloc"))]
+type Native = Debug;
+
+/// Backend used for page buffers on the target platform
+#[cfg(not(test))]
+pub type Platform = Native;
+
+/// Backend used for page buffers on the target platform
+#[cfg(test)]
+pub type Platform = Faulty<Native>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate_and_free<B: Backend>() {
+        for size in [1 << 10, 4 << 10, 64 << 10] {
+            let ptr = B::allocate_zeroed(size);
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % BUFFER_ALIGN, 0);
+            unsafe {
+                let buf = std::slice::from_raw_parts_mut(ptr, size);
+                assert!(buf.iter().all(|b| *b == 0));
+                buf.fill(1);
+                B::deallocate(ptr, size);
+            }
+        }
+    }
+
+    #[test]
+    fn std() {
+        allocate_and_free::<Std>();
+        assert!(Std::allocate_zeroed(0).is_null());
+    }
+
+    #[test]
+    fn debug() {
+        allocate_and_free::<Debug>();
+        assert!(Debug::allocate_zeroed(0).is_null());
+    }
+
+    #[test]
+    fn faulty() {
+        use crate::alloc::sim::{self, Fault};
+
+        sim::inject(Fault::Malloc, 1);
+        assert!(Faulty::<Std>::allocate_zeroed(1 << 10).is_null());
+        allocate_and_free::<Faulty<Std>>();
+    }
+
+    #[test]
+    #[should_panic(expected = "write past end of buffer")]
+    fn debug_overflow() {
+        let size = 1 << 10;
+        let ptr = Debug::allocate_zeroed(size);
+        unsafe {
+            *ptr.add(size) = 1;
+            Debug::deallocate(ptr, size);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::free_list::AllocationResult;
+use std::collections::{BTreeSet, HashMap};
+
+/// Size of the smallest block in bytes
+pub const MIN_BLOCK: usize = 16;
+
+/// Power-of-two buddy allocator for keeping track of free memory in a page.
+///
+/// Allocations are rounded up to the next power of two, so memory is wasted
+/// for sizes in between, but allocating and freeing take O(log n) time and
+/// freed blocks are merged with their free buddies immediately. Suited to
+/// pages holding many small objects of uniform size.
+pub struct Buddy {
+    /// Offsets of free blocks by order. Blocks of order k are `MIN_BLOCK << k`
+    /// bytes.
+    ///
+    /// Ordered, so the lowest free block is allocated first.
+    free: Vec<BTreeSet<usize>>,
+
+    /// Orders of allocated blocks by offset
+    allocated: HashMap<usize, usize>,
+}
+
+impl Buddy {
+    /// Creates a new `Buddy` allocator with the passed capacity, that must be
+    /// a power of two and at least `MIN_BLOCK`
+    pub fn new(cap: usize) -> Self {
+        assert!(
+            cap.is_power_of_two() && cap >= MIN_BLOCK,
+            "invalid buddy allocator capacity: {}",
+            cap
+        );
+        let orders = Self::order(cap) + 1;
+        let mut free = vec![BTreeSet::new(); orders];
+        free[orders - 1].insert(0);
+        Self {
+            free,
+            allocated: HashMap::new(),
+        }
+    }
+
+    /// Returns the order of the smallest block, that can hold `size` bytes
+    #[inline]
+    fn order(size: usize) -> usize {
+        let block = size.max(MIN_BLOCK).next_power_of_two();
+        (block / MIN_BLOCK).trailing_zeros() as usize
+    }
+
+    /// Returns the size of a block of `order` in bytes
+    #[inline]
+    fn block_size(order: usize) -> usize {
+        MIN_BLOCK << order
+    }
+
+    /// Allocate a block large enough for `size` bytes and return its offset.
+    ///
+    /// The offset is aligned to the block size.
+    pub fn allocate(&mut self, size: usize) -> AllocationResult {
+        let order = Self::order(size);
+        let found =
+            (order..self.free.len()).find(|o| !self.free[*o].is_empty());
+        let mut current = match found {
+            Some(o) => o,
+            None => return AllocationResult::NotFound(self.largest_free()),
+        };
+
+        // Split larger blocks, keeping the upper halves free
+        let offset = self.free[current].pop_first().unwrap();
+        while current > order {
+            current -= 1;
+            self.free[current].insert(offset + Self::block_size(current));
+        }
+        self.allocated.insert(offset, order);
+        AllocationResult::Allocated(offset)
+    }
+
+    /// Free a block allocated for `size` bytes at `offset` and merge it with
+    /// its buddies, while they are free
+    pub fn free(
+        &mut self,
+        mut offset: usize,
+        size: usize,
+    ) -> Result<(), &'static str> {
+        let mut order = match self.allocated.get(&offset) {
+            Some(o) if size <= Self::block_size(*o) => *o,
+            _ => return Err("block not allocated"),
+        };
+        self.allocated.remove(&offset);
+
+        while order + 1 < self.free.len() {
+            let buddy = offset ^ Self::block_size(order);
+            if !self.free[order].remove(&buddy) {
+                break;
+            }
+            offset = offset.min(buddy);
+            order += 1;
+        }
+        self.free[order].insert(offset);
+        Ok(())
+    }
+
+    /// Returns the total size of all free blocks in bytes
+    #[cfg(test)]
+    pub fn free_bytes(&self) -> usize {
+        self.free
+            .iter()
+            .enumerate()
+            .map(|(order, blocks)| blocks.len() * Self::block_size(order))
+            .sum()
+    }
+
+    /// Returns the size of the largest free block in bytes
+    pub fn largest_free(&self) -> usize {
+        self.free
+            .iter()
+            .rposition(|blocks| !blocks.is_empty())
+            .map(Self::block_size)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(b: &mut Buddy, size: usize) -> usize {
+        match b.allocate(size) {
+            AllocationResult::Allocated(offset) => offset,
+            AllocationResult::NotFound(_) => panic!("no space for {}", size),
+        }
+    }
+
+    #[test]
+    fn order() {
+        assert_eq!(Buddy::order(0), 0);
+        assert_eq!(Buddy::order(MIN_BLOCK), 0);
+        assert_eq!(Buddy::order(MIN_BLOCK + 1), 1);
+        assert_eq!(Buddy::order(4 << 10), 8);
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let mut b = Buddy::new(1 << 10);
+        assert_eq!(allocate(&mut b, 100), 0);
+        assert_eq!(allocate(&mut b, 10), 128);
+        assert_eq!(allocate(&mut b, 10), 144);
+        assert_eq!(allocate(&mut b, 64), 192);
+        assert_eq!(b.free_bytes(), 1024 - 128 - 16 - 16 - 64);
+        assert_eq!(b.largest_free(), 512);
+
+        // Blocks are aligned to their size
+        assert_eq!(allocate(&mut b, 200), 256);
+        assert!(matches!(b.allocate(513), AllocationResult::NotFound(512)));
+
+        for (offset, size) in [(144, 10), (0, 100), (192, 64), (256, 200)] {
+            b.free(offset, size).unwrap();
+        }
+        assert_eq!(b.largest_free(), 512);
+        b.free(128, 10).unwrap();
+        assert_eq!(b.largest_free(), 1 << 10);
+        assert_eq!(b.free_bytes(), 1 << 10);
+    }
+
+    #[test]
+    fn invalid_free() {
+        let mut b = Buddy::new(1 << 10);
+        let offset = allocate(&mut b, 32);
+        assert!(b.free(offset + 16, 16).is_err());
+        assert!(b.free(offset, 64).is_err());
+        b.free(offset, 32).unwrap();
+        assert!(b.free(offset, 32).is_err());
+    }
+
+    #[test]
+    fn uniform_objects() {
+        let mut b = Buddy::new(4 << 10);
+        let offsets: Vec<_> = (0..256).map(|_| allocate(&mut b, 16)).collect();
+        assert!(matches!(b.allocate(1), AllocationResult::NotFound(0)));
+        for offset in offsets.into_iter().rev() {
+            b.free(offset, 16).unwrap();
+        }
+        assert_eq!(b.largest_free(), 4 << 10);
+    }
+}
@@ -0,0 +1,118 @@
+use super::{AllocError, Page};
+
+/// Allocation larger than a page spread over a chain of pages and accessed
+/// through a single handle.
+///
+/// Acquired with `Allocator::get_chain()`. The pages are swapped in and out
+/// independently of each other.
+pub struct PageChain {
+    pages: Vec<Page>,
+
+    /// Size of each page in bytes
+    page_size: usize,
+
+    /// Size of the allocation in bytes
+    size: usize,
+}
+
+impl PageChain {
+    /// Construct a chain of `size` bytes from enough pages of `page_size` to
+    /// hold it
+    pub(super) fn new(pages: Vec<Page>, page_size: usize, size: usize) -> Self {
+        debug_assert_eq!(pages.len(), Self::page_count(page_size, size));
+        Self {
+            pages,
+            page_size,
+            size,
+        }
+    }
+
+    /// Returns the number of pages needed to hold `size` bytes
+    #[inline]
+    pub(super) fn page_count(page_size: usize, size: usize) -> usize {
+        size.div_ceil(page_size)
+    }
+
+    /// Returns the size of the allocation in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the pages of the chain in order
+    #[inline]
+    pub fn pages(&self) -> &[Page] {
+        &self.pages
+    }
+
+    /// Copy `buf.len()` bytes starting at `offset` into `buf`.
+    ///
+    /// Panics, if the range exceeds the allocation.
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<(), AllocError> {
+        let mut done = 0;
+        for (page, start, len) in self.spans(offset, buf.len()) {
+            buf[done..done + len]
+                .copy_from_slice(&page.read()?[start..start + len]);
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Copy `data` into the allocation starting at `offset`.
+    ///
+    /// Panics, if the range exceeds the allocation.
+    pub fn write_at(
+        &self,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<(), AllocError> {
+        let mut done = 0;
+        for (page, start, len) in self.spans(offset, data.len()) {
+            page.write()?[start..start + len]
+                .copy_from_slice(&data[done..done + len]);
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Copy the entire allocation into a new vector
+    pub fn to_vec(&self) -> Result<Vec<u8>, AllocError> {
+        let mut buf = vec![0; self.size];
+        self.read_at(0, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Split a range of the allocation into the page, offset in the page and
+    /// length of each of its parts
+    fn spans(
+        &self,
+        offset: usize,
+        len: usize,
+    ) -> impl Iterator<Item = (&Page, usize, usize)> {
+        assert!(
+            offset.checked_add(len).is_some_and(|end| end <= self.size),
+            "range {}+{} out of bounds of allocation of {} bytes",
+            offset,
+            len,
+            self.size
+        );
+
+        let page_size = self.page_size;
+        let end = offset + len;
+        let mut pos = offset;
+        std::iter::from_fn(move || {
+            if pos >= end {
+                return None;
+            }
+            let start = pos % page_size;
+            let len = (page_size - start).min(end - pos);
+            let page = &self.pages[pos / page_size];
+            pos += len;
+            Some((page, start, len))
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use super::PageId;
+use std::{fmt, io, sync::PoisonError};
+
+/// Error returned by page allocator operations
+#[derive(Debug)]
+pub enum AllocError {
+    /// Failed to allocate memory from the operating system
+    OutOfMemory,
+
+    /// Resident memory budget is exhausted and no pages could be swapped out
+    /// to make room
+    BudgetExceeded,
+
+    /// Reading from or writing to the spill file failed
+    SpillIo(io::Error),
+
+    /// Compressing or decompressing page data failed
+    Compression(io::Error),
+
+    /// A lock was poisoned by a thread panicking while holding it
+    Poisoned,
+
+    /// Page is not stored in any allocator tier
+    PageNotFound(PageId),
+
+    /// Checksum of a page loaded back into resident memory does not match the
+    /// one computed, when it was swapped out
+    Corrupted(PageId),
+
+    /// Resident memory quota of the named allocation scope is exhausted and
+    /// none of its pages could be swapped out to make room
+    QuotaExceeded(String),
+
+    /// Acquiring a page would require waiting for memory to be freed
+    WouldBlock,
+
+    /// Allocator configuration is invalid or can not be applied
+    InvalidConfig(String),
+
+    /// Page has no free range large enough for a sub-page allocation
+    PageFull(PageId),
+
+    /// Sub-page allocation does not belong to the page or has already been
+    /// freed
+    InvalidSlot(PageId),
+
+    /// Handle refers to a sub-page allocation, that has since been freed
+    StaleHandle(PageId),
+
+    /// Allocator has been shut down with `Allocator::shutdown()`
+    ShutDown,
+
+    /// Registering a memory pressure listener failed
+    Pressure(io::Error),
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfMemory => write!(f, "out of memory"),
+            Self::BudgetExceeded => {
+                write!(f, "resident memory budget exceeded")
+            }
+            Self::SpillIo(err) => write!(f, "spill file I/O: {}", err),
+            Self::Compression(err) => write!(f, "page compression: {}", err),
+            Self::Poisoned => write!(f, "lock poisoned"),
+            Self::PageNotFound(id) => write!(f, "page {} not found", id),
+            Self::Corrupted(id) => write!(f, "page {} data corrupted", id),
+            Self::QuotaExceeded(name) => {
+                write!(f, "memory quota of scope {} exceeded", name)
+            }
+            Self::WouldBlock => write!(f, "operation would block"),
+            Self::InvalidConfig(msg) => {
+                write!(f, "invalid allocator configuration: {}", msg)
+            }
+            Self::PageFull(id) => write!(f, "page {} full", id),
+            Self::InvalidSlot(id) => {
+                write!(f, "invalid sub-page allocation in page {}", id)
+            }
+            Self::StaleHandle(id) => {
+                write!(f, "stale sub-page allocation handle in page {}", id)
+            }
+            Self::ShutDown => write!(f, "allocator shut down"),
+            Self::Pressure(err) => write!(f, "memory pressure: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AllocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::SpillIo(err)
+            | Self::Compression(err)
+            | Self::Pressure(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for AllocError {
+    #[inline]
+    fn from(_: PoisonError<T>) -> Self {
+        Self::Poisoned
+    }
+}
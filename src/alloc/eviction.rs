@@ -0,0 +1,378 @@
+use super::{lru_map::LRUMap, sim, PageId};
+use std::collections::HashSet;
+
+/// Decides the order pages are swapped out in, when the allocator needs to
+/// free resident memory.
+///
+/// Pages not used for long periods of time are swapped out based on their age
+/// regardless of the policy.
+pub trait EvictionPolicy: Send {
+    /// Page was acquired from the allocator
+    fn insert(&mut self, id: PageId);
+
+    /// Page was accessed or loaded back into resident memory
+    fn access(&mut self, id: PageId);
+
+    /// Page was swapped out of resident memory
+    fn evict(&mut self, id: PageId);
+
+    /// Page was released back to the allocator
+    fn remove(&mut self, id: PageId);
+
+    /// Return the IDs of all tracked pages ordered from the best to the worst
+    /// candidate for swapping out.
+    ///
+    /// May update the policy's internal state.
+    fn candidates(&mut self) -> Vec<PageId>;
+}
+
+/// Built-in eviction policies selectable through `AllocatorConfig`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// Least recently used pages first
+    Lru,
+
+    /// Approximation of LRU giving recently referenced pages a second chance
+    Clock,
+
+    /// Adaptive replacement cache balancing recency and frequency
+    Arc,
+
+    /// Pages referenced only once are evicted before frequently used ones
+    TwoQ,
+}
+
+impl Default for Eviction {
+    #[inline]
+    fn default() -> Self {
+        Self::Lru
+    }
+}
+
+impl Eviction {
+    /// Construct a new instance of the policy
+    pub fn build(self) -> Box<dyn EvictionPolicy> {
+        match self {
+            Self::Lru => Box::new(Lru::default()),
+            Self::Clock => Box::new(Clock::default()),
+            Self::Arc => Box::new(Arc::default()),
+            Self::TwoQ => Box::new(TwoQ::default()),
+        }
+    }
+}
+
+impl Default for Box<dyn EvictionPolicy> {
+    #[inline]
+    fn default() -> Self {
+        Eviction::default().build()
+    }
+}
+
+/// Iterate the keys of an `LRUMap` from the least to the most recently used
+fn keys(m: &mut LRUMap<PageId>) -> impl Iterator<Item = PageId> + '_ {
+    m.iter().map(|(id, _)| id)
+}
+
+/// Evicts least recently used pages first
+#[derive(Default)]
+pub struct Lru {
+    pages: LRUMap<PageId>,
+}
+
+impl EvictionPolicy for Lru {
+    fn insert(&mut self, id: PageId) {
+        self.pages.insert(id, sim::now());
+    }
+
+    fn access(&mut self, id: PageId) {
+        self.pages.bump(&id, sim::now());
+    }
+
+    fn evict(&mut self, _: PageId) {}
+
+    fn remove(&mut self, id: PageId) {
+        self.pages.remove(&id);
+    }
+
+    fn candidates(&mut self) -> Vec<PageId> {
+        keys(&mut self.pages).collect()
+    }
+}
+
+/// Sweeps pages in a circle, evicting pages not referenced since the last
+/// sweep and clearing the reference bit of the rest
+#[derive(Default)]
+pub struct Clock {
+    /// Pages in sweeping order starting at the clock hand
+    ring: LRUMap<PageId>,
+
+    /// Pages referenced since they were last swept
+    referenced: HashSet<PageId>,
+}
+
+impl EvictionPolicy for Clock {
+    fn insert(&mut self, id: PageId) {
+        self.ring.insert(id, sim::now());
+    }
+
+    fn access(&mut self, id: PageId) {
+        if self.ring.contains(&id) {
+            self.referenced.insert(id);
+        }
+    }
+
+    fn evict(&mut self, id: PageId) {
+        self.referenced.remove(&id);
+    }
+
+    fn remove(&mut self, id: PageId) {
+        self.ring.remove(&id);
+        self.referenced.remove(&id);
+    }
+
+    fn candidates(&mut self) -> Vec<PageId> {
+        let referenced = &self.referenced;
+        let (mut unreferenced, referenced): (Vec<_>, Vec<_>) =
+            keys(&mut self.ring).partition(|id| !referenced.contains(id));
+
+        // Give referenced pages a second chance by moving them behind the
+        // hand
+        let now = sim::now();
+        for id in referenced.iter() {
+            self.referenced.remove(id);
+            self.ring.insert(*id, now);
+        }
+
+        unreferenced.extend(referenced);
+        unreferenced
+    }
+}
+
+/// Adaptive replacement cache.
+///
+/// Resident pages are split between pages used once recently and pages used
+/// at least twice. Swapped out pages are remembered in ghost lists, and
+/// accessing them adapts the target size of each resident list.
+#[derive(Default)]
+pub struct Arc {
+    /// Resident pages used once recently
+    t1: LRUMap<PageId>,
+
+    /// Resident pages used at least twice recently
+    t2: LRUMap<PageId>,
+
+    /// Pages swapped out of `t1`
+    b1: LRUMap<PageId>,
+
+    /// Pages swapped out of `t2`
+    b2: LRUMap<PageId>,
+
+    /// Target size of `t1`
+    p: usize,
+}
+
+impl EvictionPolicy for Arc {
+    fn insert(&mut self, id: PageId) {
+        self.t1.insert(id, sim::now());
+    }
+
+    fn access(&mut self, id: PageId) {
+        let resident = self.t1.len() + self.t2.len();
+        let hit =
+            self.t1.remove(&id).is_some() || self.t2.remove(&id).is_some();
+        if !hit {
+            if self.b1.remove(&id).is_some() {
+                // Recency list was too small
+                let delta = (self.b2.len() / (self.b1.len() + 1)).max(1);
+                self.p = (self.p + delta).min(resident + 1);
+            } else if self.b2.remove(&id).is_some() {
+                // Frequency list was too small
+                let delta = (self.b1.len() / (self.b2.len() + 1)).max(1);
+                self.p = self.p.saturating_sub(delta);
+            } else {
+                return;
+            }
+        }
+        self.t2.insert(id, sim::now());
+    }
+
+    fn evict(&mut self, id: PageId) {
+        let now = sim::now();
+        if self.t1.remove(&id).is_some() {
+            self.b1.insert(id, now);
+        } else if self.t2.remove(&id).is_some() {
+            self.b2.insert(id, now);
+        }
+    }
+
+    fn remove(&mut self, id: PageId) {
+        for l in [&mut self.t1, &mut self.t2, &mut self.b1, &mut self.b2] {
+            l.remove(&id);
+        }
+    }
+
+    fn candidates(&mut self) -> Vec<PageId> {
+        // Swapped out pages are only candidates for further swapping out
+        let mut ids: Vec<_> = keys(&mut self.b1).collect();
+        ids.extend(keys(&mut self.b2));
+        if self.t1.len() > self.p {
+            ids.extend(keys(&mut self.t1));
+            ids.extend(keys(&mut self.t2));
+        } else {
+            ids.extend(keys(&mut self.t2));
+            ids.extend(keys(&mut self.t1));
+        }
+        ids
+    }
+}
+
+/// Two queue policy.
+///
+/// New pages enter a FIFO queue and are only promoted to the LRU queue of hot
+/// pages, when accessed again after being swapped out. Keeps pages scanned
+/// once from flushing out frequently used ones.
+#[derive(Default)]
+pub struct TwoQ {
+    /// FIFO queue of newly acquired pages
+    a1in: LRUMap<PageId>,
+
+    /// LRU queue of hot pages
+    am: LRUMap<PageId>,
+
+    /// Swapped out pages
+    out: LRUMap<PageId>,
+}
+
+impl EvictionPolicy for TwoQ {
+    fn insert(&mut self, id: PageId) {
+        self.a1in.insert(id, sim::now());
+    }
+
+    fn access(&mut self, id: PageId) {
+        // Accesses to pages in `a1in` are considered correlated and do not
+        // move them
+        if self.am.contains(&id) || self.out.remove(&id).is_some() {
+            self.am.insert(id, sim::now());
+        }
+    }
+
+    fn evict(&mut self, id: PageId) {
+        if self.a1in.remove(&id).is_some() || self.am.remove(&id).is_some() {
+            self.out.insert(id, sim::now());
+        }
+    }
+
+    fn remove(&mut self, id: PageId) {
+        for l in [&mut self.a1in, &mut self.am, &mut self.out] {
+            l.remove(&id);
+        }
+    }
+
+    fn candidates(&mut self) -> Vec<PageId> {
+        let mut ids: Vec<_> = keys(&mut self.out).collect();
+
+        // Keep a quarter of resident pages for new ones
+        let kin = (self.a1in.len() + self.am.len()) / 4;
+        if self.a1in.len() > kin {
+            ids.extend(keys(&mut self.a1in));
+            ids.extend(keys(&mut self.am));
+        } else {
+            ids.extend(keys(&mut self.am));
+            ids.extend(keys(&mut self.a1in));
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(e: Eviction, n: PageId) -> Box<dyn EvictionPolicy> {
+        let mut p = e.build();
+        for id in 0..n {
+            p.insert(id);
+        }
+        p
+    }
+
+    #[test]
+    fn all_pages_tracked() {
+        for e in [
+            Eviction::Lru,
+            Eviction::Clock,
+            Eviction::Arc,
+            Eviction::TwoQ,
+        ] {
+            let mut p = policy(e, 16);
+            for id in (0..16).step_by(3) {
+                p.access(id);
+            }
+            for id in (0..16).step_by(4) {
+                p.evict(id);
+            }
+            p.remove(5);
+            p.access(5);
+
+            let mut ids = p.candidates();
+            ids.sort_unstable();
+            let expected: Vec<_> = (0..16).filter(|id| *id != 5).collect();
+            assert_eq!(ids, expected, "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn lru() {
+        let mut p = policy(Eviction::Lru, 4);
+        p.access(0);
+        p.access(2);
+        assert_eq!(p.candidates(), [1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn clock() {
+        let mut p = policy(Eviction::Clock, 4);
+        p.access(0);
+        p.access(2);
+        assert_eq!(p.candidates(), [1, 3, 0, 2]);
+
+        // Reference bits are cleared by the sweep
+        p.access(1);
+        assert_eq!(p.candidates(), [3, 0, 2, 1]);
+    }
+
+    #[test]
+    fn arc() {
+        let mut p = policy(Eviction::Arc, 4);
+
+        // Pages used twice are kept over pages used once
+        p.access(0);
+        p.access(1);
+        assert_eq!(p.candidates(), [2, 3, 0, 1]);
+
+        // Swapped out pages go first
+        p.evict(2);
+        assert_eq!(p.candidates(), [2, 3, 0, 1]);
+
+        // Accessing a page swapped out of the recency list grows its target
+        // size, which protects the remaining pages used once
+        p.access(2);
+        assert_eq!(p.candidates(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn two_q() {
+        let mut p = policy(Eviction::TwoQ, 8);
+
+        // Correlated accesses do not promote new pages
+        p.access(0);
+        assert_eq!(p.candidates(), [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        // Pages accessed after being swapped out are promoted
+        p.evict(0);
+        p.evict(1);
+        assert_eq!(p.candidates(), [0, 1, 2, 3, 4, 5, 6, 7]);
+        p.access(0);
+        assert_eq!(p.candidates(), [1, 2, 3, 4, 5, 6, 7, 0]);
+    }
+}
@@ -0,0 +1,311 @@
+//! Lock-free stack for passing unused buffers between threads without taking
+//! the allocator lock
+
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+};
+
+/// Index of no node
+const NIL: u32 = u32::MAX;
+
+/// Number of nodes in the first chunk. Each following chunk is twice the size
+/// of the previous one.
+const FIRST_CHUNK: u32 = 1 << 5;
+
+/// Number of chunks needed to address almost all indices below `NIL`
+const CHUNKS: usize = 27;
+
+/// Maximum number of nodes in all chunks
+const CAPACITY: u32 = FIRST_CHUNK * ((1 << CHUNKS) - 1);
+
+/// Stack node. Nodes are only freed together with the stack, so popping
+/// threads can always safely read the `next` field of a node another thread
+/// has already popped.
+struct Node<T> {
+    /// Index of the next node in the list
+    next: AtomicU32,
+
+    /// Only initialized, while the node is on the list of used nodes
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Pack the head of a list from its node index and ABA tag
+#[inline]
+fn pack(tag: u32, index: u32) -> u64 {
+    (tag as u64) << 32 | index as u64
+}
+
+/// Unpack the ABA tag and node index from the head of a list
+#[inline]
+fn unpack(head: u64) -> (u32, u32) {
+    ((head >> 32) as u32, head as u32)
+}
+
+/// Treiber stack with nodes addressed by index in chunked storage.
+///
+/// List heads are tagged with a counter incremented on every update, so a
+/// head modified and restored by other threads between a thread's load and
+/// compare-exchange is not mistaken for unchanged.
+pub struct FreeStack<T> {
+    /// Nodes holding values
+    used: AtomicU64,
+
+    /// Nodes available for reuse
+    unused: AtomicU64,
+
+    /// Lazily allocated chunks of nodes
+    chunks: [AtomicPtr<Node<T>>; CHUNKS],
+
+    /// Number of nodes ever allocated
+    allocated: AtomicU32,
+
+    /// Number of values on the stack
+    len: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for FreeStack<T> {}
+unsafe impl<T: Send> Sync for FreeStack<T> {}
+
+impl<T> Default for FreeStack<T> {
+    fn default() -> Self {
+        Self {
+            used: AtomicU64::new(pack(0, NIL)),
+            unused: AtomicU64::new(pack(0, NIL)),
+            chunks: Default::default(),
+            allocated: AtomicU32::new(0),
+            len: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T> FreeStack<T> {
+    /// Returns the number of values on the stack.
+    ///
+    /// Only exact, while no values are concurrently pushed or popped.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Push a value to the top of the stack
+    pub fn push(&self, value: T) {
+        let i = match self.pop_node(&self.unused) {
+            Some(i) => i,
+            None => self.allocate_node(),
+        };
+
+        // The node is owned by this thread, until pushed to a list
+        unsafe { (*self.node(i).value.get()).write(value) };
+        self.push_node(&self.used, i);
+        self.len.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Pop the value from the top of the stack
+    pub fn pop(&self) -> Option<T> {
+        let i = self.pop_node(&self.used)?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+
+        // The node is owned by this thread, until pushed to a list
+        let value = unsafe { (*self.node(i).value.get()).assume_init_read() };
+        self.push_node(&self.unused, i);
+        Some(value)
+    }
+
+    /// Returns the chunk and offset in it of a node index
+    #[inline]
+    fn locate(i: u32) -> (usize, usize) {
+        let n = i / FIRST_CHUNK + 1;
+        let chunk = 31 - n.leading_zeros();
+        let start = FIRST_CHUNK * ((1 << chunk) - 1);
+        (chunk as usize, (i - start) as usize)
+    }
+
+    /// Returns the node at an index allocated with `allocate_node()`
+    #[inline]
+    fn node(&self, i: u32) -> &Node<T> {
+        let (chunk, offset) = Self::locate(i);
+
+        // The chunk is installed before the index is published to other
+        // threads
+        unsafe { &*self.chunks[chunk].load(Ordering::Acquire).add(offset) }
+    }
+
+    /// Allocate a new node, installing its chunk, if needed
+    fn allocate_node(&self) -> u32 {
+        let i = self.allocated.fetch_add(1, Ordering::Relaxed);
+        assert!(i < CAPACITY, "free stack capacity exceeded");
+
+        let (chunk, _) = Self::locate(i);
+        let slot = &self.chunks[chunk];
+        if slot.load(Ordering::Acquire).is_null() {
+            let size = (FIRST_CHUNK as usize) << chunk;
+            let nodes: Box<[Node<T>]> = (0..size)
+                .map(|_| Node {
+                    next: AtomicU32::new(NIL),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect();
+            let nodes = Box::into_raw(nodes) as *mut Node<T>;
+            if slot
+                .compare_exchange(
+                    null_mut(),
+                    nodes,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_err()
+            {
+                // Installed by another thread in the meantime
+                unsafe { Self::free_chunk(nodes, chunk) };
+            }
+        }
+        i
+    }
+
+    /// Free a chunk of nodes without dropping their values
+    unsafe fn free_chunk(nodes: *mut Node<T>, chunk: usize) {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            nodes,
+            (FIRST_CHUNK as usize) << chunk,
+        )));
+    }
+
+    /// Push an owned node to the list with the passed head
+    fn push_node(&self, head: &AtomicU64, i: u32) {
+        let node = self.node(i);
+        let mut current = head.load(Ordering::Relaxed);
+        loop {
+            let (tag, top) = unpack(current);
+            node.next.store(top, Ordering::Relaxed);
+            match head.compare_exchange_weak(
+                current,
+                pack(tag.wrapping_add(1), i),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(h) => current = h,
+            }
+        }
+    }
+
+    /// Pop a node from the list with the passed head and take ownership of it
+    fn pop_node(&self, head: &AtomicU64) -> Option<u32> {
+        let mut current = head.load(Ordering::Acquire);
+        loop {
+            let (tag, top) = unpack(current);
+            if top == NIL {
+                return None;
+            }
+
+            // Might be stale, if the node has been popped by another thread in
+            // the meantime, in which case the tag has changed and the
+            // compare-exchange fails
+            let next = self.node(top).next.load(Ordering::Relaxed);
+            match head.compare_exchange_weak(
+                current,
+                pack(tag.wrapping_add(1), next),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(top),
+                Err(h) => current = h,
+            }
+        }
+    }
+}
+
+impl<T> Drop for FreeStack<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+        for (chunk, slot) in self.chunks.iter_mut().enumerate() {
+            let nodes = *slot.get_mut();
+            if !nodes.is_null() {
+                unsafe { Self::free_chunk(nodes, chunk) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Barrier},
+    };
+
+    #[test]
+    fn locate() {
+        type S = FreeStack<()>;
+        assert_eq!(S::locate(0), (0, 0));
+        assert_eq!(S::locate(31), (0, 31));
+        assert_eq!(S::locate(32), (1, 0));
+        assert_eq!(S::locate(95), (1, 63));
+        assert_eq!(S::locate(96), (2, 0));
+        assert_eq!(S::locate(CAPACITY - 1), (CHUNKS - 1, (32 << 26) - 1));
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let s = FreeStack::default();
+        assert!(s.pop().is_none());
+        for i in 0..100 {
+            s.push(Box::new(i));
+        }
+        assert_eq!(s.len(), 100);
+        for i in (50..100).rev() {
+            assert_eq!(*s.pop().unwrap(), i);
+        }
+
+        // Nodes are reused
+        for i in 0..50 {
+            s.push(Box::new(i));
+        }
+        assert_eq!(s.allocated.load(Ordering::Relaxed), 100);
+        assert_eq!(s.len(), 100);
+
+        // Remaining values are dropped with the stack
+    }
+
+    #[test]
+    fn concurrent() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 1000;
+
+        let s = Arc::new(FreeStack::default());
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let threads: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let s = s.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let mut popped = Vec::new();
+                    for i in 0..PER_THREAD {
+                        s.push(t * PER_THREAD + i);
+                        if i % 2 == 0 {
+                            popped.extend(s.pop());
+                        }
+                    }
+                    popped
+                })
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        for t in threads {
+            for v in t.join().unwrap() {
+                assert!(seen.insert(v));
+            }
+        }
+        while let Some(v) = s.pop() {
+            assert!(seen.insert(v));
+        }
+        assert_eq!(seen.len(), THREADS * PER_THREAD);
+        assert_eq!(s.len(), 0);
+    }
+}
@@ -0,0 +1,77 @@
+use super::PageId;
+
+/// Callback invoked with the ID of the affected page
+type PageHook = Box<dyn FnMut(PageId) + Send>;
+
+/// Callback deciding, if a page may be dumped to disk
+type SpillHook = Box<dyn FnMut(PageId) -> bool + Send>;
+
+/// Callback invoked with the number of pages, that could not be acquired
+type BudgetHook = Box<dyn FnMut(usize) + Send>;
+
+/// Callbacks registered by the embedding application for allocator events.
+///
+/// Invoked while holding the allocator lock, so they must not call back into
+/// the allocator or any of its pages.
+#[derive(Default)]
+pub(super) struct Hooks {
+    evict: Vec<PageHook>,
+    spill: Vec<SpillHook>,
+    fault_in: Vec<PageHook>,
+    budget_exceeded: Vec<BudgetHook>,
+}
+
+impl Hooks {
+    /// Register a callback for pages compressed out of resident memory
+    pub fn on_evict(&mut self, f: PageHook) {
+        self.evict.push(f);
+    }
+
+    /// Register a callback for zswapped pages about to be dumped to disk
+    pub fn on_spill(&mut self, f: SpillHook) {
+        self.spill.push(f);
+    }
+
+    /// Register a callback for pages loaded back into resident memory
+    pub fn on_fault_in(&mut self, f: PageHook) {
+        self.fault_in.push(f);
+    }
+
+    /// Register a callback for page acquisitions failing on an exhausted
+    /// resident memory budget
+    pub fn on_budget_exceeded(&mut self, f: BudgetHook) {
+        self.budget_exceeded.push(f);
+    }
+
+    /// Page was compressed out of resident memory
+    pub fn evict(&mut self, id: PageId) {
+        for f in self.evict.iter_mut() {
+            f(id);
+        }
+    }
+
+    /// Returns, if the page may be dumped to disk. All callbacks are invoked
+    /// regardless of previous vetoes.
+    pub fn spill(&mut self, id: PageId) -> bool {
+        let mut allow = true;
+        for f in self.spill.iter_mut() {
+            allow &= f(id);
+        }
+        allow
+    }
+
+    /// Page was loaded back into resident memory
+    pub fn fault_in(&mut self, id: PageId) {
+        for f in self.fault_in.iter_mut() {
+            f(id);
+        }
+    }
+
+    /// Acquiring `n` pages failed, because no pages could be swapped out to
+    /// make room within the resident memory budget
+    pub fn budget_exceeded(&mut self, n: usize) {
+        for f in self.budget_exceeded.iter_mut() {
+            f(n);
+        }
+    }
+}
@@ -1,5 +1,160 @@
+use crate::linked_list::{CheckedRef, LinkedList};
+use std::{collections::HashMap, hash::Hash, time::Instant};
+
+/// Key together with its last usage time
+struct Entry<K> {
+    key: K,
+    used: Instant,
+}
+
 /// Hash map that maintains the last usage time of entires
-pub struct LRUMap {
-    // TODO: hashmap
-// TODO: LL
+pub struct LRUMap<K>
+where
+    K: Hash + Eq + Copy + 'static,
+{
+    /// References to the entries in `list` by key
+    refs: HashMap<K, CheckedRef<Entry<K>>>,
+
+    /// Entries ordered from least to most recently used
+    list: LinkedList<Entry<K>>,
+}
+
+impl<K> Default for LRUMap<K>
+where
+    K: Hash + Eq + Copy + 'static,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> LRUMap<K>
+where
+    K: Hash + Eq + Copy + 'static,
+{
+    /// Create new empty map
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            refs: HashMap::new(),
+            list: LinkedList::new(),
+        }
+    }
+
+    /// Returns the number of keys in the map
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.refs.len()
+    }
+
+    /// Returns, if the map contains no keys
+    #[inline]
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    /// Returns, if the map contains the key
+    #[inline]
+    pub fn contains(&self, key: &K) -> bool {
+        self.refs.contains_key(key)
+    }
+
+    /// Insert key as the most recently used one, replacing any previous entry
+    pub fn insert(&mut self, key: K, used: Instant) {
+        self.remove(&key);
+
+        let mut c = self.list.cursor_mut();
+        c.seek_end();
+        c.insert_after(Entry { key, used });
+        c.next();
+        self.refs.insert(key, c.checked_reference().unwrap());
+    }
+
+    /// Returns the last usage time of a key, if in the map
+    pub fn get(&mut self, key: &K) -> Option<Instant> {
+        let r = self.refs.get(key)?;
+        Some(r.cursor_mut(&mut self.list)?.value()?.used)
+    }
+
+    /// Set the last usage time of a key, if it is newer than the stored one.
+    /// Returns false, if the key is not in the map.
+    pub fn bump(&mut self, key: &K, used: Instant) -> bool {
+        let mut c = match self.refs.get(key) {
+            Some(r) => r.cursor_mut(&mut self.list).unwrap(),
+            None => return false,
+        };
+        let e = c.value().unwrap();
+        if used > e.used {
+            e.used = used;
+            c.move_to_back();
+        }
+        true
+    }
+
+    /// Remove key from the map and return its last usage time, if any
+    pub fn remove(&mut self, key: &K) -> Option<Instant> {
+        let r = self.refs.remove(key)?;
+        Some(self.list.remove(r)?.used)
+    }
+
+    /// Iterate keys and their usage times from the least to the most recently
+    /// used
+    pub fn iter(&mut self) -> impl Iterator<Item = (K, Instant)> + '_ {
+        self.list.iter_mut().map(|e| (e.key, e.used))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LRUMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn bump_reorders() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut m = LRUMap::new();
+        for i in 0..32 {
+            m.insert(i, at(i));
+        }
+        assert_eq!(m.len(), 32);
+
+        // Bump all even keys to the back
+        for i in (0..32).step_by(2) {
+            assert!(m.bump(&i, at(100 + i)));
+        }
+        assert!(!m.bump(&100, at(1000)));
+
+        // Older times must not move keys
+        assert!(m.bump(&1, at(0)));
+        assert_eq!(m.get(&1), Some(at(1)));
+        assert_eq!(m.get(&2), Some(at(102)));
+        assert_eq!(m.get(&100), None);
+
+        let keys: Vec<_> = m.iter().map(|(k, _)| k).collect();
+        let expected: Vec<_> =
+            (1..32).step_by(2).chain((0..32).step_by(2)).collect();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn remove() {
+        let start = Instant::now();
+        let mut m = LRUMap::new();
+        for i in 0..32 {
+            m.insert(i, start + Duration::from_millis(i));
+        }
+        for i in (0..32).step_by(3) {
+            assert_eq!(m.remove(&i), Some(start + Duration::from_millis(i)));
+            assert_eq!(m.remove(&i), None);
+        }
+
+        let keys: Vec<_> = m.iter().map(|(k, _)| k).collect();
+        let expected: Vec<_> = (0..32).filter(|i| i % 3 != 0).collect();
+        assert_eq!(keys, expected);
+        assert_eq!(m.len(), expected.len());
+    }
 }
@@ -1,61 +1,190 @@
-mod free_list;
-mod linked_list;
+mod aead;
+#[cfg(unix)]
+mod arena;
+mod backend;
+mod buddy;
+mod chain;
+mod crc32;
+mod error;
+mod eviction;
+mod free_stack;
+mod hooks;
 mod lru_map;
+mod numa;
+#[cfg(target_os = "linux")]
+mod pressure;
+mod scope;
+mod sim;
+mod slab;
+mod slot;
+mod snapshot;
+mod spill;
+mod trace;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
 
 use std::{
-    borrow::BorrowMut,
-    collections::VecDeque,
+    collections::{BTreeMap, HashMap, HashSet},
     ops::{Deref, DerefMut},
+    path::PathBuf,
     ptr::null_mut,
-    sync::RwLock,
-    time::Instant,
-    usize,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender},
+        Arc, Condvar, Mutex, PoisonError, RwLock, RwLockReadGuard,
+        RwLockWriteGuard, Weak,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
-use self::free_list::FreeList;
+#[cfg(feature = "alloc-tracing")]
+pub use self::trace::TraceRecord;
+pub use self::{
+    aead::EncryptionKey,
+    chain::PageChain,
+    error::AllocError,
+    eviction::{Eviction, EvictionPolicy},
+    scope::{Scope, ScopeStats},
+    slot::{Slot, SlotMode},
+    snapshot::PageSnapshot,
+};
+
+#[cfg(test)]
+pub(crate) use self::sim::inject;
+pub(crate) use self::sim::Fault;
+
+#[cfg(unix)]
+use self::arena::{Arena, HUGE_PAGE_SIZE};
+use self::{
+    backend::{Backend, Platform},
+    free_stack::FreeStack,
+    hooks::Hooks,
+    lru_map::LRUMap,
+    scope::{ScopeId, ScopeState},
+    slot::Slots,
+    snapshot::Frozen,
+    spill::{PendingRead, SpillFile},
+    trace::CallSite,
+};
+use crate::free_list::{AllocationResult, FreeList};
+
+/// Unique identifier of a `Page`
+pub type PageId = u64;
+
+/// Default size of a page in bytes
+const DEFAULT_PAGE_SIZE: usize = 4 << 10;
+
+/// Name of the spill file in `AllocatorConfig::spill_dir`
+const SPILL_FILE_NAME: &str = "pdb.spill";
+
+/// Maximum number of `Allocator::prefetch()` calls queued for the maintenance
+/// thread. Further calls are dropped, until it catches up.
+const PREFETCH_QUEUE_SIZE: usize = 64;
 
 /// Wraps a pointer to an allocated fixed size buffer with dropping and
 // dereferencing to a slice
-struct Buffer<const CAP: usize> {
+struct Buffer {
     ptr: *mut u8,
+
+    /// Size of the buffer in bytes
+    size: usize,
+
+    /// NUMA node the buffer's memory is placed on
+    node: usize,
+
+    /// Huge page arena the buffer was carved from. Allocated from the
+    /// platform backend, if None.
+    #[cfg(unix)]
+    arena: Option<Arc<Arena>>,
 }
 
-impl<const CAP: usize> Buffer<CAP> {
-    /// Allocates a new fixed size buffer
-    fn new() -> Self {
+impl Buffer {
+    /// Allocates a new zeroed fixed size buffer placed on a NUMA node
+    fn new(size: usize, node: usize) -> Self {
+        let ptr = Platform::allocate_zeroed(size);
+        if !ptr.is_null() {
+            numa::bind(ptr, size, node);
+        }
+        Self {
+            ptr,
+            size,
+            node,
+            #[cfg(unix)]
+            arena: None,
+        }
+    }
+
+    /// Carve a new zeroed buffer out of a huge page arena.
+    /// Returns None, if the arena is exhausted.
+    #[cfg(unix)]
+    fn from_arena(arena: &Arc<Arena>, size: usize) -> Option<Self> {
+        Some(Self {
+            ptr: arena.carve()?,
+            size,
+            node: 0,
+            arena: Some(arena.clone()),
+        })
+    }
+
+    /// Prepare the buffer of a released page for reuse by other pages
+    fn wipe(&mut self, scrub: bool) {
+        if scrub {
+            // Arena memory is reused even after the buffer is dropped
+            self.fill(0);
+        } else if cfg!(any(miri, feature = "debug-alloc")) {
+            // Expose reads of released memory by later acquirers
+            self.fill(backend::Debug::POISON);
+        }
+    }
+
+    /// Create a buffer that does not point to any memory
+    #[inline]
+    fn null() -> Self {
         Self {
-            ptr: unsafe { libc::malloc(CAP) } as *mut u8,
+            ptr: null_mut(),
+            size: 0,
+            node: 0,
+            #[cfg(unix)]
+            arena: None,
         }
     }
 }
 
-impl<const CAP: usize> Drop for Buffer<CAP> {
+impl Drop for Buffer {
     fn drop(&mut self) {
-        if self.ptr != null_mut() {
-            unsafe {
-                libc::free(self.ptr as *mut libc::c_void);
-            }
+        if self.ptr.is_null() {
+            return;
+        }
+
+        // Arena memory is unmapped together with the arena
+        #[cfg(unix)]
+        if self.arena.is_some() {
+            return;
         }
+
+        unsafe { Platform::deallocate(self.ptr, self.size) };
     }
 }
 
-impl<const CAP: usize> Deref for Buffer<CAP> {
+impl Deref for Buffer {
     type Target = [u8];
 
     #[inline]
     fn deref(&self) -> &Self::Target {
-        unsafe { std::slice::from_raw_parts(self.ptr, CAP) }
+        unsafe { std::slice::from_raw_parts(self.ptr, self.size) }
     }
 }
 
-impl<const CAP: usize> DerefMut for Buffer<CAP> {
+impl DerefMut for Buffer {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { std::slice::from_raw_parts_mut(self.ptr, CAP) }
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.size) }
     }
 }
 
-unsafe impl<const CAP: usize> Send for Buffer<CAP> {}
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
 
 /// Stores `Page`s in a more compact compressed format, only storing the used
 /// memory of a `Page`.
@@ -64,97 +193,3580 @@ struct ZswapPage {
     ///
     /// Kept small (page size), so they can be cheaply defragmented by
     /// rebuilding the entire page.
-    buf: Buffer<{ 4 << 10 }>,
+    buf: Buffer,
+
+    /// Unique zswap page identifier
+    id: u64,
 
     /// List of free memory ranges
     free_list: FreeList,
-    //
-    // TODO: page registry with access times
+
+    /// Number of compressed pages stored in the buffer
+    stored: usize,
 }
 
 impl ZswapPage {
     /// Construct the page with a preallocated buffer
-    fn new() -> Self {
+    fn new(id: u64, buf: Buffer) -> Self {
+        Self {
+            free_list: FreeList::new(buf.size),
+            buf,
+            id,
+            stored: 0,
+        }
+    }
+
+    /// Store compressed page data, if there is enough free space
+    fn store(&mut self, data: &[u8]) -> Option<ZswapLocation> {
+        match self.free_list.allocate(data.len()) {
+            AllocationResult::Allocated(offset) => {
+                self.buf[offset..offset + data.len()].copy_from_slice(data);
+                self.stored += 1;
+                Some(ZswapLocation {
+                    zswap_page: self.id,
+                    offset,
+                    size: data.len(),
+                })
+            }
+            AllocationResult::NotFound(_) => None,
+        }
+    }
+}
+
+/// Unused buffers not yet returned to the operating system, kept in separate
+/// lock-free stacks per NUMA node, so buffers can be passed between threads
+/// without holding the allocator lock.
+///
+/// Buffers pushed without holding the lock may still have the page size from
+/// before `Allocator::configure()`.
+struct FreePages {
+    /// Buffers by NUMA node
+    nodes: Box<[FreeStack<Buffer>]>,
+}
+
+impl Default for FreePages {
+    fn default() -> Self {
         Self {
-            buf: Buffer::new(),
-            free_list: FreeList::new(4 << 10),
+            nodes: (0..numa::MAX_NODES).map(|_| Default::default()).collect(),
+        }
+    }
+}
+
+impl FreePages {
+    /// Returns the total number of buffers.
+    ///
+    /// Only exact, while no buffers are concurrently added or taken.
+    #[inline]
+    fn len(&self) -> usize {
+        self.nodes.iter().map(|n| n.len()).sum()
+    }
+
+    /// Returns, if there are no buffers on any node
+    #[cfg(test)]
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add buffer to the stack of its NUMA node
+    fn push(&self, buf: Buffer) {
+        self.nodes[buf.node].push(buf);
+    }
+
+    /// Take the most recently added buffer placed on a NUMA node
+    fn pop(&self, node: usize) -> Option<Buffer> {
+        self.nodes.get(node)?.pop()
+    }
+
+    /// Take a buffer from any NUMA node
+    fn pop_any(&self) -> Option<Buffer> {
+        self.nodes.iter().find_map(|n| n.pop())
+    }
+
+    /// Free all buffers and return their number
+    fn clear(&self) -> usize {
+        let mut n = 0;
+        while self.pop_any().is_some() {
+            n += 1;
         }
+        n
     }
 }
 
+/// Location of a compressed `Page` in a `ZswapPage`
+#[derive(Clone, Copy)]
+struct ZswapLocation {
+    /// ID of the containing `ZswapPage`
+    zswap_page: u64,
+
+    /// Offset of the compressed data in the `ZswapPage`
+    offset: usize,
+
+    /// Size of the compressed data
+    size: usize,
+}
+
 /// Page functionality protected by a mutex
 struct PageInner {
-    buffer: Buffer<{ 4 << 10 }>,
+    /// Uncompressed page memory.
+    /// Null, if the page has been swapped out of resident memory or its
+    /// contents are frozen.
+    buffer: Buffer,
+
+    /// Contents shared with snapshots of the page, that must be copied before
+    /// writing to the page. Frozen pages are never swapped out.
+    frozen: Option<Arc<Frozen>>,
+
+    /// Sub-page allocations of the page's memory. Created on first use.
+    slots: Option<Slots>,
+}
+
+impl PageInner {
+    /// Returns, if the page's contents are in resident memory
+    #[inline]
+    fn is_resident(&self) -> bool {
+        !self.buffer.ptr.is_null() || self.frozen.is_some()
+    }
+
+    /// Returns the page's contents, if resident
+    #[inline]
+    fn data(&self) -> &[u8] {
+        match &self.frozen {
+            Some(f) => &f.buffer,
+            None => &self.buffer,
+        }
+    }
+}
+
+/// Page state shared between the `Page` and the allocator's page registry
+struct PageShared {
+    /// Unique page identifier
+    id: PageId,
+
+    /// Allocator the page was acquired from
+    allocator: Allocator,
+
+    /// Scope the page was acquired through, if any
+    scope: Option<ScopeId>,
+
+    /// Number of existing `PinGuard`s for the page
+    pins: AtomicUsize,
+
+    /// Number of times the page has been used
+    accesses: AtomicU64,
+
+    /// Number of times the page has been loaded back into resident memory
+    faults: AtomicU64,
+
+    /// Time the page was acquired at
+    acquired: Instant,
+
+    /// Time the page was last used at in nanoseconds after `acquired` plus
+    /// one. Zero, if not used since the last merge into the allocator's page
+    /// registry, which happens without acquiring the allocator lock.
+    last_use: AtomicU64,
+
+    inner: RwLock<PageInner>,
+}
+
+/// Page for column, index and aggregate allocations.
+///
+/// Pages are 4 KB by default. See `AllocatorConfig::page_size`.
+pub struct Page(Arc<PageShared>);
+
+impl Page {
+    /// Returns the unique identifier of the page
+    #[inline]
+    pub fn id(&self) -> PageId {
+        self.0.id
+    }
+
+    /// Acquire shared access to the page's memory, loading it back into
+    /// resident memory, if it has been swapped out.
+    ///
+    /// Records the page as used just now. The page is not swapped out, while
+    /// the guard is held.
+    pub fn read(&self) -> Result<PageReadGuard<'_>, AllocError> {
+        loop {
+            {
+                let g = self.0.inner.read()?;
+                if g.is_resident() {
+                    self.touch();
+                    return Ok(PageReadGuard(g, self.0.id));
+                }
+            }
+
+            // Loading the page requires exclusive access. The page might get
+            // swapped out again before the shared lock is reacquired, so
+            // loop.
+            let mut g = self.0.inner.write()?;
+            if !g.is_resident() {
+                self.fault_in(&mut g)?;
+            }
+        }
+    }
+
+    /// Acquire exclusive access to the page's memory, loading it back into
+    /// resident memory, if it has been swapped out.
+    ///
+    /// Records the page as used just now. The page is not swapped out, while
+    /// the guard is held.
+    pub fn write(&self) -> Result<PageWriteGuard<'_>, AllocError> {
+        let mut g = self.0.inner.write()?;
+        if g.frozen.is_some() {
+            self.thaw(&mut g)?;
+        } else if g.buffer.ptr.is_null() {
+            self.fault_in(&mut g)?;
+        }
+        self.touch();
+        Ok(PageWriteGuard(g, self.0.id))
+    }
+
+    /// Take a read-only snapshot of the page's current contents, loading it
+    /// back into resident memory, if it has been swapped out.
+    ///
+    /// The contents are only copied, once the page is written to.
+    pub fn snapshot(&self) -> Result<PageSnapshot, AllocError> {
+        let mut g = self.0.inner.write()?;
+        if g.frozen.is_none() {
+            if g.buffer.ptr.is_null() {
+                self.fault_in(&mut g)?;
+            }
+            let buffer = std::mem::replace(&mut g.buffer, Buffer::null());
+            g.frozen = Some(Arc::new(Frozen {
+                id: self.0.id,
+                allocator: self.0.allocator.clone(),
+                buffer,
+            }));
+        }
+        Ok(PageSnapshot(g.frozen.clone().unwrap()))
+    }
+
+    /// Give the page a private copy of its frozen contents, so it can be
+    /// written to without affecting its snapshots
+    fn thaw(&self, p: &mut PageInner) -> Result<(), AllocError> {
+        match Arc::try_unwrap(p.frozen.take().unwrap()) {
+            // All snapshots have been dropped, so no copy is needed
+            Ok(frozen) => p.buffer = frozen.into_buffer(),
+            Err(frozen) => {
+                match self.0.allocator.with(|a| a.take_buffer()) {
+                    Ok(mut buf) => {
+                        buf.copy_from_slice(&frozen.buffer);
+                        p.buffer = buf;
+                    }
+                    Err(err) => {
+                        p.frozen = Some(frozen);
+                        return Err(err);
+                    }
+                }
+
+                // Might be the last reference by now, which returns the
+                // buffer to the allocator
+                drop(frozen);
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the swapped out page back into resident memory.
+    ///
+    /// Disk reads are performed without holding the allocator lock. Only
+    /// threads accessing this page are blocked by holding the page lock.
+    fn fault_in(&self, p: &mut PageInner) -> Result<(), AllocError> {
+        let id = self.0.id;
+        let allocator = &self.0.allocator;
+        match allocator.with(|a| a.fault_in(id, p))? {
+            None => Ok(()),
+            Some(read) => {
+                let res = read.read();
+                allocator.with(|a| a.finish_fault_in(id, p, read, res))
+            }
+        }
+    }
+
+    /// Record the page as used just now.
+    ///
+    /// Does not acquire the allocator lock, so can be called freely on hot
+    /// paths.
+    #[inline]
+    pub fn touch(&self) {
+        self.0.accesses.fetch_add(1, Ordering::Relaxed);
+        let since = sim::now().saturating_duration_since(self.0.acquired);
+        self.0
+            .last_use
+            .fetch_max(since.as_nanos() as u64 + 1, Ordering::Relaxed);
+    }
+
+    /// Pin the page in resident memory, loading it back, if it has been
+    /// swapped out.
+    ///
+    /// The page is never swapped out, while any `PinGuard` for it exists.
+    pub fn pin(&self) -> Result<PinGuard<'_>, AllocError> {
+        // Incrementing before taking the lock ensures the allocator sees the
+        // pin on its next attempt to swap the page out
+        self.0.pins.fetch_add(1, Ordering::AcqRel);
+        let guard = PinGuard(&self.0);
+
+        let mut g = self.0.inner.write()?;
+        if !g.is_resident() {
+            self.fault_in(&mut g)?;
+        }
+        Ok(guard)
+    }
+
+    /// Pin the page in resident memory for as long as it exists, loading it
+    /// back, if it has been swapped out.
+    ///
+    /// The address of the page's memory does not change afterwards, so it can
+    /// be referenced by raw pointers. The page must not be snapshotted.
+    #[cfg(feature = "node-pool")]
+    pub(crate) fn into_pinned(self) -> Result<PinnedPage, AllocError> {
+        self.0.pins.fetch_add(1, Ordering::AcqRel);
+        let mut pinned = PinnedPage {
+            page: self,
+            ptr: null_mut(),
+            size: 0,
+        };
+
+        let mut g = pinned.page.write()?;
+        let (ptr, size) = (g.as_mut_ptr(), g.len());
+        drop(g);
+        pinned.ptr = ptr;
+        pinned.size = size;
+        Ok(pinned)
+    }
+
+    /// Returns, if the page is currently pinned in resident memory
+    #[inline]
+    pub fn is_pinned(&self) -> bool {
+        self.0.pins.load(Ordering::Acquire) != 0
+    }
+
+    /// Allocate a byte range within the page's memory for packing many small
+    /// values into a single page.
+    ///
+    /// The range is accessed with `PageReadGuard::slot()` and
+    /// `PageWriteGuard::slot_mut()`. Does not require the page to be resident.
+    ///
+    /// Returns `AllocError::PageFull`, if no large enough free range exists.
+    pub fn allocate(&self, size: usize) -> Result<Slot, AllocError> {
+        let mut g = self.0.inner.write()?;
+        let page_size = self.0.allocator.with(|a| a.config.page_size);
+        g.slots
+            .get_or_insert_with(|| Slots::new(page_size, SlotMode::default()))
+            .allocate(self.0.id, size)
+    }
+
+    /// Select how future sub-page allocations are placed in the page's memory.
+    /// Defaults to `SlotMode::FreeList`.
+    ///
+    /// Returns `AllocError::InvalidConfig`, if the page has live sub-page
+    /// allocations.
+    pub fn set_slot_mode(&self, mode: SlotMode) -> Result<(), AllocError> {
+        let mut g = self.0.inner.write()?;
+        let page_size = self.0.allocator.with(|a| a.config.page_size);
+        match &mut g.slots {
+            Some(slots) => slots.set_mode(self.0.id, page_size, mode),
+            None => {
+                g.slots = Some(Slots::new(page_size, mode));
+                Ok(())
+            }
+        }
+    }
+
+    /// Same as `allocate()`, but places the range at an offset aligned to
+    /// `align` bytes, which must be a power of two, for example for SIMD
+    /// processing of column data.
+    pub fn allocate_aligned(
+        &self,
+        size: usize,
+        align: usize,
+    ) -> Result<Slot, AllocError> {
+        let mut g = self.0.inner.write()?;
+        let page_size = self.0.allocator.with(|a| a.config.page_size);
+        g.slots
+            .get_or_insert_with(|| Slots::new(page_size, SlotMode::default()))
+            .allocate_aligned(self.0.id, size, align)
+    }
+
+    /// Free a byte range allocated with `allocate()`, so it can be reused.
+    ///
+    /// Returns `AllocError::InvalidSlot`, if the range belongs to another
+    /// page, and `AllocError::StaleHandle`, if it has already been freed.
+    pub fn free(&self, slot: Slot) -> Result<(), AllocError> {
+        if slot.page != self.0.id {
+            return Err(AllocError::InvalidSlot(self.0.id));
+        }
+        self.0
+            .inner
+            .write()?
+            .slots
+            .as_mut()
+            .ok_or(AllocError::StaleHandle(self.0.id))?
+            .free(slot)
+    }
 }
 
-impl Drop for PageInner {
+/// Keeps a `Page` pinned in resident memory until dropped
+pub struct PinGuard<'a>(&'a PageShared);
+
+impl<'a> Drop for PinGuard<'a> {
+    #[inline]
     fn drop(&mut self) {
-        with_allocator(|a| a.release_page(self));
+        if self.0.pins.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Page can be swapped out again
+            self.0.allocator.0.released.notify_all();
+        }
     }
 }
 
-/// 4 KB page for column, index and aggregate allocations
-pub struct Page(RwLock<PageInner>);
+/// `Page` pinned in resident memory for its entire lifetime, with its memory
+/// at a fixed address
+#[cfg(feature = "node-pool")]
+pub(crate) struct PinnedPage {
+    page: Page,
 
-// Swapping, compressing table, aggregate and index allocator
-#[derive(Default)]
-struct Allocator {
-    /// Underlying 4 KB memory pages for swapping `Page`s into
-    //
-    // TODO: each 100 ms (configurable) defragment up to 4 pages from the back
-    // and move them to the front
-    //
-    // TODO: some sort of data structure for enabling both finding a page by ID
-    // and ordering pages by fragmentation to pick the least fragmented one for
-    // new allocations
-    zswap_pages: VecDeque<ZswapPage>,
+    /// Start of the page's memory
+    ptr: *mut u8,
 
-    // TODO: page registry via LRU map
-    // TODO: allow bumping usage time w/o allocator lock via separate mutex
-    //
-    /// Unused pages not yet returned to the operating system.
-    /// Stored together with their monotonous insertion time.
-    //
-    // TODO: keep a small pool of pages (4?) in reserve for allocator purposes
-    free_pages: VecDeque<(Instant, Buffer<{ 4 << 10 }>)>,
+    /// Size of the page's memory in bytes
+    size: usize,
 }
 
-impl Allocator {
-    fn get_page(&mut self) -> Result<Page, String> {
-        todo!()
+#[cfg(feature = "node-pool")]
+impl PinnedPage {
+    /// Returns the start of the page's memory
+    #[inline]
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    /// Returns the size of the page's memory in bytes
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the unique identifier of the page
+    #[cfg(test)]
+    #[inline]
+    pub fn id(&self) -> PageId {
+        self.page.id()
+    }
+}
+
+#[cfg(feature = "node-pool")]
+impl Drop for PinnedPage {
+    #[inline]
+    fn drop(&mut self) {
+        drop(PinGuard(&self.page.0));
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        let allocator = self.0.allocator.clone();
+        let (frozen, buf, scrub) = allocator.with(|a| {
+            let (frozen, buf) = a.release_page(&mut self.0);
+            (frozen, buf, a.config.scrub)
+        });
+
+        // Dropping the last reference to frozen contents acquires the
+        // allocator lock
+        drop(frozen);
+        if let Some(mut buf) = buf {
+            buf.wipe(scrub);
+            allocator.0.free_pages.push(buf);
+        }
+        allocator.0.released.notify_all();
+    }
+}
+
+/// Shared access to a `Page`'s memory.
+///
+/// Keeps the page in resident memory until dropped.
+pub struct PageReadGuard<'a>(RwLockReadGuard<'a, PageInner>, PageId);
+
+impl<'a> Deref for PageReadGuard<'a> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.0.data()
+    }
+}
+
+/// Exclusive access to a `Page`'s memory.
+///
+/// Keeps the page in resident memory until dropped.
+pub struct PageWriteGuard<'a>(RwLockWriteGuard<'a, PageInner>, PageId);
+
+impl<'a> Deref for PageWriteGuard<'a> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0.buffer
+    }
+}
+
+impl<'a> DerefMut for PageWriteGuard<'a> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0.buffer
     }
+}
+
+/// Configuration of the page allocator
+#[derive(Clone, Debug)]
+pub struct AllocatorConfig {
+    /// Size of a page in bytes.
+    ///
+    /// Must be a power of two and at least 1 KB. Defaults to 4 KB.
+    pub page_size: usize,
+
+    /// Maximum size of resident page memory in bytes.
+    ///
+    /// When reached, pages chosen by the eviction policy are swapped out to
+    /// make room for new ones. Buffers for the allocator's own bookkeeping may
+    /// exceed the limit temporarily.
+    ///
+    /// Unlimited, if None.
+    pub max_resident: Option<usize>,
+
+    /// Interval between defragmentation runs of the maintenance thread.
+    /// Defaults to 100 ms.
+    pub defrag_interval: Duration,
+
+    /// Maximum number of zswap pages to rebuild per defragmentation run.
+    /// Defaults to 4. Setting to 0 disables defragmentation.
+    pub defrag_pages: usize,
+
+    /// Policy for choosing pages to swap out, when the resident memory budget
+    /// is exhausted. Defaults to LRU.
+    pub eviction: Eviction,
+
+    /// Pages not used for this long are compressed into zswap pages.
+    /// Defaults to 10 seconds.
+    pub zswap_age: Duration,
+
+    /// Zswapped pages not used for this long are dumped to disk.
+    /// Defaults to 60 seconds.
+    pub spill_age: Duration,
+
+    /// When the number of zswap pages exceeds this, the zswapped pages chosen
+    /// by the eviction policy are dumped to disk regardless of their age.
+    /// Defaults to 1024.
+    pub max_zswap_pages: usize,
+
+    /// Minimum interval between dumping pages to disk based on their age or
+    /// zswap occupancy. Pages are still dumped immediately, when the resident
+    /// memory budget is exhausted.
+    ///
+    /// Defaults to 0, which disables rate limiting.
+    pub spill_interval: Duration,
 
-    fn release_page(&mut self, p: &mut PageInner) {
-        // Return buffer to allocator
-        self.free_pages
-            .push_back((Instant::now(), Buffer { ptr: p.buffer.ptr }));
-        p.buffer.ptr = null_mut(); // Prevent double free
+    /// Carve page buffers out of 2 MB huge page backed arenas to reduce TLB
+    /// pressure for large working sets.
+    ///
+    /// Arena memory is only returned to the operating system, once all of the
+    /// arena's buffers are freed. Defaults to false.
+    pub huge_pages: bool,
+
+    /// Pages loaded by `Allocator::prefetch()` are only swapped out after all
+    /// other candidates for this long, so they survive until the scan reaches
+    /// them. Defaults to 1 second.
+    pub prefetch_protection: Duration,
+
+    /// Encrypt pages dumped to disk with XChaCha20-Poly1305 using this key, so
+    /// swapped out data is not stored in plaintext. Defaults to None.
+    pub spill_key: Option<EncryptionKey>,
+
+    /// Directory to keep the spill file in across restarts.
+    ///
+    /// Pages still dumped to disk, when the process terminates, are
+    /// registered for recovery with `Allocator::recover()` by the next
+    /// allocator using the same directory. The directory must not be shared
+    /// by multiple allocators at the same time.
+    ///
+    /// Defaults to None, which uses a temporary file removed with the
+    /// allocator.
+    pub spill_dir: Option<PathBuf>,
+
+    /// Overwrite released page buffers, freed compressed page data and the
+    /// on-disk data of pages removed from the spill file with zeros, so
+    /// sensitive data does not linger in recycled memory or on disk.
+    /// Defaults to false.
+    pub scrub: bool,
+}
 
-        todo!("unregister page")
+impl Default for AllocatorConfig {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            max_resident: None,
+            defrag_interval: Duration::from_millis(100),
+            defrag_pages: 4,
+            eviction: Eviction::Lru,
+            zswap_age: Duration::from_secs(10),
+            spill_age: Duration::from_secs(60),
+            max_zswap_pages: 1 << 10,
+            spill_interval: Duration::ZERO,
+            huge_pages: false,
+            prefetch_protection: Duration::from_secs(1),
+            spill_key: None,
+            spill_dir: None,
+            scrub: false,
+        }
+    }
+}
+
+impl AllocatorConfig {
+    /// Validate configuration values
+    fn validate(&self) -> Result<(), AllocError> {
+        if !self.page_size.is_power_of_two() || self.page_size < 1 << 10 {
+            return Err(AllocError::InvalidConfig(format!(
+                "invalid page size: {}",
+                self.page_size
+            )));
+        }
+        #[cfg(unix)]
+        if self.huge_pages && self.page_size > HUGE_PAGE_SIZE {
+            return Err(AllocError::InvalidConfig(format!(
+                "page size {} exceeds huge page size",
+                self.page_size
+            )));
+        }
+        #[cfg(not(unix))]
+        if self.huge_pages {
+            return Err(AllocError::InvalidConfig(
+                "huge pages not supported on this platform".into(),
+            ));
+        }
+        if let Some(max) = self.max_resident {
+            if max < self.page_size {
+                return Err(AllocError::InvalidConfig(format!(
+                    "memory budget too small: {}",
+                    max
+                )));
+            }
+        }
+        Ok(())
     }
 }
 
-/// Run function with global page allocator as an argument, acquiring exclusive
-/// access to it
-fn with_allocator<F, R>(f: F) -> R
-where
-    F: FnOnce(&mut Allocator) -> R,
-{
-    use std::sync::{Mutex, Once};
+/// State shared between all handles to an allocator
+struct AllocatorShared {
+    inner: Mutex<AllocatorInner>,
+
+    /// Buffers are taken for acquired pages and added for dropped pages without
+    /// holding the allocator lock
+    free_pages: Arc<FreePages>,
+
+    /// Notified, when pages are released or unpinned, so threads waiting for
+    /// resident memory can retry
+    released: Condvar,
 
-    static ONCE: Once = Once::new();
-    static mut ALLOCATOR: Option<Mutex<Allocator>> = None;
-    ONCE.call_once(|| unsafe {
-        ALLOCATOR = Some(Default::default());
-    });
+    /// Maintenance thread joined on shutdown
+    maintenance: Mutex<Option<JoinHandle<()>>>,
 
-    f(&mut *unsafe { ALLOCATOR.as_ref().unwrap() }
-        .lock()
-        .unwrap()
-        .borrow_mut())
+    /// Queues pages to prefetch to the maintenance thread. Dropped on
+    /// shutdown to stop the thread.
+    prefetch: Mutex<Option<SyncSender<Vec<PageId>>>>,
 }
 
-/// Acquire a 4 KB page for column, index and aggregate allocations
-pub fn get_page() -> Result<Page, String> {
-    with_allocator(|a| a.get_page())
+/// Handle to a swapping, compressing table, aggregate and index allocator.
+///
+/// Cheap to clone. Pages keep the allocator they were acquired from alive.
+#[derive(Clone)]
+pub struct Allocator(Arc<AllocatorShared>);
+
+impl Allocator {
+    /// Create a new allocator independent from the global one
+    pub fn new(config: AllocatorConfig) -> Result<Self, AllocError> {
+        let policy = config.eviction.build();
+        Self::with_policy(config, policy)
+    }
+
+    /// Create a new allocator with a custom eviction policy.
+    /// `AllocatorConfig::eviction` is ignored.
+    pub fn with_policy(
+        config: AllocatorConfig,
+        policy: Box<dyn EvictionPolicy>,
+    ) -> Result<Self, AllocError> {
+        config.validate()?;
+
+        let free_pages = Arc::new(FreePages::default());
+        let mut inner = AllocatorInner {
+            config,
+            policy,
+            free_pages: free_pages.clone(),
+            ..Default::default()
+        };
+        inner.open_spill_dir()?;
+        let (prefetch, queue) = sync_channel(PREFETCH_QUEUE_SIZE);
+        let a = Self(Arc::new(AllocatorShared {
+            inner: Mutex::new(inner),
+            free_pages,
+            released: Condvar::new(),
+            maintenance: Default::default(),
+            prefetch: Mutex::new(Some(prefetch)),
+        }));
+        *a.0.maintenance.lock().unwrap() =
+            Some(spawn_maintenance(Arc::downgrade(&a.0), queue));
+        Ok(a)
+    }
+
+    /// Returns a handle to the global default allocator
+    pub fn global() -> Self {
+        lazy_static::lazy_static! {
+            static ref GLOBAL: Allocator =
+                Allocator::new(Default::default()).unwrap();
+        }
+
+        GLOBAL.clone()
+    }
+
+    /// Run function with the allocator state as an argument, acquiring
+    /// exclusive access to it
+    fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut AllocatorInner) -> R,
+    {
+        f(&mut self.0.inner.lock().unwrap())
+    }
+
+    /// Compress a page out of resident memory regardless of its age
+    #[cfg(test)]
+    pub(crate) fn zswap(&self, id: PageId) -> Result<(), AllocError> {
+        self.with(|a| a.zswap(id))
+    }
+
+    /// Acquire a page for column, index and aggregate allocations
+    #[track_caller]
+    pub fn get_page(&self) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        let buf = self.0.free_pages.pop(numa::current_node());
+        self.with(|a| a.get_page(self, None, buf, site))
+    }
+
+    /// Acquire `n` pages at once, making room for all of them with a single
+    /// pass over the eviction candidates.
+    ///
+    /// Either all or none of the pages are acquired.
+    #[track_caller]
+    pub fn get_pages(&self, n: usize) -> Result<Vec<Page>, AllocError> {
+        let site = CallSite::caller();
+        self.with(|a| a.get_pages(self, None, n, None, site))
+    }
+
+    /// Acquire enough pages to hold an allocation of `size` bytes larger than
+    /// a page
+    #[track_caller]
+    pub fn get_chain(&self, size: usize) -> Result<PageChain, AllocError> {
+        let site = CallSite::caller();
+        self.with(|a| a.get_chain(self, None, size, site))
+    }
+
+    /// Acquire a page without swapping out other pages to make room.
+    ///
+    /// Returns `AllocError::WouldBlock`, if the resident memory budget is
+    /// exhausted.
+    #[track_caller]
+    pub fn try_get_page(&self) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        self.with(|a| a.try_get_page(self, None, site))
+    }
+
+    /// Acquire a page, waiting up to `timeout` for other pages to be released
+    /// or unpinned, if the resident memory budget is exhausted and no pages
+    /// can be swapped out
+    #[track_caller]
+    pub fn get_page_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        self.wait_for(timeout, |a| a.get_page(self, None, None, site))
+    }
+
+    /// Run `f` with the allocator state, until it does not fail due to an
+    /// exhausted budget or quota or `timeout` expires
+    fn wait_for<F>(
+        &self,
+        timeout: Duration,
+        mut f: F,
+    ) -> Result<Page, AllocError>
+    where
+        F: FnMut(&mut AllocatorInner) -> Result<Page, AllocError>,
+    {
+        // Unpinning does not take the allocator lock, so its notification can
+        // be missed. Retry periodically to cover that.
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+        let deadline = Instant::now() + timeout;
+        let mut g = self.0.inner.lock().unwrap();
+        loop {
+            match f(&mut g) {
+                Err(err @ AllocError::BudgetExceeded)
+                | Err(err @ AllocError::QuotaExceeded(_)) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(err);
+                    }
+                    g = self
+                        .0
+                        .released
+                        .wait_timeout(g, (deadline - now).min(POLL_INTERVAL))
+                        .unwrap()
+                        .0;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Reclaim a page recovered from the spill file of a previous allocator
+    /// in `AllocatorConfig::spill_dir`.
+    ///
+    /// The page is only loaded back into resident memory, when first
+    /// accessed. Returns `AllocError::PageNotFound`, if there is no such
+    /// unclaimed page.
+    #[track_caller]
+    pub fn recover(&self, id: PageId) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        self.with(|a| a.recover(self, id, site))
+    }
+
+    /// Returns the IDs of recovered pages not yet reclaimed with `recover()`
+    /// in ascending order
+    pub fn recoverable(&self) -> Vec<PageId> {
+        let mut ids: Vec<_> =
+            self.with(|a| a.recovered.iter().copied().collect());
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Create a named allocation scope limiting the resident memory of pages
+    /// acquired through it to `quota` bytes. Unlimited, if None.
+    pub fn scope(
+        &self,
+        name: impl Into<String>,
+        quota: Option<usize>,
+    ) -> Result<Scope, AllocError> {
+        let name = name.into();
+        let id = self.with(|a| {
+            let page_size = a.config.page_size;
+            if let Some(q) = quota {
+                if q < page_size {
+                    return Err(AllocError::InvalidConfig(format!(
+                        "quota of scope {} too small: {}",
+                        name, q
+                    )));
+                }
+            }
+
+            let id = a.next_scope_id;
+            a.next_scope_id += 1;
+            a.scopes.insert(
+                id,
+                ScopeState {
+                    name,
+                    max_resident: quota.map(|q| q / page_size),
+                    quota,
+                    acquired: 0,
+                    resident: 0,
+                    closed: false,
+                },
+            );
+            Ok(id)
+        })?;
+        Ok(Scope {
+            allocator: self.clone(),
+            id,
+        })
+    }
+
+    /// Hint that pages are about to be accessed, for example by a sequential
+    /// scan.
+    ///
+    /// Swapped out pages are loaded back into resident memory by the
+    /// maintenance thread in the passed order. Pages not acquired from this
+    /// allocator, already resident or in use are skipped. The hint is dropped,
+    /// if too many prefetches are already queued or after shutdown.
+    pub fn prefetch(&self, ids: &[PageId]) {
+        let ids: Vec<_> = self.with(|a| {
+            ids.iter()
+                .copied()
+                .filter(|id| {
+                    matches!(
+                        a.lookup(*id),
+                        Some(PageLocation::Zswapped | PageLocation::Spilled)
+                    )
+                })
+                .collect()
+        });
+        if ids.is_empty() {
+            return;
+        }
+
+        if let Some(queue) = &*self.0.prefetch.lock().unwrap() {
+            // Prefetching is only a hint
+            queue.try_send(ids).ok();
+        }
+    }
+
+    /// Find the storage tier of an acquired page by its ID.
+    /// Returns None, if no such page is acquired from this allocator.
+    pub fn lookup(&self, id: PageId) -> Option<PageLocation> {
+        self.with(|a| a.lookup(id))
+    }
+
+    /// Returns the access statistics of acquired pages by their IDs in the
+    /// passed order. None for IDs of pages not acquired from this allocator.
+    pub fn page_access(&self, ids: &[PageId]) -> Vec<Option<PageAccess>> {
+        self.with(|a| {
+            a.merge_usage();
+            ids.iter().map(|id| a.page_access(*id)).collect()
+        })
+    }
+
+    /// Take a snapshot of the allocator's counters
+    pub fn stats(&self) -> Stats {
+        self.with(|a| a.stats())
+    }
+
+    /// Returns the most recent page acquisitions from oldest to newest
+    #[cfg(feature = "alloc-tracing")]
+    pub fn trace(&self) -> Vec<TraceRecord> {
+        self.with(|a| a.trace.records())
+    }
+
+    /// Write the most recent page acquisitions followed by the bytes still
+    /// held by each consumer in descending order
+    #[cfg(feature = "alloc-tracing")]
+    pub fn dump_trace(
+        &self,
+        mut w: impl std::io::Write,
+    ) -> std::io::Result<()> {
+        let records = self.trace();
+        let mut held = HashMap::<&str, usize>::new();
+        for r in records.iter() {
+            writeln!(w, "{}", r)?;
+            if r.lifetime.is_none() {
+                *held
+                    .entry(r.label.as_deref().unwrap_or("<unscoped>"))
+                    .or_default() += r.size;
+            }
+        }
+
+        let mut held: Vec<_> = held.into_iter().collect();
+        held.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for (label, size) in held {
+            writeln!(w, "{}: {} B held", label, size)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the configuration of the allocator.
+    ///
+    /// Can only be called, while no pages are acquired.
+    pub fn configure(&self, config: AllocatorConfig) -> Result<(), AllocError> {
+        self.with(|a| a.configure(config))
+    }
+
+    /// Replace the resident memory budget of the allocator and immediately
+    /// swap out pages and release unused buffers to fit within it.
+    /// Unlimited, if None.
+    ///
+    /// The budget is applied, even if not enough pages can be swapped out to
+    /// fit within it, in which case `AllocError::BudgetExceeded` is returned.
+    pub fn set_budget(
+        &self,
+        max_resident: Option<usize>,
+    ) -> Result<(), AllocError> {
+        self.with(|a| {
+            if let Some(max) = max_resident {
+                if max < a.config.page_size {
+                    return Err(AllocError::InvalidConfig(format!(
+                        "memory budget too small: {}",
+                        max
+                    )));
+                }
+            }
+            a.config.max_resident = max_resident;
+            match max_resident {
+                Some(max) => a.shrink(max / a.config.page_size),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Listen for memory pressure notifications of a cgroup v2
+    /// `memory.pressure` or the system-wide `/proc/pressure/memory` file and
+    /// shrink the resident page set by a quarter each time the host is under
+    /// pressure.
+    ///
+    /// The listener thread stops, when the allocator is dropped or shut down.
+    #[cfg(target_os = "linux")]
+    pub fn listen_pressure(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), AllocError> {
+        // Interval of checking, if the allocator is still alive
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+        let monitor = pressure::PressureMonitor::open(path.as_ref())
+            .map_err(AllocError::Pressure)?;
+        let allocator = Arc::downgrade(&self.0);
+        std::thread::Builder::new()
+            .name("pdb-alloc-pressure".into())
+            .spawn(move || loop {
+                // The file becoming unavailable, for example by the cgroup
+                // being removed, ends the listener
+                let fired = match monitor.wait(POLL_INTERVAL) {
+                    Ok(fired) => fired,
+                    Err(_) => return,
+                };
+                let stop = match allocator.upgrade() {
+                    Some(a) => Allocator(a).with(|a| {
+                        if fired && !a.shut_down {
+                            // Shrinking is best effort
+                            a.shrink(a.resident - a.resident / 4).ok();
+                        }
+                        a.shut_down
+                    }),
+                    None => true,
+                };
+                if stop {
+                    return;
+                }
+            })
+            .map_err(AllocError::Pressure)?;
+        Ok(())
+    }
+
+    /// Register a callback invoked with the ID of each page compressed out of
+    /// resident memory.
+    ///
+    /// Callbacks are invoked while holding the allocator lock and must not
+    /// call back into the allocator or any of its pages.
+    pub fn on_evict(&self, f: impl FnMut(PageId) + Send + 'static) {
+        self.with(|a| a.hooks.on_evict(Box::new(f)))
+    }
+
+    /// Register a callback invoked with the ID of each zswapped page about to
+    /// be dumped to disk. Returning false keeps the page in zswap, so
+    /// latency-critical pages are never read back from disk.
+    ///
+    /// Pages are dumped on `Allocator::shutdown()` regardless of vetoes.
+    /// Same locking constraints as `Allocator::on_evict()` apply.
+    pub fn on_spill(&self, f: impl FnMut(PageId) -> bool + Send + 'static) {
+        self.with(|a| a.hooks.on_spill(Box::new(f)))
+    }
+
+    /// Register a callback invoked with the ID of each page loaded back into
+    /// resident memory.
+    ///
+    /// Same locking constraints as `Allocator::on_evict()` apply.
+    pub fn on_fault_in(&self, f: impl FnMut(PageId) + Send + 'static) {
+        self.with(|a| a.hooks.on_fault_in(Box::new(f)))
+    }
+
+    /// Register a callback invoked with the number of requested pages, when
+    /// acquiring or loading pages fails with `AllocError::BudgetExceeded`.
+    ///
+    /// Same locking constraints as `Allocator::on_evict()` apply.
+    pub fn on_budget_exceeded(&self, f: impl FnMut(usize) + Send + 'static) {
+        self.with(|a| a.hooks.on_budget_exceeded(Box::new(f)))
+    }
+
+    /// Stop background maintenance and prefetching, dump all zswapped pages
+    /// to disk and sync the spill file.
+    ///
+    /// Acquiring and recovering pages fails with `AllocError::ShutDown`
+    /// afterwards. Already acquired pages remain usable. Pages dumped to disk
+    /// are kept in a persistent spill file for recovery, even when released
+    /// after shutdown.
+    pub fn shutdown(&self) -> Result<(), AllocError> {
+        let res = self.with(|a| a.shutdown());
+
+        // Disconnecting the queue wakes up the maintenance thread
+        self.0.prefetch.lock().unwrap().take();
+        let maintenance = self.0.maintenance.lock().unwrap().take();
+        if let Some(h) = maintenance {
+            // Only fails, if the thread panicked, which is not ours to handle
+            h.join().ok();
+        }
+
+        // Fail threads waiting for memory instead of letting them time out
+        self.0.released.notify_all();
+        res
+    }
+}
+
+/// Allocator state protected by a mutex
+#[derive(Default)]
+struct AllocatorInner {
+    config: AllocatorConfig,
+
+    /// Decides the order pages are swapped out in under memory pressure
+    policy: Box<dyn EvictionPolicy>,
+
+    /// Underlying page-sized memory buffers for swapping `Page`s into.
+    ///
+    /// Periodically defragmented from the back by the maintenance thread.
+    //
+    /// Keyed and ordered by ID, so newer zswap pages are at the back.
+    //
+    // TODO: order pages by fragmentation to pick the least fragmented one for
+    // new allocations
+    zswap_pages: BTreeMap<u64, ZswapPage>,
+
+    /// ID to assign to the next created zswap page
+    next_zswap_id: u64,
+
+    /// Locations of pages compressed into `zswap_pages`
+    zswapped: HashMap<PageId, ZswapLocation>,
+
+    /// Checksums of the uncompressed data of swapped out pages, verified on
+    /// loading them back
+    checksums: HashMap<PageId, u32>,
+
+    /// File for dumping cold zswapped pages to. Created on first use, unless
+    /// reopened from `AllocatorConfig::spill_dir`.
+    spill: Option<SpillFile>,
+
+    /// Pages in the reopened spill file not yet reclaimed with
+    /// `Allocator::recover()`
+    recovered: HashSet<PageId>,
+
+    /// Registry of acquired pages ordered by their last usage time.
+    ///
+    /// Usage times recorded by `Page::touch()` are only merged into the
+    /// registry, when eviction candidates are needed.
+    pages: LRUMap<PageId>,
+
+    /// Shared state of all acquired pages
+    handles: HashMap<PageId, Arc<PageShared>>,
+
+    /// ID to assign to the next acquired page
+    next_id: PageId,
+
+    /// Unused pages not yet returned to the operating system
+    //
+    // TODO: keep a small pool of pages (4?) in reserve for allocator purposes
+    free_pages: Arc<FreePages>,
+
+    /// Number of currently allocated page-sized buffers, including unused ones
+    resident: usize,
+
+    /// Huge page arena new buffers are carved from, if enabled
+    #[cfg(unix)]
+    arena: Option<Arc<Arena>>,
+
+    /// Number of pages loaded back into resident memory
+    page_faults: u64,
+
+    /// Number of pages compressed into zswap
+    evictions: u64,
+
+    /// Number of zswapped pages dumped to disk
+    spills: u64,
+
+    /// Last time pages were dumped to disk based on their age or zswap
+    /// occupancy
+    last_spill: Option<Instant>,
+
+    /// Accounting of named allocation scopes
+    scopes: HashMap<ScopeId, ScopeState>,
+
+    /// ID to assign to the next created scope
+    next_scope_id: ScopeId,
+
+    /// Pages loaded by prefetching and when they were loaded. Moved to the
+    /// back of the eviction candidates until
+    /// `AllocatorConfig::prefetch_protection` expires.
+    prefetched: HashMap<PageId, Instant>,
+
+    /// Most recent page acquisitions
+    #[cfg(feature = "alloc-tracing")]
+    trace: trace::Trace,
+
+    /// Set by `Allocator::shutdown()`
+    shut_down: bool,
+
+    /// Callbacks registered for allocator events
+    hooks: Hooks,
+}
+
+/// Storage tier of an acquired page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageLocation {
+    /// Uncompressed in resident memory
+    Resident,
+
+    /// Compressed into a zswap page
+    Zswapped,
+
+    /// Dumped to the spill file
+    Spilled,
+}
+
+/// Access statistics of an acquired page for estimating the cost of using it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageAccess {
+    /// Storage tier of the page. Pages not in resident memory must be
+    /// faulted in before use.
+    pub location: PageLocation,
+
+    /// Number of times the page has been used
+    pub accesses: u64,
+
+    /// Number of times the page has been loaded back into resident memory
+    pub faults: u64,
+
+    /// Time the page was last used
+    pub last_access: Instant,
+}
+
+/// Snapshot of allocator counters for monitoring
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Size of a page in bytes
+    pub page_size: usize,
+
+    /// Acquired pages in resident memory
+    pub resident_pages: usize,
+
+    /// Acquired pages compressed into zswap
+    pub zswapped_pages: usize,
+
+    /// Acquired pages dumped to disk
+    pub spilled_pages: usize,
+
+    /// Pages recovered from a previous allocator's spill file, that are not
+    /// reclaimed yet
+    pub recovered_pages: usize,
+
+    /// Page-sized buffers currently allocated, including zswap and unused
+    /// buffers
+    pub allocated_buffers: usize,
+
+    /// Buffers used for storing zswapped pages
+    pub zswap_buffers: usize,
+
+    /// Unused buffers not yet returned to the operating system
+    pub free_buffers: usize,
+
+    /// Bytes of memory saved by compressing zswapped pages
+    pub compression_saved_bytes: usize,
+
+    /// Free bytes in zswap buffers, that are possibly fragmented
+    pub zswap_free_bytes: usize,
+
+    /// Number of pages loaded back into resident memory
+    pub page_faults: u64,
+
+    /// Number of pages compressed into zswap
+    pub evictions: u64,
+
+    /// Number of zswapped pages dumped to disk
+    pub spills: u64,
+
+    /// Usage of allocation scopes ordered by name
+    pub scopes: Vec<ScopeStats>,
+}
+
+impl AllocatorInner {
+    fn stats(&self) -> Stats {
+        let spilled_pages = self.spill.as_ref().map(|s| s.len()).unwrap_or(0)
+            - self.recovered.len();
+        let compressed: usize = self.zswapped.values().map(|l| l.size).sum();
+        Stats {
+            page_size: self.config.page_size,
+            resident_pages: self.handles.len()
+                - self.zswapped.len()
+                - spilled_pages,
+            zswapped_pages: self.zswapped.len(),
+            spilled_pages,
+            recovered_pages: self.recovered.len(),
+            allocated_buffers: self.resident,
+            zswap_buffers: self.zswap_pages.len(),
+            free_buffers: self.free_pages.len(),
+            compression_saved_bytes: self.zswapped.len()
+                * self.config.page_size
+                - compressed,
+            zswap_free_bytes: self.zswap_pages.len() * self.config.page_size
+                - compressed,
+            page_faults: self.page_faults,
+            evictions: self.evictions,
+            spills: self.spills,
+            scopes: {
+                let mut scopes: Vec<ScopeStats> =
+                    self.scopes.values().map(Into::into).collect();
+                scopes.sort_by(|a, b| a.name.cmp(&b.name));
+                scopes
+            },
+        }
+    }
+
+    /// Returns the access statistics of an acquired page as of the last merge
+    /// of pending usage times
+    fn page_access(&mut self, id: PageId) -> Option<PageAccess> {
+        let shared = self.handles.get(&id)?;
+        let (accesses, faults) = (
+            shared.accesses.load(Ordering::Relaxed),
+            shared.faults.load(Ordering::Relaxed),
+        );
+        Some(PageAccess {
+            location: self.lookup(id)?,
+            accesses,
+            faults,
+            last_access: self.pages.get(&id)?,
+        })
+    }
+
+    fn lookup(&self, id: PageId) -> Option<PageLocation> {
+        if !self.handles.contains_key(&id) {
+            None
+        } else if self.zswapped.contains_key(&id) {
+            Some(PageLocation::Zswapped)
+        } else if self.spill.as_ref().map(|s| s.contains(id)).unwrap_or(false) {
+            Some(PageLocation::Spilled)
+        } else {
+            Some(PageLocation::Resident)
+        }
+    }
+
+    fn configure(&mut self, config: AllocatorConfig) -> Result<(), AllocError> {
+        config.validate()?;
+        if !self.handles.is_empty() {
+            return Err(AllocError::InvalidConfig(
+                "can not configure allocator with acquired pages".into(),
+            ));
+        }
+
+        // Cached buffers may be of a different size
+        self.resident -= self.free_pages.clear();
+        #[cfg(unix)]
+        {
+            self.arena = None;
+        }
+
+        if config.eviction != self.config.eviction {
+            self.policy = config.eviction.build();
+        }
+        self.config = config;
+
+        // Page size, key or directory may differ
+        self.open_spill_dir()
+    }
+
+    /// Reject further page acquisitions and flush all zswapped pages to the
+    /// spill file
+    fn shutdown(&mut self) -> Result<(), AllocError> {
+        self.shut_down = true;
+
+        let ids: Vec<_> = self.zswapped.keys().copied().collect();
+        for id in ids {
+            self.spill(id)?;
+        }
+        if let Some(spill) = &self.spill {
+            spill.sync().map_err(AllocError::SpillIo)?;
+        }
+        Ok(())
+    }
+
+    /// Reopen the spill file left in `AllocatorConfig::spill_dir` by a
+    /// previous allocator, if any, and register its pages for recovery
+    fn open_spill_dir(&mut self) -> Result<(), AllocError> {
+        self.spill = None;
+        self.recovered.clear();
+        let path = match &self.config.spill_dir {
+            Some(dir) => dir.join(SPILL_FILE_NAME),
+            None => return Ok(()),
+        };
+
+        let mut spill = match SpillFile::open(
+            path,
+            self.config.page_size,
+            self.config.spill_key.as_ref(),
+        ) {
+            Ok(s) => s,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(())
+            }
+            Err(err) => return Err(AllocError::SpillIo(err)),
+        };
+        spill.persist();
+        spill.set_scrub(self.config.scrub);
+        self.recovered = spill.ids().collect();
+        if let Some(max) = self.recovered.iter().max() {
+            self.next_id = self.next_id.max(max + 1);
+        }
+        self.spill = Some(spill);
+        Ok(())
+    }
+
+    /// Acquire a page. `buf` is a buffer already taken from the free page pool,
+    /// if any.
+    fn get_page(
+        &mut self,
+        allocator: &Allocator,
+        scope: Option<ScopeId>,
+        buf: Option<Buffer>,
+        site: CallSite,
+    ) -> Result<Page, AllocError> {
+        self.get_pages(allocator, scope, 1, buf, site)
+            .map(|mut pages| pages.pop().unwrap())
+    }
+
+    /// Acquire a chain of pages holding `size` bytes
+    fn get_chain(
+        &mut self,
+        allocator: &Allocator,
+        scope: Option<ScopeId>,
+        size: usize,
+        site: CallSite,
+    ) -> Result<PageChain, AllocError> {
+        let page_size = self.config.page_size;
+        let n = PageChain::page_count(page_size, size);
+        let pages = self.get_pages(allocator, scope, n, None, site)?;
+        Ok(PageChain::new(pages, page_size, size))
+    }
+
+    /// Acquire `n` pages making room for all of them at once. Either all or
+    /// none of the pages are acquired.
+    ///
+    /// `buf` is a buffer already taken from the free page pool, if any.
+    fn get_pages(
+        &mut self,
+        allocator: &Allocator,
+        scope: Option<ScopeId>,
+        n: usize,
+        buf: Option<Buffer>,
+        site: CallSite,
+    ) -> Result<Vec<Page>, AllocError> {
+        let mut buffers = Vec::with_capacity(n);
+        buffers.extend(buf.and_then(|buf| self.current_size(buf)));
+        if let Err(err) = self.take_buffers(scope, n, &mut buffers) {
+            for buf in buffers {
+                self.return_buffer(buf);
+            }
+            return Err(err);
+        }
+        Ok(buffers
+            .into_iter()
+            .map(|buf| {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.register_page(allocator, id, scope, buf, site)
+            })
+            .collect())
+    }
+
+    /// Make room for and take buffers for `n` pages, including the ones
+    /// already in `buffers`
+    fn take_buffers(
+        &mut self,
+        scope: Option<ScopeId>,
+        n: usize,
+        buffers: &mut Vec<Buffer>,
+    ) -> Result<(), AllocError> {
+        if self.shut_down {
+            return Err(AllocError::ShutDown);
+        }
+        self.swap_cold_pages()?;
+        if let Some(scope) = scope {
+            self.enforce_quota(scope, n)?;
+        }
+        let missing = n - buffers.len();
+        if self.available_buffers() < missing {
+            self.reclaim(missing)?;
+        }
+        while buffers.len() < n {
+            buffers.push(self.take_internal_buffer()?);
+        }
+        Ok(())
+    }
+
+    /// Reclaim a page recovered from a reopened spill file without loading
+    /// it
+    fn recover(
+        &mut self,
+        allocator: &Allocator,
+        id: PageId,
+        site: CallSite,
+    ) -> Result<Page, AllocError> {
+        if self.shut_down {
+            return Err(AllocError::ShutDown);
+        }
+        if !self.recovered.remove(&id) {
+            return Err(AllocError::PageNotFound(id));
+        }
+        let page =
+            self.register_page(allocator, id, None, Buffer::null(), site);
+        self.policy.evict(id);
+        Ok(page)
+    }
+
+    /// Add a new page with the passed buffer to the page registry.
+    /// The page is swapped out, if the buffer is null.
+    fn register_page(
+        &mut self,
+        allocator: &Allocator,
+        id: PageId,
+        scope: Option<ScopeId>,
+        buffer: Buffer,
+        site: CallSite,
+    ) -> Page {
+        let resident = !buffer.ptr.is_null();
+        let shared = Arc::new(PageShared {
+            id,
+            allocator: allocator.clone(),
+            scope,
+            pins: AtomicUsize::new(0),
+            accesses: AtomicU64::new(0),
+            faults: AtomicU64::new(0),
+            acquired: sim::now(),
+            last_use: AtomicU64::new(0),
+            inner: RwLock::new(PageInner {
+                buffer,
+                frozen: None,
+                slots: None,
+            }),
+        });
+        self.pages.insert(id, sim::now());
+        self.policy.insert(id);
+        self.handles.insert(id, shared.clone());
+        if let Some(s) = scope.and_then(|s| self.scopes.get_mut(&s)) {
+            s.acquired += 1;
+            if resident {
+                s.resident += 1;
+            }
+        }
+        #[cfg(feature = "alloc-tracing")]
+        self.trace.acquire(
+            id,
+            scope
+                .and_then(|s| self.scopes.get(&s))
+                .map(|s| s.name.clone()),
+            site,
+            self.config.page_size,
+        );
+        #[cfg(not(feature = "alloc-tracing"))]
+        let _ = site;
+
+        Page(shared)
+    }
+
+    /// Remove a dropped page from the allocator. Returns the page's frozen
+    /// contents, that must be dropped without holding the allocator lock, and
+    /// its buffer to wipe and add to the free page pool without holding it.
+    fn release_page(
+        &mut self,
+        shared: &mut Arc<PageShared>,
+    ) -> (Option<Arc<Frozen>>, Option<Buffer>) {
+        let id = shared.id;
+        self.pages.remove(&id);
+        self.policy.remove(id);
+        self.handles.remove(&id);
+        if let Some(loc) = self.zswapped.remove(&id) {
+            self.free_zswapped(loc);
+        }
+        self.checksums.remove(&id);
+        if !self.shut_down {
+            if let Some(spill) = &mut self.spill {
+                spill.remove(id);
+            }
+        }
+        self.prefetched.remove(&id);
+        #[cfg(feature = "alloc-tracing")]
+        self.trace.release(id);
+
+        // Only the page itself can be holding a reference after removal from
+        // the registry
+        let p = Arc::get_mut(shared)
+            .unwrap()
+            .inner
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let resident = p.is_resident();
+        let frozen = p.frozen.take();
+        let buf = if p.buffer.ptr.is_null() {
+            None
+        } else {
+            let buf = std::mem::replace(&mut p.buffer, Buffer::null());
+            self.keep_buffer(buf)
+        };
+
+        if let Some(scope) = shared.scope {
+            if let Some(s) = self.scopes.get_mut(&scope) {
+                s.acquired -= 1;
+                if resident {
+                    s.resident -= 1;
+                }
+                if s.closed && s.acquired == 0 {
+                    self.scopes.remove(&scope);
+                }
+            }
+        }
+
+        (frozen, buf)
+    }
+
+    /// Acquire a page, only if it can be made resident without swapping out
+    /// other pages
+    fn try_get_page(
+        &mut self,
+        allocator: &Allocator,
+        scope: Option<ScopeId>,
+        site: CallSite,
+    ) -> Result<Page, AllocError> {
+        if self.shut_down {
+            return Err(AllocError::ShutDown);
+        }
+        let at_quota = scope
+            .and_then(|s| self.scopes.get(&s))
+            .map(|s| s.at_quota())
+            .unwrap_or(false);
+        if at_quota || self.available_buffers() == 0 {
+            return Err(AllocError::WouldBlock);
+        }
+        self.get_page(allocator, scope, None, site)
+    }
+
+    /// Returns the scope a page was acquired through, if any
+    fn scope_of(&self, id: PageId) -> Option<ScopeId> {
+        self.handles.get(&id)?.scope
+    }
+
+    /// Record a page of a scope entering or leaving resident memory
+    fn account_resident(&mut self, id: PageId, resident: bool) {
+        if let Some(s) = self.scope_of(id).and_then(|s| self.scopes.get_mut(&s))
+        {
+            if resident {
+                s.resident += 1;
+            } else {
+                s.resident -= 1;
+            }
+        }
+    }
+
+    /// Swap out pages of a scope, until `n` more of its pages can be made
+    /// resident without exceeding the scope's quota
+    fn enforce_quota(
+        &mut self,
+        scope: ScopeId,
+        n: usize,
+    ) -> Result<(), AllocError> {
+        match self.scopes.get(&scope) {
+            Some(s) if s.over_quota(n) => (),
+            _ => return Ok(()),
+        }
+
+        let candidates: Vec<_> = self
+            .eviction_candidates(usize::MAX)
+            .into_iter()
+            .filter(|id| self.scope_of(*id) == Some(scope))
+            .collect();
+        for id in candidates {
+            self.zswap(id)?;
+            if !self.scopes[&scope].over_quota(n) {
+                return Ok(());
+            }
+        }
+        Err(AllocError::QuotaExceeded(self.scopes[&scope].name.clone()))
+    }
+
+    /// Returns the number of buffers, that can be taken without exceeding the
+    /// resident memory budget
+    fn available_buffers(&self) -> usize {
+        let headroom = match self.config.max_resident {
+            Some(max) => {
+                (max / self.config.page_size).saturating_sub(self.resident)
+            }
+            None => usize::MAX,
+        };
+        self.free_pages.len().saturating_add(headroom)
+    }
+
+    /// Returns, if allocating `n` more buffers would exceed the resident
+    /// memory budget
+    fn over_budget(&self, n: usize) -> bool {
+        match self.config.max_resident {
+            Some(max) => (self.resident + n) * self.config.page_size > max,
+            None => false,
+        }
+    }
+
+    /// Take an unused buffer for a `Page`, swapping out least recently used
+    /// pages, if the resident memory budget is exhausted
+    fn take_buffer(&mut self) -> Result<Buffer, AllocError> {
+        if self.available_buffers() == 0 {
+            self.reclaim(1)?;
+        }
+        self.take_internal_buffer()
+    }
+
+    /// Take an unused buffer from the free page pool or allocate a new one
+    /// without enforcing the resident memory budget
+    ///
+    /// Buffers are taken from the NUMA node of the calling thread, if possible.
+    fn take_internal_buffer(&mut self) -> Result<Buffer, AllocError> {
+        let node = numa::current_node();
+        while let Some(buf) = self.free_pages.pop(node) {
+            if let Some(buf) = self.current_size(buf) {
+                return Ok(buf);
+            }
+        }
+
+        // Prefer allocating memory on the local node, while within budget
+        if self.over_budget(1) {
+            while let Some(buf) = self.free_pages.pop_any() {
+                if let Some(buf) = self.current_size(buf) {
+                    return Ok(buf);
+                }
+            }
+        }
+
+        // Huge page arenas are shared between nodes
+        #[cfg(unix)]
+        let buf = if self.config.huge_pages {
+            self.carve_buffer()?
+        } else {
+            Buffer::new(self.config.page_size, node)
+        };
+        #[cfg(not(unix))]
+        let buf = Buffer::new(self.config.page_size, node);
+        if buf.ptr.is_null() {
+            return Err(AllocError::OutOfMemory);
+        }
+        self.resident += 1;
+        Ok(buf)
+    }
+
+    /// Carve a new buffer out of the current huge page arena, mapping a new
+    /// arena, if exhausted
+    #[cfg(unix)]
+    fn carve_buffer(&mut self) -> Result<Buffer, AllocError> {
+        let size = self.config.page_size;
+        if let Some(buf) = self
+            .arena
+            .as_ref()
+            .and_then(|a| Buffer::from_arena(a, size))
+        {
+            return Ok(buf);
+        }
+
+        let arena = Arc::new(Arena::new(size).ok_or(AllocError::OutOfMemory)?);
+        let buf = Buffer::from_arena(&arena, size).unwrap();
+        self.arena = Some(arena);
+        Ok(buf)
+    }
+
+    /// Return an unused buffer to the free page pool or free it, if over the
+    /// resident memory budget
+    fn return_buffer(&mut self, buf: Buffer) {
+        if let Some(mut buf) = self.keep_buffer(buf) {
+            buf.wipe(self.config.scrub);
+            self.free_pages.push(buf);
+        }
+    }
+
+    /// Free an unused buffer, if over the resident memory budget. Otherwise
+    /// return it for adding to the free page pool.
+    fn keep_buffer(&mut self, buf: Buffer) -> Option<Buffer> {
+        if self.over_budget(0) {
+            // Dropping the buffer returns its memory to the OS
+            self.resident -= 1;
+            drop(buf);
+            None
+        } else {
+            Some(buf)
+        }
+    }
+
+    /// Free a buffer taken from the free page pool, if it was added to the
+    /// pool with the page size from before `configure()`
+    fn current_size(&mut self, buf: Buffer) -> Option<Buffer> {
+        if buf.size == self.config.page_size {
+            Some(buf)
+        } else {
+            self.resident -= 1;
+            None
+        }
+    }
+
+    /// Release unused buffers and swap out pages chosen by the eviction policy,
+    /// until at most `max` page-sized buffers are allocated
+    fn shrink(&mut self, max: usize) -> Result<(), AllocError> {
+        let candidates = self.eviction_candidates(usize::MAX);
+
+        // Compressing pages is cheaper than writing them to disk, so try that
+        // first
+        let mut zswap = candidates.iter().copied();
+        let mut spill = candidates.iter().copied();
+        loop {
+            while self.resident > max {
+                match self.free_pages.pop_any() {
+                    Some(buf) => {
+                        self.resident -= 1;
+                        drop(buf);
+                    }
+                    None => break,
+                }
+            }
+            if self.resident <= max {
+                return Ok(());
+            }
+
+            match zswap.next() {
+                Some(id) => self.zswap(id)?,
+                None => match spill.next() {
+                    Some(id) => self.spill(id)?,
+                    None => return Err(AllocError::BudgetExceeded),
+                },
+            }
+        }
+    }
+
+    /// Swap out least recently used pages regardless of their age, until `n`
+    /// buffers can be taken without exceeding the resident memory budget
+    fn reclaim(&mut self, n: usize) -> Result<(), AllocError> {
+        let candidates = self.eviction_candidates(usize::MAX);
+
+        // Compressing pages is cheaper than writing them to disk, so try that
+        // first
+        for id in candidates.iter() {
+            if self.available_buffers() >= n {
+                return Ok(());
+            }
+            self.zswap(*id)?;
+        }
+        for id in candidates {
+            if self.available_buffers() >= n {
+                return Ok(());
+            }
+            self.spill(id)?;
+        }
+
+        if self.available_buffers() >= n {
+            Ok(())
+        } else {
+            self.hooks.budget_exceeded(n);
+            Err(AllocError::BudgetExceeded)
+        }
+    }
+
+    /// Compress resident pages and dump zswapped pages to disk based on their
+    /// last usage time and the number of zswap pages
+    fn swap_cold_pages(&mut self) -> Result<(), AllocError> {
+        self.merge_usage();
+
+        let now = sim::now();
+        let may_spill = match self.last_spill {
+            Some(t) => now.duration_since(t) >= self.config.spill_interval,
+            None => true,
+        };
+        let spills = self.spills;
+
+        let (zswap_age, spill_age) =
+            (self.config.zswap_age, self.config.spill_age);
+        let cold: Vec<_> = self
+            .pages
+            .iter()
+            .take_while(|(_, used)| now.duration_since(*used) >= zswap_age)
+            .collect();
+        for (id, used) in cold {
+            if self.zswapped.contains_key(&id) {
+                if may_spill && now.duration_since(used) >= spill_age {
+                    self.spill(id)?;
+                }
+            } else {
+                self.zswap(id)?;
+            }
+        }
+
+        let max = self.config.max_zswap_pages;
+        if may_spill && self.zswap_pages.len() > max {
+            let zswapped = &self.zswapped;
+            let candidates: Vec<_> = self
+                .policy
+                .candidates()
+                .into_iter()
+                .filter(|id| zswapped.contains_key(id))
+                .collect();
+            for id in candidates {
+                if self.zswap_pages.len() <= max {
+                    break;
+                }
+                self.spill(id)?;
+            }
+        }
+
+        if self.spills != spills {
+            self.last_spill = Some(now);
+        }
+        Ok(())
+    }
+
+    /// Compress a resident page into a zswap page and free its buffer.
+    ///
+    /// Pages currently in use, pinned or not benefiting from compression are
+    /// skipped.
+    fn zswap(&mut self, id: PageId) -> Result<(), AllocError> {
+        let shared = match self.handles.get(&id) {
+            Some(s) => s.clone(),
+            None => return Ok(()),
+        };
+        let mut p = match shared.inner.try_write() {
+            Ok(p) => p,
+            Err(_) => return Ok(()),
+        };
+        if p.buffer.ptr.is_null() || shared.pins.load(Ordering::Acquire) != 0 {
+            return Ok(());
+        }
+
+        let compressed = lz4::block::compress(&p.buffer, None, false)
+            .map_err(AllocError::Compression)?;
+        if compressed.len() >= self.config.page_size {
+            return Ok(());
+        }
+
+        let loc = self.store_zswapped(&compressed)?;
+        self.zswapped.insert(id, loc);
+        self.checksums.insert(id, crc32::checksum(&p.buffer));
+        self.policy.evict(id);
+        self.account_resident(id, false);
+        let buf = std::mem::replace(&mut p.buffer, Buffer::null());
+        self.return_buffer(buf);
+        self.evictions += 1;
+        self.hooks.evict(id);
+
+        Ok(())
+    }
+
+    /// Store compressed page data in the first zswap page with enough space
+    fn store_zswapped(
+        &mut self,
+        data: &[u8],
+    ) -> Result<ZswapLocation, AllocError> {
+        for z in self.zswap_pages.values_mut() {
+            if let Some(loc) = z.store(data) {
+                return Ok(loc);
+            }
+        }
+
+        let mut z =
+            ZswapPage::new(self.next_zswap_id, self.take_internal_buffer()?);
+        self.next_zswap_id += 1;
+        // Data is always smaller than a page
+        let loc = z.store(data).unwrap();
+        self.zswap_pages.insert(z.id, z);
+        Ok(loc)
+    }
+
+    /// Return the zswap page containing the location
+    fn zswap_page(&mut self, loc: ZswapLocation) -> &mut ZswapPage {
+        self.zswap_pages
+            .get_mut(&loc.zswap_page)
+            .expect("zswap page not found")
+    }
+
+    /// Free zswapped page data and release the containing zswap page, if it
+    /// is no longer used
+    fn free_zswapped(&mut self, loc: ZswapLocation) {
+        let scrub = self.config.scrub;
+        let z = self.zswap_page(loc);
+        if scrub {
+            z.buf[loc.offset..loc.offset + loc.size].fill(0);
+        }
+        z.free_list
+            .free(loc.offset, loc.size)
+            .expect("zswap free list corrupted");
+        z.stored -= 1;
+
+        if z.stored == 0 {
+            let z = self.zswap_pages.remove(&loc.zswap_page).unwrap();
+            self.return_buffer(z.buf);
+        }
+    }
+
+    /// Rebuild up to `n` zswap pages from the back of the queue by moving
+    /// their contents into free space of existing zswap pages closer to the
+    /// front.
+    ///
+    /// No zswap pages are acquired, so the zswap area never grows. Data, that
+    /// does not fit into a page closer to the front, is kept in place.
+    fn defragment(&mut self, n: usize) {
+        // Not worth it, unless at least one zswap page can be freed
+        let page_size = self.config.page_size;
+        let compressed: usize = self.zswapped.values().map(|l| l.size).sum();
+        if self.zswap_pages.len() * page_size - compressed < page_size {
+            return;
+        }
+
+        let ids: Vec<_> =
+            self.zswap_pages.keys().rev().take(n).copied().collect();
+        for zswap_id in ids {
+            let mut z = self.zswap_pages.remove(&zswap_id).unwrap();
+
+            let contained: Vec<_> = self
+                .zswapped
+                .iter()
+                .filter(|(_, loc)| loc.zswap_page == zswap_id)
+                .map(|(id, loc)| (*id, *loc))
+                .collect();
+            for (id, loc) in contained {
+                let data = &z.buf[loc.offset..loc.offset + loc.size];
+                let new = self
+                    .zswap_pages
+                    .range_mut(..zswap_id)
+                    .find_map(|(_, front)| front.store(data));
+                if let Some(new) = new {
+                    self.zswapped.insert(id, new);
+                    z.free_list
+                        .free(loc.offset, loc.size)
+                        .expect("zswap free list corrupted");
+                    z.stored -= 1;
+                }
+            }
+
+            if z.stored == 0 {
+                self.return_buffer(z.buf);
+            } else {
+                self.zswap_pages.insert(z.id, z);
+            }
+        }
+    }
+
+    /// Dump a zswapped page to the spill file, unless vetoed by a registered
+    /// hook
+    fn spill(&mut self, id: PageId) -> Result<(), AllocError> {
+        let loc = match self.zswapped.get(&id) {
+            Some(loc) => *loc,
+            None => return Ok(()),
+        };
+        if !self.shut_down && !self.hooks.spill(id) {
+            return Ok(());
+        }
+        let data = self.zswap_page(loc).buf[loc.offset..loc.offset + loc.size]
+            .to_vec();
+
+        if self.spill.is_none() {
+            // Distinguishes spill files of allocators in the same process
+            static SPILL_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
+            let path = match &self.config.spill_dir {
+                Some(dir) => dir.join(SPILL_FILE_NAME),
+                None => std::env::temp_dir().join(format!(
+                    "pdb-{}-{}.spill",
+                    std::process::id(),
+                    SPILL_FILE_ID.fetch_add(1, Ordering::Relaxed)
+                )),
+            };
+            let mut spill = SpillFile::create(
+                path,
+                self.config.page_size,
+                self.config.spill_key.as_ref(),
+            )
+            .map_err(AllocError::SpillIo)?;
+            if self.config.spill_dir.is_some() {
+                spill.persist();
+            }
+            spill.set_scrub(self.config.scrub);
+            self.spill = Some(spill);
+        }
+        self.spill
+            .as_mut()
+            .unwrap()
+            .write(id, &data)
+            .map_err(AllocError::SpillIo)?;
+
+        self.zswapped.remove(&id);
+        self.free_zswapped(loc);
+        self.compact_zswap_page(loc.zswap_page);
+        self.spills += 1;
+        Ok(())
+    }
+
+    /// Move the remaining contents of a zswap page to its front, so its free
+    /// memory is a single range at the back, unless it is contiguous already
+    fn compact_zswap_page(&mut self, zswap_id: u64) {
+        let z = match self.zswap_pages.get_mut(&zswap_id) {
+            Some(z) => z,
+            None => return,
+        };
+        if z.free_list.fragmentation() == 0.0 {
+            // Free memory is contiguous already
+            return;
+        }
+        let mut contained: Vec<_> = self
+            .zswapped
+            .values_mut()
+            .filter(|loc| loc.zswap_page == zswap_id)
+            .collect();
+        contained.sort_unstable_by_key(|loc| loc.offset);
+
+        // Allocating in order of offset only ever moves data to the front
+        let mut free_list = FreeList::new(self.config.page_size);
+        let mut end = 0;
+        for loc in contained {
+            let offset = match free_list.allocate(loc.size) {
+                AllocationResult::Allocated(offset) => offset,
+                AllocationResult::NotFound(_) => {
+                    unreachable!("zswap page contents exceed its size")
+                }
+            };
+            z.buf.copy_within(loc.offset..loc.offset + loc.size, offset);
+            loc.offset = offset;
+            end = offset + loc.size;
+        }
+        if self.config.scrub {
+            z.buf[end..].fill(0);
+        }
+        z.free_list = free_list;
+    }
+
+    /// Load a swapped out page back into resident memory.
+    ///
+    /// Zswapped pages are loaded immediately. For spilled pages a read is
+    /// started and returned, that must be performed without holding the
+    /// allocator lock and passed to `finish_fault_in()`.
+    fn fault_in(
+        &mut self,
+        id: PageId,
+        p: &mut PageInner,
+    ) -> Result<Option<PendingRead>, AllocError> {
+        match self.zswapped.get(&id) {
+            Some(loc) => {
+                let loc = *loc;
+                let data = self.zswap_page(loc).buf
+                    [loc.offset..loc.offset + loc.size]
+                    .to_vec();
+                self.load(id, p, &data)?;
+                Ok(None)
+            }
+            None => self
+                .spill
+                .as_mut()
+                .and_then(|s| s.begin_read(id))
+                .map(Some)
+                .ok_or(AllocError::PageNotFound(id)),
+        }
+    }
+
+    /// Complete loading a spilled page with the result of its read started by
+    /// `fault_in()`
+    fn finish_fault_in(
+        &mut self,
+        id: PageId,
+        p: &mut PageInner,
+        read: PendingRead,
+        res: std::io::Result<Vec<u8>>,
+    ) -> Result<(), AllocError> {
+        let current = self.spill.as_mut().unwrap().finish_read(read);
+        let data = res.map_err(AllocError::SpillIo)?;
+        if !current {
+            // Only possible, if the page was loaded by another thread in the
+            // meantime, which requires the page lock held by the caller
+            return Err(AllocError::PageNotFound(id));
+        }
+        self.load(id, p, &data)
+    }
+
+    /// Load a swapped out page ahead of its use without blocking on it.
+    ///
+    /// Returns a read, that must be performed without holding the allocator
+    /// lock and passed to `finish_prefetch()`, if the page is spilled.
+    fn begin_prefetch(&mut self, id: PageId) -> Option<PendingRead> {
+        if self.shut_down {
+            return None;
+        }
+        let shared = self.handles.get(&id)?.clone();
+
+        // A page locked by another thread is being accessed already
+        let mut p = shared.inner.try_write().ok()?;
+        if p.is_resident() {
+            return None;
+        }
+        match self.fault_in(id, &mut p) {
+            Ok(None) => {
+                self.prefetched.insert(id, sim::now());
+                None
+            }
+            Ok(Some(read)) => Some(read),
+            Err(_) => None,
+        }
+    }
+
+    /// Complete prefetching a spilled page with the result of its read
+    /// started by `begin_prefetch()`
+    fn finish_prefetch(
+        &mut self,
+        id: PageId,
+        read: PendingRead,
+        res: std::io::Result<Vec<u8>>,
+    ) {
+        // Page might have been released, loaded or locked in the meantime
+        let shared = self.handles.get(&id).cloned();
+        let p = shared.as_ref().and_then(|s| s.inner.try_write().ok());
+        match p {
+            Some(mut p) if !p.is_resident() && !self.shut_down => {
+                if self.finish_fault_in(id, &mut p, read, res).is_ok() {
+                    self.prefetched.insert(id, sim::now());
+                }
+            }
+            _ => {
+                self.spill.as_mut().unwrap().finish_read(read);
+            }
+        }
+    }
+
+    /// Decompress page data into a new resident buffer for the page and verify
+    /// its checksum.
+    ///
+    /// The swapped out copy of the page is only discarded on success.
+    fn load(
+        &mut self,
+        id: PageId,
+        p: &mut PageInner,
+        compressed: &[u8],
+    ) -> Result<(), AllocError> {
+        if let Some(scope) = self.scope_of(id) {
+            self.enforce_quota(scope, 1)?;
+        }
+        let mut buffer = self.take_buffer()?;
+        if let Err(err) = lz4::block::decompress_to_buffer(
+            compressed,
+            Some(self.config.page_size as i32),
+            &mut buffer,
+        ) {
+            self.return_buffer(buffer);
+            return Err(AllocError::Compression(err));
+        }
+        if let Some(sum) = self.checksums.get(&id) {
+            if crc32::checksum(&buffer) != *sum
+                || sim::triggered(Fault::Checksum)
+            {
+                self.return_buffer(buffer);
+                return Err(AllocError::Corrupted(id));
+            }
+        }
+        p.buffer = buffer;
+
+        // Making room for the buffer might have moved the page between tiers
+        self.checksums.remove(&id);
+        if let Some(loc) = self.zswapped.remove(&id) {
+            self.free_zswapped(loc);
+        }
+        if let Some(spill) = &mut self.spill {
+            spill.remove(id);
+        }
+        self.pages.bump(&id, sim::now());
+        self.policy.access(id);
+        self.account_resident(id, true);
+        self.page_faults += 1;
+        if let Some(shared) = self.handles.get(&id) {
+            shared.faults.fetch_add(1, Ordering::Relaxed);
+        }
+        self.hooks.fault_in(id);
+
+        Ok(())
+    }
+
+    /// Merge page usage times recorded without holding the allocator lock into
+    /// the page registry
+    fn merge_usage(&mut self) {
+        let mut pending: Vec<_> = self
+            .handles
+            .values()
+            .filter_map(|p| match p.last_use.swap(0, Ordering::Relaxed) {
+                0 => None,
+                n => Some((p.id, p.acquired + Duration::from_nanos(n - 1))),
+            })
+            .collect();
+
+        // Apply in chronological order to keep the registry ordered
+        pending.sort_unstable_by_key(|(_, used)| *used);
+        for (id, used) in pending {
+            if self.pages.bump(&id, used) {
+                self.policy.access(id);
+            }
+        }
+    }
+
+    /// Return the IDs of up to `n` best candidates for swapping out according
+    /// to the eviction policy
+    fn eviction_candidates(&mut self, n: usize) -> Vec<PageId> {
+        self.merge_usage();
+        let mut ids = self.policy.candidates();
+
+        if !self.prefetched.is_empty() {
+            let now = sim::now();
+            let protection = self.config.prefetch_protection;
+            self.prefetched
+                .retain(|_, loaded| now.duration_since(*loaded) < protection);
+
+            let prefetched = &self.prefetched;
+            let (mut unprotected, protected): (Vec<_>, Vec<_>) =
+                ids.into_iter().partition(|id| !prefetched.contains_key(id));
+            unprotected.extend(protected);
+            ids = unprotected;
+        }
+
+        ids.truncate(n);
+        ids
+    }
+}
+
+/// Start a thread periodically performing maintenance of an allocator and
+/// prefetching pages queued in between, until it is dropped or shut down
+fn spawn_maintenance(
+    allocator: Weak<AllocatorShared>,
+    queue: Receiver<Vec<PageId>>,
+) -> JoinHandle<()> {
+    std::thread::Builder::new()
+        .name("pdb-alloc-maintenance".into())
+        .spawn(move || loop {
+            let interval = allocator.upgrade().and_then(|a| {
+                Allocator(a).with(|a| {
+                    if a.shut_down {
+                        return None;
+                    }
+
+                    a.defragment(a.config.defrag_pages);
+                    Some(a.config.defrag_interval)
+                })
+            });
+            let deadline = match interval {
+                Some(interval) => Instant::now() + interval,
+                None => return,
+            };
+
+            loop {
+                let timeout =
+                    deadline.saturating_duration_since(Instant::now());
+                let ids = match queue.recv_timeout(timeout) {
+                    Ok(ids) => ids,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    // Disconnected by `Allocator::shutdown()` or dropping
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+                for id in ids {
+                    let a = match allocator.upgrade() {
+                        Some(a) => Allocator(a),
+                        None => return,
+                    };
+                    if let Some(read) = a.with(|a| a.begin_prefetch(id)) {
+                        let res = read.read();
+                        a.with(|a| a.finish_prefetch(id, read, res));
+                    }
+                }
+            }
+        })
+        .expect("failed to spawn allocator maintenance thread")
+}
+
+/// Acquire a page for column, index and aggregate allocations from the global
+/// allocator
+#[track_caller]
+pub fn get_page() -> Result<Page, AllocError> {
+    Allocator::global().get_page()
+}
+
+/// Take a snapshot of the global allocator's counters
+pub fn stats() -> Stats {
+    Allocator::global().stats()
+}
+
+/// Replace the configuration of the global allocator.
+///
+/// Can only be called, while no pages are acquired.
+pub fn configure(config: AllocatorConfig) -> Result<(), AllocError> {
+    Allocator::global().configure(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touch_reorders_eviction_candidates() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let a = alloc.get_page().unwrap();
+        let b = alloc.get_page().unwrap();
+        assert_ne!(a.id(), b.id());
+
+        let candidates = || alloc.with(|a| a.eviction_candidates(usize::MAX));
+        assert_eq!(candidates(), [a.id(), b.id()]);
+
+        a.touch();
+        assert_eq!(candidates(), [b.id(), a.id()]);
+
+        drop(b);
+        assert_eq!(candidates(), [a.id()]);
+    }
+
+    #[test]
+    fn access_records_usage() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let pages: Vec<_> = (0..3).map(|_| alloc.get_page().unwrap()).collect();
+        let ids: Vec<_> = pages.iter().map(|p| p.id()).collect();
+        let candidates = || alloc.with(|a| a.eviction_candidates(usize::MAX));
+
+        pages[0].read().unwrap();
+        assert_eq!(candidates(), [ids[1], ids[2], ids[0]]);
+        pages[1].write().unwrap();
+        assert_eq!(candidates(), [ids[2], ids[0], ids[1]]);
+
+        // Held guards keep the page resident
+        let g = pages[2].read().unwrap();
+        alloc.with(|a| a.zswap(ids[2])).unwrap();
+        assert_eq!(alloc.lookup(ids[2]), Some(PageLocation::Resident));
+        drop(g);
+        alloc.with(|a| a.zswap(ids[2])).unwrap();
+        assert_eq!(alloc.lookup(ids[2]), Some(PageLocation::Zswapped));
+    }
+
+    #[test]
+    fn custom_eviction_policy() {
+        /// Evicts most recently acquired pages first
+        #[derive(Default)]
+        struct Mru(Vec<PageId>);
+
+        impl EvictionPolicy for Mru {
+            fn insert(&mut self, id: PageId) {
+                self.0.push(id);
+            }
+
+            fn access(&mut self, _: PageId) {}
+
+            fn evict(&mut self, _: PageId) {}
+
+            fn remove(&mut self, id: PageId) {
+                self.0.retain(|i| *i != id);
+            }
+
+            fn candidates(&mut self) -> Vec<PageId> {
+                self.0.iter().rev().copied().collect()
+            }
+        }
+
+        let alloc = Allocator::with_policy(
+            Default::default(),
+            Box::new(Mru::default()),
+        )
+        .unwrap();
+        let pages: Vec<_> = (0..4).map(|_| alloc.get_page().unwrap()).collect();
+        pages[0].touch();
+
+        let mut expected: Vec<_> = pages.iter().map(|p| p.id()).collect();
+        expected.reverse();
+        let candidates = || alloc.with(|a| a.eviction_candidates(usize::MAX));
+        assert_eq!(candidates(), expected);
+
+        drop(pages);
+        assert!(candidates().is_empty());
+    }
+
+    #[test]
+    fn independent_allocators() {
+        let a = Allocator::new(Default::default()).unwrap();
+        let b = Allocator::new(AllocatorConfig {
+            page_size: 16 << 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let pa = a.get_page().unwrap();
+        let pb = b.get_page().unwrap();
+        assert_eq!(pa.read().unwrap().len(), DEFAULT_PAGE_SIZE);
+        assert_eq!(pb.read().unwrap().len(), 16 << 10);
+        assert_eq!(a.stats().resident_pages, 1);
+        assert_eq!(b.stats().resident_pages, 1);
+
+        a.with(|a| a.zswap(pa.id())).unwrap();
+        b.with(|b| b.zswap(pb.id())).unwrap();
+        a.with(|a| a.spill(pa.id())).unwrap();
+        assert_eq!(a.stats().spills, 1);
+        assert_eq!(b.stats().spills, 0);
+        assert_eq!(b.stats().zswapped_pages, 1);
+
+        // Pages keep their allocator alive
+        drop(a);
+        assert!(pa.read().unwrap().iter().all(|b| *b == 0));
+        drop(pa);
+
+        drop(pb);
+        assert_eq!(b.stats().zswapped_pages, 0);
+        assert_eq!(b.stats().resident_pages, 0);
+    }
+
+    #[test]
+    fn configure_page_size() {
+        let a = Allocator::new(Default::default()).unwrap();
+        for page_size in [1000, 512] {
+            assert!(matches!(
+                a.configure(AllocatorConfig {
+                    page_size,
+                    ..Default::default()
+                }),
+                Err(AllocError::InvalidConfig(_))
+            ));
+        }
+
+        a.configure(AllocatorConfig {
+            page_size: 16 << 10,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(a.get_page().unwrap().read().unwrap().len(), 16 << 10);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn huge_pages() {
+        assert!(matches!(
+            Allocator::new(AllocatorConfig {
+                page_size: 2 * HUGE_PAGE_SIZE,
+                huge_pages: true,
+                ..Default::default()
+            }),
+            Err(AllocError::InvalidConfig(_))
+        ));
+
+        let a = Allocator::new(AllocatorConfig {
+            huge_pages: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Span more than one arena
+        let n = 2 * HUGE_PAGE_SIZE / DEFAULT_PAGE_SIZE + 1;
+        let pages: Vec<_> = (0..n).map(|_| a.get_page().unwrap()).collect();
+        for (i, p) in pages.iter().enumerate() {
+            let mut g = p.write().unwrap();
+            assert!(g.iter().all(|b| *b == 0));
+            g.fill(i as u8);
+        }
+        for (i, p) in pages.iter().enumerate() {
+            assert!(p.read().unwrap().iter().all(|b| *b == i as u8));
+        }
+
+        let first = pages[0].0.inner.read().unwrap().buffer.ptr as usize;
+        assert_eq!(first % DEFAULT_PAGE_SIZE, 0);
+        assert_eq!(
+            pages[1].0.inner.read().unwrap().buffer.ptr as usize,
+            first + DEFAULT_PAGE_SIZE
+        );
+
+        a.with(|a| a.zswap(pages[0].id())).unwrap();
+        assert!(pages[0].read().unwrap().iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn free_pages_per_node() {
+        let free = FreePages::default();
+        for node in [0, 2, 2, 1] {
+            free.push(Buffer::new(DEFAULT_PAGE_SIZE, node));
+        }
+        assert_eq!(free.len(), 4);
+
+        assert_eq!(free.pop(2).unwrap().node, 2);
+        assert_eq!(free.pop(2).unwrap().node, 2);
+        assert!(free.pop(2).is_none());
+        assert!(free.pop(3).is_none());
+        assert_eq!(free.pop_any().unwrap().node, 0);
+        assert_eq!(free.pop_any().unwrap().node, 1);
+        assert!(free.pop_any().is_none());
+        assert!(free.is_empty());
+    }
+
+    #[test]
+    fn drop_free_buffers_of_previous_page_size() {
+        let a = Allocator::new(Default::default()).unwrap();
+        drop(a.get_page().unwrap());
+        assert_eq!(a.stats().free_buffers, 1);
+
+        // Buffer of a page dropped concurrently with reconfiguration
+        let buf = a.0.free_pages.pop_any().unwrap();
+        a.configure(AllocatorConfig {
+            page_size: 2 * DEFAULT_PAGE_SIZE,
+            ..Default::default()
+        })
+        .unwrap();
+        a.0.free_pages.push(buf);
+
+        let page = a.get_page().unwrap();
+        assert_eq!(page.read().unwrap().len(), 2 * DEFAULT_PAGE_SIZE);
+        let stats = a.stats();
+        assert_eq!(stats.allocated_buffers, 1);
+        assert_eq!(stats.free_buffers, 0);
+    }
+
+    #[test]
+    fn resident_budget() {
+        let a = Allocator::new(AllocatorConfig {
+            max_resident: Some(4 * DEFAULT_PAGE_SIZE),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Zeroed pages compress well, so all fit into a single zswap page
+        let pages: Vec<_> = (0..16).map(|_| a.get_page().unwrap()).collect();
+        let stats = a.stats();
+        assert!(stats.allocated_buffers <= 5);
+        assert!(stats.zswapped_pages >= 12);
+        assert_eq!(stats.resident_pages + stats.zswapped_pages, 16);
+        assert_eq!(stats.evictions, stats.zswapped_pages as u64);
+        assert!(
+            stats.compression_saved_bytes
+                > stats.zswapped_pages * (DEFAULT_PAGE_SIZE - 64)
+        );
+
+        drop(pages);
+        let stats = a.stats();
+        assert!(stats.allocated_buffers <= 4);
+        assert_eq!(stats.zswapped_pages, 0);
+        assert_eq!(stats.zswap_buffers, 0);
+    }
+
+    #[test]
+    fn resident_budget_exceeded() {
+        let a = Allocator::new(AllocatorConfig {
+            max_resident: Some(2 * DEFAULT_PAGE_SIZE),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Fill pages with data, that can not be compressed
+        let mut rng = crate::rng::Rng::new(1);
+        let mut pages = Vec::new();
+        for _ in 0..2 {
+            let p = a.get_page().unwrap();
+            rng.fill(&mut p.0.inner.write().unwrap().buffer);
+            pages.push(p);
+        }
+
+        let exceeded = Arc::new(AtomicUsize::new(0));
+        let e = exceeded.clone();
+        a.on_budget_exceeded(move |n| {
+            e.fetch_add(n, Ordering::Relaxed);
+        });
+        assert!(matches!(a.get_page(), Err(AllocError::BudgetExceeded)));
+        assert_eq!(exceeded.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn backpressure() {
+        let a = Allocator::new(AllocatorConfig {
+            max_resident: Some(2 * DEFAULT_PAGE_SIZE),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Fill pages with data, that can not be compressed
+        let mut rng = crate::rng::Rng::new(1);
+        let mut pages = Vec::new();
+        for _ in 0..2 {
+            let p = a.try_get_page().unwrap();
+            rng.fill(&mut p.write().unwrap());
+            pages.push(p);
+        }
+        assert!(matches!(a.try_get_page(), Err(AllocError::WouldBlock)));
+
+        let timeout = Duration::from_millis(20);
+        let start = Instant::now();
+        assert!(matches!(
+            a.get_page_timeout(timeout),
+            Err(AllocError::BudgetExceeded)
+        ));
+        assert!(start.elapsed() >= timeout);
+
+        let p = pages.pop().unwrap();
+        let t = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            drop(p);
+        });
+        a.get_page_timeout(Duration::from_secs(10)).unwrap();
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn defragment() {
+        let alloc = Allocator::new(AllocatorConfig {
+            // Keep the maintenance thread from interfering
+            defrag_pages: 0,
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Pages of varying compressibility
+        let pages: Vec<_> = (0..64)
+            .map(|i| {
+                let p = alloc.get_page().unwrap();
+                let mut g = p.0.inner.write().unwrap();
+                for (j, b) in g.buffer.iter_mut().enumerate() {
+                    *b = (j / (i + 1)) as u8;
+                }
+                drop(g);
+                p
+            })
+            .collect();
+        let expected: Vec<Vec<u8>> = pages
+            .iter()
+            .map(|p| p.0.inner.read().unwrap().buffer.to_vec())
+            .collect();
+        let mut a = alloc.0.inner.lock().unwrap();
+        for p in pages.iter() {
+            a.zswap(p.id()).unwrap();
+            assert!(a.zswapped.contains_key(&p.id()));
+        }
+        let before = a.stats().zswap_buffers;
+
+        // Fault in pages unevenly to fragment zswap
+        for p in pages.iter().filter(|p| p.id() % 3 != 0) {
+            let mut g = p.0.inner.write().unwrap();
+            assert!(a.fault_in(p.id(), &mut g).unwrap().is_none());
+        }
+        let fragmented = a.stats().zswap_buffers;
+        assert!(fragmented > 1);
+
+        let next_id = a.next_zswap_id;
+        a.defragment(usize::MAX);
+        let stats = a.stats();
+        assert!(stats.zswap_buffers < fragmented);
+        assert!(stats.zswap_buffers < before);
+
+        // Data is only moved into existing zswap pages
+        assert_eq!(a.next_zswap_id, next_id);
+
+        for (p, expected) in pages.iter().zip(expected.iter()) {
+            let mut g = p.0.inner.write().unwrap();
+            if g.buffer.ptr.is_null() {
+                assert!(a.fault_in(p.id(), &mut g).unwrap().is_none());
+            }
+            assert_eq!(&*g.buffer, expected.as_slice());
+        }
+
+        // Pages take the allocator lock on drop
+        drop(a);
+    }
+
+    #[test]
+    fn spill_compacts_zswap_page() {
+        let alloc = Allocator::new(AllocatorConfig {
+            defrag_pages: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let pages: Vec<_> = (0..4).map(|_| alloc.get_page().unwrap()).collect();
+        for (i, p) in pages.iter().enumerate() {
+            p.write().unwrap().fill(i as u8 + 1);
+        }
+
+        alloc.with(|a| {
+            for p in pages.iter() {
+                a.zswap(p.id()).unwrap();
+            }
+            assert_eq!(a.zswap_pages.len(), 1);
+            a.spill(pages[1].id()).unwrap();
+
+            let mut locs: Vec<_> = a.zswapped.values().copied().collect();
+            locs.sort_unstable_by_key(|loc| loc.offset);
+            assert_eq!(locs[0].offset, 0);
+            for w in locs.windows(2) {
+                // Sizes are padded to the next word
+                let end = w[0].offset + w[0].size;
+                let word = std::mem::size_of::<usize>();
+                assert_eq!(w[1].offset, end + word - end % word);
+            }
+        });
+
+        for (i, p) in pages.iter().enumerate() {
+            assert!(p.read().unwrap().iter().all(|b| *b == i as u8 + 1));
+        }
+    }
+
+    #[test]
+    fn page_access() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let hot = alloc.get_page().unwrap();
+        let cold = alloc.get_page().unwrap();
+        let acquired = Instant::now();
+
+        for _ in 0..3 {
+            drop(hot.read().unwrap());
+        }
+        cold.write().unwrap().fill(1);
+        alloc.with(|a| a.zswap(cold.id()).unwrap());
+
+        let access = alloc.page_access(&[hot.id(), cold.id(), 1 << 40]);
+        let hot_access = access[0].clone().unwrap();
+        assert_eq!(hot_access.location, PageLocation::Resident);
+        assert_eq!(hot_access.accesses, 3);
+        assert_eq!(hot_access.faults, 0);
+        assert!(hot_access.last_access >= acquired);
+        let cold_access = access[1].clone().unwrap();
+        assert_eq!(cold_access.location, PageLocation::Zswapped);
+        assert_eq!(cold_access.accesses, 1);
+        assert!(access[2].is_none());
+
+        drop(cold.read().unwrap());
+        let cold_access = alloc.page_access(&[cold.id()])[0].clone().unwrap();
+        assert_eq!(cold_access.location, PageLocation::Resident);
+        assert_eq!(cold_access.accesses, 2);
+        assert_eq!(cold_access.faults, 1);
+    }
+
+    #[test]
+    fn pinned_pages_stay_resident() {
+        let a = Allocator::new(Default::default()).unwrap();
+        let pinned = a.get_page().unwrap();
+        let unpinned = a.get_page().unwrap();
+
+        let guard = pinned.pin().unwrap();
+        assert!(pinned.is_pinned());
+        assert!(!unpinned.is_pinned());
+
+        a.with(|a| {
+            for p in [&pinned, &unpinned] {
+                a.zswap(p.id()).unwrap();
+            }
+            assert!(!a.zswapped.contains_key(&pinned.id()));
+            assert!(a.zswapped.contains_key(&unpinned.id()));
+        });
+
+        drop(guard);
+        assert!(!pinned.is_pinned());
+        a.with(|a| {
+            a.zswap(pinned.id()).unwrap();
+            assert!(a.zswapped.contains_key(&pinned.id()));
+        });
+    }
+
+    #[test]
+    fn pin_faults_in() {
+        let a = Allocator::new(Default::default()).unwrap();
+        let p = a.get_page().unwrap();
+        a.with(|a| a.zswap(p.id())).unwrap();
+        assert!(p.0.inner.read().unwrap().buffer.ptr.is_null());
+
+        let guard = p.pin().unwrap();
+        assert!(!p.0.inner.read().unwrap().buffer.ptr.is_null());
+        a.with(|a| a.zswap(p.id())).unwrap();
+        assert!(!p.0.inner.read().unwrap().buffer.ptr.is_null());
+        drop(guard);
+    }
+
+    #[test]
+    fn dump_policy() {
+        let alloc = Allocator::new(AllocatorConfig {
+            zswap_age: Duration::ZERO,
+            spill_age: Duration::ZERO,
+            spill_interval: Duration::from_secs(3600),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // Each acquisition swaps out all older pages by one tier
+        let p0 = alloc.get_page().unwrap();
+        let p1 = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p0.id()), Some(PageLocation::Zswapped));
+        let p2 = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p0.id()), Some(PageLocation::Spilled));
+        assert_eq!(alloc.lookup(p1.id()), Some(PageLocation::Zswapped));
+
+        // Dumping is rate limited
+        let _p3 = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p1.id()), Some(PageLocation::Zswapped));
+        assert_eq!(alloc.lookup(p2.id()), Some(PageLocation::Zswapped));
+        assert_eq!(alloc.stats().spills, 1);
+
+        // Zswap occupancy threshold
+        let alloc = Allocator::new(AllocatorConfig {
+            max_zswap_pages: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let p = alloc.get_page().unwrap();
+        alloc.with(|a| a.zswap(p.id())).unwrap();
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Zswapped));
+        let _p = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Spilled));
+    }
+
+    #[test]
+    fn hooks() {
+        let alloc = Allocator::new(AllocatorConfig {
+            zswap_age: Duration::ZERO,
+            spill_age: Duration::ZERO,
+            ..Default::default()
+        })
+        .unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let e = events.clone();
+        alloc.on_evict(move |id| e.lock().unwrap().push(("evict", id)));
+        let e = events.clone();
+        alloc.on_fault_in(move |id| e.lock().unwrap().push(("fault_in", id)));
+
+        // Veto dumping the first page to disk
+        let p0 = alloc.get_page().unwrap();
+        let critical = p0.id();
+        let e = events.clone();
+        alloc.on_spill(move |id| {
+            e.lock().unwrap().push(("spill", id));
+            id != critical
+        });
+
+        let p1 = alloc.get_page().unwrap();
+        let p2 = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p0.id()), Some(PageLocation::Zswapped));
+        assert_eq!(alloc.lookup(p1.id()), Some(PageLocation::Zswapped));
+        let _p3 = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p0.id()), Some(PageLocation::Zswapped));
+        assert_eq!(alloc.lookup(p1.id()), Some(PageLocation::Spilled));
+
+        p0.read().unwrap();
+        assert_eq!(
+            std::mem::take(&mut *events.lock().unwrap()),
+            vec![
+                ("evict", p0.id()),
+                ("spill", p0.id()),
+                ("evict", p1.id()),
+                ("spill", p0.id()),
+                ("spill", p1.id()),
+                ("evict", p2.id()),
+                ("fault_in", p0.id()),
+            ]
+        );
+
+        // Shutdown dumps vetoed pages regardless
+        alloc.with(|a| a.zswap(p0.id())).unwrap();
+        alloc.shutdown().unwrap();
+        assert_eq!(alloc.lookup(p0.id()), Some(PageLocation::Spilled));
+    }
+
+    #[test]
+    fn scope_quotas() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        assert!(matches!(
+            alloc.scope("tiny", Some(1)),
+            Err(AllocError::InvalidConfig(_))
+        ));
+
+        let table = alloc.scope("table", None).unwrap();
+        let aggregate = alloc
+            .scope("aggregate", Some(2 * DEFAULT_PAGE_SIZE))
+            .unwrap();
+        let table_pages: Vec<_> =
+            (0..3).map(|_| table.get_page().unwrap()).collect();
+        let aggregate_pages: Vec<_> =
+            (0..4).map(|_| aggregate.get_page().unwrap()).collect();
+
+        // The aggregate only swaps out its own pages
+        for p in table_pages.iter() {
+            assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Resident));
+        }
+        let s = aggregate.stats();
+        assert_eq!(s.acquired_pages, 4);
+        assert_eq!(s.resident_pages, 2);
+
+        // Faulting in a page keeps the scope within its quota
+        aggregate_pages[0].read().unwrap();
+        assert_eq!(aggregate.stats().resident_pages, 2);
+        assert_eq!(
+            alloc.stats().scopes,
+            [
+                ScopeStats {
+                    name: "aggregate".into(),
+                    quota: Some(2 * DEFAULT_PAGE_SIZE),
+                    acquired_pages: 4,
+                    resident_pages: 2,
+                },
+                ScopeStats {
+                    name: "table".into(),
+                    quota: None,
+                    acquired_pages: 3,
+                    resident_pages: 3,
+                },
+            ]
+        );
+
+        // Pinned pages can not be swapped out
+        let guards: Vec<_> = aggregate_pages
+            .iter()
+            .take(2)
+            .map(|p| p.pin().unwrap())
+            .collect();
+        assert!(matches!(
+            aggregate.get_page(),
+            Err(AllocError::QuotaExceeded(name)) if name == "aggregate"
+        ));
+        drop(guards);
+
+        // Scope state outlives the handle, until all pages are released
+        drop(aggregate);
+        assert_eq!(alloc.stats().scopes.len(), 2);
+        drop(aggregate_pages);
+        assert_eq!(alloc.stats().scopes.len(), 1);
+        drop(table_pages);
+        assert_eq!(table.stats().resident_pages, 0);
+    }
+
+    #[test]
+    fn corrupted_page() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let p = alloc.get_page().unwrap();
+        p.write().unwrap().fill(7);
+
+        alloc.with(|a| {
+            a.zswap(p.id()).unwrap();
+
+            // The trailing bytes of an LZ4 block are always literals, so
+            // flipping one still decompresses
+            let loc = a.zswapped[&p.id()];
+            a.zswap_page(loc).buf[loc.offset + loc.size - 1] ^= 0xFF;
+        });
+        assert!(
+            matches!(p.read(), Err(AllocError::Corrupted(id)) if id == p.id())
+        );
+    }
+
+    #[test]
+    fn injected_faults() {
+        sim::freeze();
+        let alloc = Allocator::new(Default::default()).unwrap();
+
+        sim::inject(Fault::Malloc, 1);
+        assert!(matches!(alloc.get_page(), Err(AllocError::OutOfMemory)));
+        let p = alloc.get_page().unwrap();
+        p.write().unwrap().fill(7);
+
+        // Aging is driven by the simulated clock
+        sim::advance(AllocatorConfig::default().zswap_age);
+        let _p = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Zswapped));
+
+        // Failed writes keep the page in zswap until the next attempt
+        sim::advance(AllocatorConfig::default().spill_age);
+        sim::inject(Fault::SpillWrite, 1);
+        assert!(matches!(alloc.get_page(), Err(AllocError::SpillIo(_))));
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Zswapped));
+        let _p = alloc.get_page().unwrap();
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Spilled));
+
+        // Failed verification keeps the page on disk
+        sim::inject(Fault::Checksum, 1);
+        assert!(
+            matches!(p.read(), Err(AllocError::Corrupted(id)) if id == p.id())
+        );
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Spilled));
+        assert!(p.read().unwrap().iter().all(|b| *b == 7));
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Resident));
+    }
+
+    #[test]
+    fn prefetch() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let pages: Vec<_> = (0..8).map(|_| alloc.get_page().unwrap()).collect();
+        for (i, p) in pages.iter().enumerate() {
+            p.write().unwrap().fill(i as u8);
+        }
+        alloc.with(|a| {
+            for p in pages.iter() {
+                a.zswap(p.id()).unwrap();
+            }
+            for p in pages.iter().skip(4) {
+                a.spill(p.id()).unwrap();
+            }
+        });
+        let ids: Vec<_> = pages.iter().map(|p| p.id()).collect();
+        alloc.prefetch(&ids);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while ids
+            .iter()
+            .any(|id| alloc.lookup(*id) != Some(PageLocation::Resident))
+        {
+            assert!(Instant::now() < deadline, "pages not prefetched");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(alloc.stats().page_faults, 8);
+        let fresh = alloc.get_page().unwrap();
+
+        // Prefetched pages are swapped out last, even though they are more
+        // recently used than the fresh page
+        alloc.with(|a| {
+            let candidates = a.eviction_candidates(usize::MAX);
+            assert_eq!(candidates[0], fresh.id());
+            let mut prefetched = candidates[1..].to_vec();
+            prefetched.sort_unstable();
+            assert_eq!(prefetched, ids);
+        });
+        for (i, p) in pages.iter().enumerate() {
+            assert!(p.read().unwrap().iter().all(|b| *b == i as u8));
+        }
+        assert_eq!(alloc.stats().page_faults, 8);
+    }
+
+    #[test]
+    fn snapshot() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let p = alloc.get_page().unwrap();
+        p.write().unwrap().fill(1);
+        let buffers = alloc.stats().allocated_buffers;
+
+        // Contents are shared, until the page is written to
+        let snap = p.snapshot().unwrap();
+        let snap2 = p.snapshot().unwrap();
+        assert_eq!(snap.id(), p.id());
+        assert_eq!(snap.as_ptr(), snap2.as_ptr());
+        assert_eq!(p.read().unwrap().as_ptr(), snap.as_ptr());
+        assert_eq!(alloc.stats().allocated_buffers, buffers);
+
+        // Frozen pages are not swapped out
+        alloc.with(|a| a.zswap(p.id())).unwrap();
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Resident));
+
+        p.write().unwrap().fill(2);
+        assert!(snap.iter().all(|b| *b == 1));
+        assert!(p.read().unwrap().iter().all(|b| *b == 2));
+        assert_eq!(alloc.stats().allocated_buffers, buffers + 1);
+
+        // Snapshots outlive the page
+        drop(p);
+        assert!(snap2.iter().all(|b| *b == 1));
+        drop(snap);
+        drop(snap2);
+        assert_eq!(alloc.stats().free_buffers, 2);
+
+        // No copy is made, if all snapshots are dropped before writing
+        let p = alloc.get_page().unwrap();
+        drop(p.snapshot().unwrap());
+        p.write().unwrap().fill(3);
+        assert_eq!(alloc.stats().free_buffers, 1);
+    }
+
+    #[cfg(feature = "alloc-tracing")]
+    #[test]
+    fn trace() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let table = alloc.scope("table", None).unwrap();
+        let line = line!() + 1;
+        let pages = [table.get_page().unwrap(), alloc.get_page().unwrap()];
+        let released = table.get_page().unwrap();
+        let id = released.id();
+        drop(released);
+
+        let records = alloc.trace();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].page, pages[0].id());
+        assert_eq!(records[0].label.as_deref(), Some("table"));
+        assert_eq!(records[0].location.file(), file!());
+        assert_eq!(records[0].location.line(), line);
+        assert_eq!(records[1].label, None);
+        assert!(records[..2].iter().all(|r| r.lifetime.is_none()));
+        assert_eq!(records[2].page, id);
+        assert!(records[2].lifetime.is_some());
+
+        let mut dump = Vec::new();
+        alloc.dump_trace(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        assert!(dump.contains("table: 4096 B held"));
+        assert!(dump.contains("<unscoped>: 4096 B held"));
+    }
+
+    #[test]
+    fn sub_page_allocation() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let p = alloc.get_page().unwrap();
+        let slots: Vec<_> =
+            (1..=4).map(|i| p.allocate(i * 10).unwrap()).collect();
+        {
+            let mut g = p.write().unwrap();
+            for (i, s) in slots.iter().enumerate() {
+                assert_eq!(s.page(), p.id());
+                assert_eq!(s.size(), (i + 1) * 10);
+                g.slot_mut(*s).unwrap().fill(i as u8 + 1);
+            }
+        }
+
+        // Allocating does not load swapped out pages
+        alloc.with(|a| a.zswap(p.id())).unwrap();
+        let extra = p.allocate(8).unwrap();
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Zswapped));
+
+        let g = p.read().unwrap();
+        for (i, s) in slots.iter().enumerate() {
+            assert!(g.slot(*s).unwrap().iter().all(|b| *b == i as u8 + 1));
+        }
+        drop(g);
+
+        assert!(matches!(p.allocate(4096), Err(AllocError::PageFull(_))));
+        let other = alloc.get_page().unwrap();
+        assert!(matches!(other.free(extra), Err(AllocError::InvalidSlot(_))));
+        p.free(extra).unwrap();
+        assert!(matches!(p.free(extra), Err(AllocError::StaleHandle(_))));
+
+        // Further allocations do not revive the freed handle
+        let reused = p.allocate(8).unwrap();
+        let mut g = p.write().unwrap();
+        g.slot_mut(reused).unwrap().fill(9);
+        assert!(matches!(g.slot(extra), Err(AllocError::StaleHandle(_))));
+        assert!(matches!(
+            other.write().unwrap().slot_mut(reused),
+            Err(AllocError::InvalidSlot(_))
+        ));
+        drop(g);
+        assert!(matches!(p.free(extra), Err(AllocError::StaleHandle(_))));
+        p.free(reused).unwrap();
+
+        let aligned = p.allocate_aligned(100, 64).unwrap();
+        assert_eq!(aligned.offset() % 64, 0);
+        p.write().unwrap().slot_mut(aligned).unwrap().fill(1);
+        p.free(aligned).unwrap();
+
+        // Mode can only be changed without live allocations
+        let live = p.allocate(8).unwrap();
+        assert!(matches!(
+            p.set_slot_mode(SlotMode::Buddy),
+            Err(AllocError::InvalidConfig(_))
+        ));
+        for s in slots.into_iter().chain(Some(live)) {
+            p.free(s).unwrap();
+        }
+        p.set_slot_mode(SlotMode::Buddy).unwrap();
+        let slots: Vec<_> = (0..4).map(|_| p.allocate(24).unwrap()).collect();
+        for (i, s) in slots.iter().enumerate() {
+            assert_eq!(s.offset(), i * 32);
+        }
+        assert!(matches!(p.free(live), Err(AllocError::StaleHandle(_))));
+        let aligned = p.allocate_aligned(8, 256).unwrap();
+        assert_eq!(aligned.offset(), 256);
+        for s in slots.into_iter().chain(Some(aligned)) {
+            p.free(s).unwrap();
+        }
+
+        p.set_slot_mode(SlotMode::Slab { size: 48 }).unwrap();
+        let records: Vec<_> =
+            (0..85).map(|_| p.allocate(40).unwrap()).collect();
+        assert_eq!(records[84].offset(), 84 * 48);
+        assert!(matches!(p.allocate(40), Err(AllocError::PageFull(_))));
+        p.free(records[3]).unwrap();
+        assert!(matches!(p.allocate(49), Err(AllocError::PageFull(_))));
+        assert!(matches!(
+            p.allocate_aligned(8, 32),
+            Err(AllocError::PageFull(_))
+        ));
+        assert_eq!(p.allocate_aligned(8, 16).unwrap().offset(), 3 * 48);
+    }
+
+    #[test]
+    fn warm_restart() {
+        let dir = std::env::temp_dir()
+            .join(format!("pdb-warm-restart-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = || AllocatorConfig {
+            spill_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let alloc = Allocator::new(config()).unwrap();
+        let pages: Vec<_> = (0..3).map(|_| alloc.get_page().unwrap()).collect();
+        for (i, p) in pages.iter().enumerate() {
+            p.write().unwrap().fill(i as u8 + 1);
+            alloc.with(|a| {
+                a.zswap(p.id()).unwrap();
+                a.spill(p.id()).unwrap();
+            });
+        }
+        let ids: Vec<_> = pages.iter().map(|p| p.id()).collect();
+        drop(alloc);
+
+        // Simulate the process terminating without releasing the pages
+        std::mem::forget(pages);
+
+        let alloc = Allocator::new(config()).unwrap();
+        assert_eq!(alloc.recoverable(), ids);
+        assert_eq!(alloc.stats().recovered_pages, 3);
+        assert_eq!(alloc.lookup(ids[0]), None);
+
+        let p = alloc.recover(ids[1]).unwrap();
+        assert!(matches!(
+            alloc.recover(ids[1]),
+            Err(AllocError::PageNotFound(_))
+        ));
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Spilled));
+        assert!(p.read().unwrap().iter().all(|b| *b == 2));
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Resident));
+
+        let stats = alloc.stats();
+        assert_eq!(stats.recovered_pages, 2);
+        assert_eq!(stats.spilled_pages, 0);
+        assert_eq!(stats.resident_pages, 1);
+        assert!(alloc.get_page().unwrap().id() > ids[2]);
+
+        drop(p);
+        drop(alloc);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shutdown() {
+        let dir = std::env::temp_dir()
+            .join(format!("pdb-shutdown-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = || AllocatorConfig {
+            spill_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+
+        let alloc = Allocator::new(config()).unwrap();
+        let zswapped = alloc.get_page().unwrap();
+        zswapped.write().unwrap().fill(3);
+        alloc.with(|a| a.zswap(zswapped.id()).unwrap());
+        let resident = alloc.get_page().unwrap();
+
+        alloc.shutdown().unwrap();
+        assert!(alloc.0.maintenance.lock().unwrap().is_none());
+        assert_eq!(alloc.lookup(zswapped.id()), Some(PageLocation::Spilled));
+
+        // Dropped without a maintenance thread to load it
+        alloc.prefetch(&[zswapped.id()]);
+        assert_eq!(alloc.lookup(zswapped.id()), Some(PageLocation::Spilled));
+        assert!(matches!(alloc.get_page(), Err(AllocError::ShutDown)));
+        assert!(matches!(alloc.try_get_page(), Err(AllocError::ShutDown)));
+        resident.write().unwrap().fill(1);
+
+        let id = zswapped.id();
+        drop(zswapped);
+        drop(resident);
+        drop(alloc);
+
+        let alloc = Allocator::new(config()).unwrap();
+        assert_eq!(alloc.recoverable(), [id]);
+        assert!(alloc
+            .recover(id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .iter()
+            .all(|b| *b == 3));
+
+        drop(alloc);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn set_budget() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let page_size = alloc.stats().page_size;
+        let pages: Vec<_> = (0..8).map(|_| alloc.get_page().unwrap()).collect();
+        for (i, p) in pages.iter().enumerate() {
+            p.write().unwrap().fill(i as u8);
+        }
+        drop(alloc.get_page().unwrap());
+        assert_eq!(alloc.stats().free_buffers, 1);
+
+        assert!(matches!(
+            alloc.set_budget(Some(page_size - 1)),
+            Err(AllocError::InvalidConfig(_))
+        ));
+        alloc.set_budget(Some(2 * page_size)).unwrap();
+        let stats = alloc.stats();
+        assert!(stats.allocated_buffers <= 2);
+        assert_eq!(stats.free_buffers, 0);
+        assert!(stats.resident_pages < 8);
+
+        for (i, p) in pages.iter().enumerate() {
+            assert!(p.read().unwrap().iter().all(|b| *b == i as u8));
+        }
+        alloc.set_budget(None).unwrap();
+        assert_eq!(alloc.with(|a| a.config.max_resident), None);
+    }
+
+    #[test]
+    fn scrub() {
+        let alloc = Allocator::new(AllocatorConfig {
+            scrub: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let p = alloc.get_page().unwrap();
+        p.write().unwrap().fill(0xff);
+        drop(p);
+        assert_eq!(alloc.stats().free_buffers, 1);
+
+        let p = alloc.get_page().unwrap();
+        assert_eq!(alloc.stats().free_buffers, 0);
+        assert!(p.read().unwrap().iter().all(|b| *b == 0));
+
+        let mut pages = vec![p, alloc.get_page().unwrap()];
+        for p in pages.iter() {
+            p.write().unwrap().fill(0xff);
+            alloc.with(|a| a.zswap(p.id()).unwrap());
+        }
+        let loc = alloc.with(|a| a.zswapped[&pages[0].id()]);
+        drop(pages.remove(0));
+        alloc.with(|a| {
+            assert!(a.zswap_page(loc).buf[loc.offset..loc.offset + loc.size]
+                .iter()
+                .all(|b| *b == 0));
+        });
+    }
+
+    #[test]
+    fn get_pages() {
+        let page_size = DEFAULT_PAGE_SIZE;
+        let alloc = Allocator::new(AllocatorConfig {
+            max_resident: Some(4 * page_size),
+            ..Default::default()
+        })
+        .unwrap();
+        let old = alloc.get_pages(3).unwrap();
+        assert_eq!(old.len(), 3);
+
+        let new = alloc.get_pages(3).unwrap();
+        let stats = alloc.stats();
+        // One buffer is taken by the zswap page
+        assert_eq!(stats.evictions, 3);
+        assert_eq!(stats.allocated_buffers, 4);
+        let mut ids: Vec<_> = old.iter().chain(&new).map(|p| p.id()).collect();
+        ids.dedup();
+        assert_eq!(ids.len(), 6);
+
+        let pins: Vec<_> = new.iter().map(|p| p.pin().unwrap()).collect();
+        assert!(matches!(
+            alloc.get_pages(4),
+            Err(AllocError::BudgetExceeded)
+        ));
+        assert_eq!(alloc.with(|a| a.handles.len()), 6);
+        drop(pins);
+
+        let scope = alloc.scope("batch", Some(2 * page_size)).unwrap();
+        assert!(matches!(
+            scope.get_pages(3),
+            Err(AllocError::QuotaExceeded(_))
+        ));
+        assert_eq!(scope.get_pages(2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn page_chain() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let size = 2 * DEFAULT_PAGE_SIZE + 100;
+        let chain = alloc.get_chain(size).unwrap();
+        assert_eq!(chain.size(), size);
+        assert_eq!(chain.pages().len(), 3);
+
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        chain.write_at(0, &data).unwrap();
+        alloc.with(|a| a.zswap(chain.pages()[1].id()).unwrap());
+        assert_eq!(chain.to_vec().unwrap(), data);
+
+        let offset = DEFAULT_PAGE_SIZE - 10;
+        chain.write_at(offset, &[0xff; 20]).unwrap();
+        let mut buf = [0; 30];
+        chain.read_at(offset - 5, &mut buf).unwrap();
+        assert_eq!(buf[..5], data[offset - 5..offset]);
+        assert_eq!(buf[5..25], [0xff; 20]);
+        assert_eq!(buf[25..], data[offset + 20..offset + 25]);
+
+        let scope = alloc.scope("chain", None).unwrap();
+        assert_eq!(scope.get_chain(1).unwrap().pages().len(), 1);
+        assert!(alloc.get_chain(0).unwrap().pages().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn page_chain_out_of_bounds() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let chain = alloc.get_chain(DEFAULT_PAGE_SIZE + 1).unwrap();
+        chain.write_at(DEFAULT_PAGE_SIZE, &[0; 2]).unwrap();
+    }
+
+    #[test]
+    fn encrypted_spill() {
+        let alloc = Allocator::new(AllocatorConfig {
+            spill_key: Some(EncryptionKey::new([1; 32])),
+            ..Default::default()
+        })
+        .unwrap();
+        let p = alloc.get_page().unwrap();
+        p.write().unwrap().fill(9);
+        alloc.with(|a| {
+            a.zswap(p.id()).unwrap();
+            a.spill(p.id()).unwrap();
+        });
+        assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Spilled));
+        assert!(p.read().unwrap().iter().all(|b| *b == 9));
+    }
+
+    #[test]
+    fn swap_out_and_fault_in() {
+        let alloc = Allocator::new(Default::default()).unwrap();
+        let pages: Vec<_> = (0..8).map(|_| alloc.get_page().unwrap()).collect();
+        for (i, p) in pages.iter().enumerate() {
+            let mut g = p.write().unwrap();
+            for (j, b) in g.iter_mut().enumerate() {
+                *b = (i + j / 64) as u8;
+            }
+        }
+        let check = |i: usize, p: &Page| {
+            let g = p.read().unwrap();
+            assert!(g
+                .iter()
+                .enumerate()
+                .all(|(j, b)| *b == (i + j / 64) as u8));
+        };
+
+        for p in pages.iter() {
+            assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Resident));
+        }
+        alloc.with(|a| {
+            for p in pages.iter() {
+                a.zswap(p.id()).unwrap();
+                assert!(a.zswapped.contains_key(&p.id()));
+            }
+            for p in pages.iter().skip(4) {
+                a.spill(p.id()).unwrap();
+                assert!(!a.zswapped.contains_key(&p.id()));
+                assert!(a.spill.as_ref().unwrap().contains(p.id()));
+            }
+        });
+
+        for (i, p) in pages.iter().enumerate() {
+            let expected = if i < 4 {
+                PageLocation::Zswapped
+            } else {
+                PageLocation::Spilled
+            };
+            assert_eq!(alloc.lookup(p.id()), Some(expected));
+            check(i, p);
+            assert_eq!(alloc.lookup(p.id()), Some(PageLocation::Resident));
+        }
+
+        // Released pages must not leave anything behind
+        let ids: Vec<_> = pages.iter().map(|p| p.id()).collect();
+        alloc.with(|a| {
+            for p in pages.iter().step_by(2) {
+                a.zswap(p.id()).unwrap();
+            }
+            a.spill(pages[0].id()).unwrap();
+        });
+        drop(pages);
+        for id in ids.iter() {
+            assert_eq!(alloc.lookup(*id), None);
+        }
+        alloc.with(|a| {
+            for id in ids {
+                assert!(!a.zswapped.contains_key(&id));
+                assert!(!a.spill.as_ref().unwrap().contains(id));
+                assert!(!a.handles.contains_key(&id));
+            }
+        });
+    }
 }
@@ -0,0 +1,54 @@
+//! NUMA node detection and memory binding. Only effective on Linux with the
+//! `numa` feature enabled. Otherwise all memory is treated as belonging to
+//! node 0.
+
+/// Maximum number of NUMA nodes memory can be bound to
+pub const MAX_NODES: usize = 64;
+
+/// Returns the NUMA node of the CPU the calling thread is running on
+pub fn current_node() -> usize {
+    #[cfg(all(target_os = "linux", feature = "numa"))]
+    {
+        let mut cpu: libc::c_uint = 0;
+        let mut node: libc::c_uint = 0;
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_getcpu,
+                &mut cpu as *mut libc::c_uint,
+                &mut node as *mut libc::c_uint,
+                std::ptr::null_mut::<libc::c_void>(),
+            )
+        };
+        if res == 0 && (node as usize) < MAX_NODES {
+            return node as usize;
+        }
+    }
+    0
+}
+
+/// Prefer placing the memory region on a NUMA node, moving already faulted in
+/// pages.
+///
+/// Best effort. Failures only affect performance and are ignored.
+pub fn bind(ptr: *mut u8, size: usize, node: usize) {
+    #[cfg(all(target_os = "linux", feature = "numa"))]
+    {
+        const MPOL_PREFERRED: libc::c_int = 1;
+        const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+
+        let mask: u64 = 1 << node;
+        unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                ptr,
+                size,
+                MPOL_PREFERRED,
+                &mask as *const u64,
+                MAX_NODES + 1,
+                MPOL_MF_MOVE,
+            );
+        }
+    }
+    #[cfg(not(all(target_os = "linux", feature = "numa")))]
+    let _ = (ptr, size, node);
+}
@@ -0,0 +1,67 @@
+//! Listener for Linux pressure stall information (PSI) notifications
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    os::unix::io::AsRawFd,
+    path::Path,
+    time::Duration,
+};
+
+/// Notify, when tasks stall on memory for at least 100 ms within any 1 s
+/// window
+const TRIGGER: &[u8] = b"some 100000 1000000\0";
+
+/// Registered PSI trigger on a cgroup v2 `memory.pressure` or
+/// `/proc/pressure/memory` file
+pub struct PressureMonitor {
+    /// The trigger is unregistered, when the file is closed
+    file: File,
+}
+
+impl PressureMonitor {
+    /// Register a trigger on the pressure file at `path`
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        file.write_all(TRIGGER)?;
+        Ok(Self { file })
+    }
+
+    /// Wait up to `timeout` for the trigger to fire. Returns, if it fired.
+    pub fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        let res = unsafe {
+            libc::poll(&mut fd, 1, timeout.as_millis() as libc::c_int)
+        };
+        match res {
+            -1 => match io::Error::last_os_error() {
+                err if err.kind() == io::ErrorKind::Interrupted => Ok(false),
+                err => Err(err),
+            },
+            0 => Ok(false),
+            _ if fd.revents & libc::POLLERR != 0 => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "pressure file no longer available",
+            )),
+            _ => Ok(fd.revents & libc::POLLPRI != 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file() {
+        let err =
+            PressureMonitor::open(Path::new("/nonexistent/memory.pressure"))
+                .err()
+                .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
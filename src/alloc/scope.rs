@@ -0,0 +1,155 @@
+use super::{trace::CallSite, AllocError, Allocator, Page, PageChain};
+use std::time::Duration;
+
+/// Unique identifier of a `Scope`
+pub type ScopeId = u64;
+
+/// Accounting state of a `Scope` kept by the allocator
+pub(super) struct ScopeState {
+    /// Name for monitoring and errors
+    pub name: String,
+
+    /// Maximum number of resident pages. Unlimited, if None.
+    pub max_resident: Option<usize>,
+
+    /// Maximum resident memory as requested in bytes
+    pub quota: Option<usize>,
+
+    /// Number of pages acquired through the scope
+    pub acquired: usize,
+
+    /// Number of the scope's pages in resident memory
+    pub resident: usize,
+
+    /// The `Scope` handle has been dropped. The state is removed, once all
+    /// pages are released.
+    pub closed: bool,
+}
+
+impl ScopeState {
+    /// Returns, if loading another page would exceed the quota
+    #[inline]
+    pub fn at_quota(&self) -> bool {
+        self.over_quota(1)
+    }
+
+    /// Returns, if loading `n` more pages would exceed the quota
+    #[inline]
+    pub fn over_quota(&self, n: usize) -> bool {
+        self.max_resident
+            .map(|max| self.resident + n > max)
+            .unwrap_or(false)
+    }
+}
+
+/// Usage of a `Scope` for monitoring
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScopeStats {
+    /// Name of the scope
+    pub name: String,
+
+    /// Maximum resident memory of the scope's pages in bytes, if limited
+    pub quota: Option<usize>,
+
+    /// Pages acquired through the scope
+    pub acquired_pages: usize,
+
+    /// Pages acquired through the scope, that are in resident memory
+    pub resident_pages: usize,
+}
+
+impl From<&ScopeState> for ScopeStats {
+    fn from(s: &ScopeState) -> Self {
+        Self {
+            name: s.name.clone(),
+            quota: s.quota,
+            acquired_pages: s.acquired,
+            resident_pages: s.resident,
+        }
+    }
+}
+
+/// Named allocation scope for a single consumer like a table, index or query.
+///
+/// Resident memory of pages acquired through the scope is limited by its
+/// quota. A scope at its quota swaps out its own pages to make room, so it
+/// can not evict the pages of other consumers.
+pub struct Scope {
+    pub(super) allocator: Allocator,
+    pub(super) id: ScopeId,
+}
+
+impl Scope {
+    /// Acquire a page accounted to this scope
+    #[track_caller]
+    pub fn get_page(&self) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        self.allocator
+            .with(|a| a.get_page(&self.allocator, Some(self.id), None, site))
+    }
+
+    /// Acquire `n` pages accounted to this scope at once.
+    ///
+    /// Either all or none of the pages are acquired.
+    #[track_caller]
+    pub fn get_pages(&self, n: usize) -> Result<Vec<Page>, AllocError> {
+        let site = CallSite::caller();
+        self.allocator.with(|a| {
+            a.get_pages(&self.allocator, Some(self.id), n, None, site)
+        })
+    }
+
+    /// Acquire enough pages accounted to this scope to hold an allocation of
+    /// `size` bytes larger than a page
+    #[track_caller]
+    pub fn get_chain(&self, size: usize) -> Result<PageChain, AllocError> {
+        let site = CallSite::caller();
+        self.allocator
+            .with(|a| a.get_chain(&self.allocator, Some(self.id), size, site))
+    }
+
+    /// Acquire a page accounted to this scope without swapping out other
+    /// pages to make room.
+    ///
+    /// Returns `AllocError::WouldBlock`, if the scope's quota or the resident
+    /// memory budget is exhausted.
+    #[track_caller]
+    pub fn try_get_page(&self) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        self.allocator
+            .with(|a| a.try_get_page(&self.allocator, Some(self.id), site))
+    }
+
+    /// Acquire a page accounted to this scope, waiting up to `timeout` for
+    /// other pages to be released or unpinned, if no room can be made
+    #[track_caller]
+    pub fn get_page_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Page, AllocError> {
+        let site = CallSite::caller();
+        self.allocator.wait_for(timeout, |a| {
+            a.get_page(&self.allocator, Some(self.id), None, site)
+        })
+    }
+
+    /// Take a snapshot of the scope's usage
+    pub fn stats(&self) -> ScopeStats {
+        self.allocator
+            .with(|a| a.scopes.get(&self.id).map(Into::into))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        self.allocator.with(|a| {
+            if let Some(s) = a.scopes.get_mut(&self.id) {
+                s.closed = true;
+                if s.acquired == 0 {
+                    a.scopes.remove(&self.id);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,131 @@
+//! Simulated time and injectable failures for testing eviction, spill and
+//! recovery paths deterministically. Reduced to the real clock and no failures
+//! outside of tests.
+//!
+//! Both are local to the thread, that froze the clock or injected the
+//! failures, so parallel tests and background allocator threads do not
+//! interfere with each other.
+
+use std::time::Instant;
+
+/// Failure injectable into allocator operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Allocating a page buffer from the backend fails. Only injected by the
+    /// test backend.
+    #[cfg(test)]
+    Malloc,
+
+    /// Writing a page to the spill file fails
+    SpillWrite,
+
+    /// Checksum of a page loaded back into resident memory does not match
+    Checksum,
+}
+
+/// Number of `Fault` variants
+#[cfg(test)]
+const FAULTS: usize = 3;
+
+#[cfg(test)]
+thread_local! {
+    /// Number of next occurrences of each fault to fail
+    static PENDING: std::cell::Cell<[u32; FAULTS]> =
+        const { std::cell::Cell::new([0; FAULTS]) };
+
+    /// Current time of the frozen clock, if any
+    static NOW: std::cell::Cell<Option<Instant>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// Returns the current time of the simulated clock
+#[cfg(not(test))]
+#[inline]
+pub fn now() -> Instant {
+    Instant::now()
+}
+
+/// Returns the current time of the simulated clock
+#[cfg(test)]
+pub fn now() -> Instant {
+    NOW.with(|t| t.get()).unwrap_or_else(Instant::now)
+}
+
+/// Returns, if the operation should fail with an injected fault, consuming it
+#[cfg(not(test))]
+#[inline]
+pub fn triggered(_: Fault) -> bool {
+    false
+}
+
+/// Returns, if the operation should fail with an injected fault, consuming it
+#[cfg(test)]
+pub fn triggered(fault: Fault) -> bool {
+    PENDING.with(|p| {
+        let mut pending = p.get();
+        let n = &mut pending[fault as usize];
+        if *n == 0 {
+            return false;
+        }
+        *n -= 1;
+        p.set(pending);
+        true
+    })
+}
+
+/// Fail the next `n` occurrences of the fault on this thread
+#[cfg(test)]
+pub fn inject(fault: Fault, n: u32) {
+    PENDING.with(|p| {
+        let mut pending = p.get();
+        pending[fault as usize] = n;
+        p.set(pending);
+    })
+}
+
+/// Stop the clock of this thread at the current time. It only moves forward
+/// with `advance()` afterwards.
+#[cfg(test)]
+pub fn freeze() {
+    NOW.with(|t| t.set(Some(Instant::now())));
+}
+
+/// Move the frozen clock of this thread forward
+#[cfg(test)]
+pub fn advance(d: std::time::Duration) {
+    NOW.with(|t| {
+        t.set(Some(t.get().expect("clock not frozen") + d));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn inject_faults() {
+        assert!(!triggered(Fault::Malloc));
+        inject(Fault::Malloc, 2);
+        assert!(!triggered(Fault::SpillWrite));
+        assert!(triggered(Fault::Malloc));
+        assert!(triggered(Fault::Malloc));
+        assert!(!triggered(Fault::Malloc));
+
+        // Other threads are not affected
+        inject(Fault::Checksum, 1);
+        assert!(!std::thread::spawn(|| triggered(Fault::Checksum))
+            .join()
+            .unwrap());
+        assert!(triggered(Fault::Checksum));
+    }
+
+    #[test]
+    fn frozen_clock() {
+        freeze();
+        let t = now();
+        assert_eq!(now(), t);
+        advance(Duration::from_secs(60));
+        assert_eq!(now(), t + Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::free_list::AllocationResult;
+
+/// Allocator of fixed-size records in a page tracking free records in a
+/// bitmap.
+///
+/// A second level bitmap marks words of the first one with free records, so
+/// allocating and freeing take constant time for all supported page sizes.
+pub struct Slab {
+    /// Size of a record in bytes
+    size: usize,
+
+    /// Number of records fitting in the page
+    count: usize,
+
+    /// Set bits mark free records
+    free: Vec<u64>,
+
+    /// Set bits mark words of `free` with at least one free record
+    summary: Vec<u64>,
+}
+
+impl Slab {
+    /// Creates a new `Slab` allocator of records of `size` bytes with the
+    /// passed capacity
+    pub fn new(cap: usize, size: usize) -> Self {
+        assert!(
+            size != 0 && size <= cap,
+            "invalid slab record size: {}",
+            size
+        );
+        let count = cap / size;
+        let mut free = vec![u64::MAX; count.div_ceil(64)];
+        if !count.is_multiple_of(64) {
+            *free.last_mut().unwrap() = (1 << (count % 64)) - 1;
+        }
+        let mut summary = vec![u64::MAX; free.len().div_ceil(64)];
+        if !free.len().is_multiple_of(64) {
+            *summary.last_mut().unwrap() = (1 << (free.len() % 64)) - 1;
+        }
+        Self {
+            size,
+            count,
+            free,
+            summary,
+        }
+    }
+
+    /// Returns the size of a record in bytes
+    #[inline]
+    pub fn record_size(&self) -> usize {
+        self.size
+    }
+
+    /// Allocate a record for `size` bytes and return its offset.
+    ///
+    /// Returns `AllocationResult::NotFound` with the record size, if `size`
+    /// exceeds it, or 0, if all records are allocated.
+    pub fn allocate(&mut self, size: usize) -> AllocationResult {
+        if size > self.size {
+            return AllocationResult::NotFound(self.size);
+        }
+        let s = match self.summary.iter().position(|w| *w != 0) {
+            Some(s) => s,
+            None => return AllocationResult::NotFound(0),
+        };
+        let w = s * 64 + self.summary[s].trailing_zeros() as usize;
+        let bit = self.free[w].trailing_zeros() as usize;
+
+        self.free[w] &= !(1 << bit);
+        if self.free[w] == 0 {
+            self.summary[s] &= !(1 << (w % 64));
+        }
+        AllocationResult::Allocated((w * 64 + bit) * self.size)
+    }
+
+    /// Free the record at `offset`
+    pub fn free(&mut self, offset: usize) -> Result<(), &'static str> {
+        if !offset.is_multiple_of(self.size) || offset / self.size >= self.count
+        {
+            return Err("not a record offset");
+        }
+        let i = offset / self.size;
+        let (w, bit) = (i / 64, i % 64);
+        if self.free[w] & (1 << bit) != 0 {
+            return Err("record not allocated");
+        }
+
+        self.free[w] |= 1 << bit;
+        self.summary[w / 64] |= 1 << (w % 64);
+        Ok(())
+    }
+
+    /// Returns the number of free records
+    #[cfg(test)]
+    pub fn free_records(&self) -> usize {
+        self.free.iter().map(|w| w.count_ones() as usize).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(s: &mut Slab) -> usize {
+        match s.allocate(s.record_size()) {
+            AllocationResult::Allocated(offset) => offset,
+            AllocationResult::NotFound(_) => panic!("slab full"),
+        }
+    }
+
+    #[test]
+    fn allocate_and_free() {
+        let mut s = Slab::new(1 << 10, 24);
+        assert_eq!(s.free_records(), 42);
+        assert!(matches!(s.allocate(25), AllocationResult::NotFound(24)));
+
+        let offsets: Vec<_> = (0..42).map(|_| allocate(&mut s)).collect();
+        assert_eq!(offsets, (0..42).map(|i| i * 24).collect::<Vec<_>>());
+        assert!(matches!(s.allocate(1), AllocationResult::NotFound(0)));
+
+        s.free(240).unwrap();
+        assert!(s.free(240).is_err());
+        assert!(s.free(241).is_err());
+        assert!(s.free(42 * 24).is_err());
+        assert_eq!(allocate(&mut s), 240);
+    }
+
+    #[test]
+    fn many_records() {
+        // Spans multiple summary words
+        let mut s = Slab::new(2 << 20, 8);
+        assert_eq!(s.free_records(), 1 << 18);
+        for i in 0..(1 << 18) {
+            assert_eq!(allocate(&mut s), i * 8);
+        }
+        assert_eq!(s.free_records(), 0);
+
+        s.free(200_000 * 8).unwrap();
+        assert_eq!(allocate(&mut s), 200_000 * 8);
+    }
+}
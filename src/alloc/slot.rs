@@ -0,0 +1,266 @@
+use super::{
+    buddy::Buddy, slab::Slab, AllocError, PageId, PageInner, PageReadGuard,
+    PageWriteGuard,
+};
+use crate::free_list::{AllocationResult, FreeList};
+use std::collections::HashMap;
+
+/// Handle to a byte range within a page's memory allocated with
+/// `Page::allocate()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slot {
+    /// Page the range is allocated in
+    pub(super) page: PageId,
+
+    /// Offset of the range from the page's start
+    pub(super) offset: usize,
+
+    /// Size of the range in bytes
+    pub(super) size: usize,
+
+    /// Distinguishes the allocation from earlier and later ones at the same
+    /// offset
+    pub(super) generation: u64,
+}
+
+impl Slot {
+    /// Returns the ID of the page the range is allocated in
+    #[inline]
+    pub fn page(&self) -> PageId {
+        self.page
+    }
+
+    /// Returns the offset of the range from the page's start
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the size of the range in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the range's position in the memory of page `id`
+    fn range(
+        &self,
+        p: &PageInner,
+        id: PageId,
+    ) -> Result<std::ops::Range<usize>, AllocError> {
+        if self.page != id {
+            return Err(AllocError::InvalidSlot(id));
+        }
+        match &p.slots {
+            Some(slots) if slots.is_live(self) => {
+                Ok(self.offset..self.offset + self.size)
+            }
+            _ => Err(AllocError::StaleHandle(id)),
+        }
+    }
+}
+
+/// Strategy for placing sub-page allocations in a page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotMode {
+    /// Free list of arbitrarily sized ranges
+    FreeList,
+
+    /// Power-of-two buddy blocks. Wastes memory for sizes in between powers of
+    /// two, but suits pages holding many small objects of uniform size.
+    Buddy,
+
+    /// Records of `size` bytes tracked in a bitmap for pages dedicated to
+    /// fixed-size records. Larger allocations fail with
+    /// `AllocError::PageFull`. Aligned allocations only succeed, if the record
+    /// size is a multiple of the alignment.
+    Slab { size: usize },
+}
+
+impl Default for SlotMode {
+    #[inline]
+    fn default() -> Self {
+        Self::FreeList
+    }
+}
+
+/// Free memory of a page tracked according to its `SlotMode`
+enum Ranges {
+    FreeList(FreeList),
+    Buddy(Buddy),
+    Slab(Slab),
+}
+
+impl Ranges {
+    /// Create an empty tracker for a page of `page_size`
+    fn new(page_size: usize, mode: SlotMode) -> Self {
+        match mode {
+            SlotMode::FreeList => Self::FreeList(FreeList::new(page_size)),
+            SlotMode::Buddy => Self::Buddy(Buddy::new(page_size)),
+            SlotMode::Slab { size } => Self::Slab(Slab::new(page_size, size)),
+        }
+    }
+}
+
+/// Sub-page allocations of a page
+pub(super) struct Slots {
+    /// Free ranges of the page's memory
+    ranges: Ranges,
+
+    /// Generations of live allocations by offset
+    live: HashMap<usize, u64>,
+
+    /// Generation to assign to the next allocation
+    next_generation: u64,
+}
+
+impl Slots {
+    /// Create an empty allocation table for a page of `page_size`
+    pub fn new(page_size: usize, mode: SlotMode) -> Self {
+        Self {
+            ranges: Ranges::new(page_size, mode),
+            live: HashMap::new(),
+            next_generation: 0,
+        }
+    }
+
+    /// Change the placement strategy of future allocations. Only possible,
+    /// while no allocations are live.
+    pub fn set_mode(
+        &mut self,
+        id: PageId,
+        page_size: usize,
+        mode: SlotMode,
+    ) -> Result<(), AllocError> {
+        if !self.live.is_empty() {
+            return Err(AllocError::InvalidConfig(format!(
+                "page {} has live sub-page allocations",
+                id
+            )));
+        }
+
+        // Generations are kept, so handles freed before stay stale
+        self.ranges = Ranges::new(page_size, mode);
+        Ok(())
+    }
+
+    /// Allocate a range of `size` bytes in page `id`
+    pub fn allocate(
+        &mut self,
+        id: PageId,
+        size: usize,
+    ) -> Result<Slot, AllocError> {
+        let res = match &mut self.ranges {
+            Ranges::FreeList(fl) => fl.allocate(size),
+            Ranges::Buddy(b) => b.allocate(size),
+            Ranges::Slab(s) => s.allocate(size),
+        };
+        self.register(id, size, res)
+    }
+
+    /// Allocate a range of `size` bytes at an offset aligned to `align` bytes
+    /// in page `id`
+    pub fn allocate_aligned(
+        &mut self,
+        id: PageId,
+        size: usize,
+        align: usize,
+    ) -> Result<Slot, AllocError> {
+        assert!(
+            align.is_power_of_two(),
+            "alignment not a power of two: {}",
+            align
+        );
+        let res = match &mut self.ranges {
+            Ranges::FreeList(fl) => fl.allocate_aligned(size, align),
+
+            // Blocks are aligned to their size
+            Ranges::Buddy(b) => b.allocate(size.max(align)),
+
+            // Records are placed at multiples of their size
+            Ranges::Slab(s) if s.record_size().is_multiple_of(align) => {
+                s.allocate(size)
+            }
+            Ranges::Slab(_) => AllocationResult::NotFound(0),
+        };
+        self.register(id, size, res)
+    }
+
+    /// Create a handle for the result of a free list allocation
+    fn register(
+        &mut self,
+        id: PageId,
+        size: usize,
+        res: AllocationResult,
+    ) -> Result<Slot, AllocError> {
+        match res {
+            AllocationResult::Allocated(offset) => {
+                let generation = self.next_generation;
+                self.next_generation += 1;
+                self.live.insert(offset, generation);
+                Ok(Slot {
+                    page: id,
+                    offset,
+                    size,
+                    generation,
+                })
+            }
+            AllocationResult::NotFound(_) => Err(AllocError::PageFull(id)),
+        }
+    }
+
+    /// Free an allocation, so its range can be reused
+    pub fn free(&mut self, slot: Slot) -> Result<(), AllocError> {
+        if !self.is_live(&slot) {
+            return Err(AllocError::StaleHandle(slot.page));
+        }
+        match &mut self.ranges {
+            Ranges::FreeList(fl) => fl.free(slot.offset, slot.size),
+            Ranges::Buddy(b) => b.free(slot.offset, slot.size),
+            Ranges::Slab(s) => s.free(slot.offset),
+        }
+        .map_err(|_| AllocError::InvalidSlot(slot.page))?;
+        self.live.remove(&slot.offset);
+        Ok(())
+    }
+
+    /// Returns, if the allocation has not been freed yet
+    #[inline]
+    fn is_live(&self, slot: &Slot) -> bool {
+        self.live.get(&slot.offset) == Some(&slot.generation)
+    }
+}
+
+impl<'a> PageReadGuard<'a> {
+    /// Returns the memory of a range allocated in the page.
+    ///
+    /// Returns `AllocError::InvalidSlot`, if the range belongs to another
+    /// page, and `AllocError::StaleHandle`, if it has been freed.
+    #[inline]
+    pub fn slot(&self, slot: Slot) -> Result<&[u8], AllocError> {
+        let range = slot.range(&self.0, self.1)?;
+        Ok(&self[range])
+    }
+}
+
+impl<'a> PageWriteGuard<'a> {
+    /// Returns the memory of a range allocated in the page.
+    ///
+    /// Returns `AllocError::InvalidSlot`, if the range belongs to another
+    /// page, and `AllocError::StaleHandle`, if it has been freed.
+    #[inline]
+    pub fn slot(&self, slot: Slot) -> Result<&[u8], AllocError> {
+        let range = slot.range(&self.0, self.1)?;
+        Ok(&self[range])
+    }
+
+    /// Returns the mutable memory of a range allocated in the page.
+    ///
+    /// Returns `AllocError::InvalidSlot`, if the range belongs to another
+    /// page, and `AllocError::StaleHandle`, if it has been freed.
+    #[inline]
+    pub fn slot_mut(&mut self, slot: Slot) -> Result<&mut [u8], AllocError> {
+        let range = slot.range(&self.0, self.1)?;
+        Ok(&mut self[range])
+    }
+}
@@ -0,0 +1,56 @@
+use super::{Allocator, Buffer, PageId};
+use std::{ops::Deref, sync::Arc};
+
+/// Page contents shared between a `Page` and its snapshots
+pub(super) struct Frozen {
+    /// Page the contents were taken from
+    pub id: PageId,
+
+    /// Allocator to return the buffer to
+    pub allocator: Allocator,
+
+    pub buffer: Buffer,
+}
+
+impl Frozen {
+    /// Take back the buffer without returning it to the allocator
+    #[inline]
+    pub fn into_buffer(mut self) -> Buffer {
+        std::mem::replace(&mut self.buffer, Buffer::null())
+    }
+}
+
+impl Drop for Frozen {
+    fn drop(&mut self) {
+        if !self.buffer.ptr.is_null() {
+            let buf = std::mem::replace(&mut self.buffer, Buffer::null());
+            self.allocator.with(|a| a.return_buffer(buf));
+        }
+    }
+}
+
+/// Read-only snapshot of a `Page`'s contents taken with `Page::snapshot()`.
+///
+/// Cheap to clone. The contents are shared with the page, until the page is
+/// next written to, which makes a private copy for the page.
+/// Shared contents stay in resident memory, until the page is written to or
+/// all of its snapshots are dropped.
+#[derive(Clone)]
+pub struct PageSnapshot(pub(super) Arc<Frozen>);
+
+impl PageSnapshot {
+    /// Returns the unique identifier of the page the snapshot was taken of
+    #[inline]
+    pub fn id(&self) -> PageId {
+        self.0.id
+    }
+}
+
+impl Deref for PageSnapshot {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0.buffer
+    }
+}
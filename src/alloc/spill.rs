@@ -0,0 +1,961 @@
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+use super::uring::Ring;
+use super::{
+    aead::{self, EncryptionKey, KEY_SIZE, NONCE_SIZE, TAG_SIZE},
+    crc32,
+    sim::{self, Fault},
+    PageId,
+};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fs::{File, OpenOptions},
+    io,
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// Maximum number of concurrently outstanding spill file operations
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+const RING_ENTRIES: u32 = 64;
+
+/// Identifies spill files
+const MAGIC: &[u8; 8] = b"PDBSPILL";
+
+/// Version of the on-disk format. Must be incremented on any incompatible
+/// change.
+///
+/// Version 2 added encryption. Version 1 files are read as unencrypted.
+const VERSION: u32 = 2;
+
+/// Codec of the stored page data: LZ4 block format without a size prefix
+const CODEC_LZ4: u32 = 1;
+
+/// Stored page data is not encrypted
+const CIPHER_NONE: u32 = 0;
+
+/// Stored page data is encrypted with XChaCha20-Poly1305
+const CIPHER_XCHACHA20_POLY1305: u32 = 1;
+
+/// Size of the random salt in the file header, that the file's key is derived
+/// with
+const SALT_SIZE: usize = 8;
+
+/// Size of the file header: magic, version, page size, codec, cipher and salt
+const FILE_HEADER_SIZE: u64 = 32;
+
+/// Size of the header preceding each block. See `BlockHeader`.
+const BLOCK_HEADER_SIZE: usize = 24;
+
+/// Offset of `BlockHeader::flags` in an encoded header
+const FLAGS_OFFSET: u64 = 20;
+
+/// Block contains a stored page. Free otherwise.
+const FLAG_LIVE: u32 = 1;
+
+/// Header preceding each block in the file. Encoded as little endian.
+///
+/// Blocks are laid out back to back after the file header, so the file can be
+/// scanned by following the extents.
+#[derive(Default, PartialEq, Eq, Debug)]
+struct BlockHeader {
+    /// ID of the stored page
+    id: PageId,
+
+    /// Size of the block in bytes, including the header and any padding
+    extent: u32,
+
+    /// Size of the stored data
+    len: u32,
+
+    /// CRC-32 of the stored data
+    checksum: u32,
+
+    flags: u32,
+}
+
+impl BlockHeader {
+    fn encode(&self) -> [u8; BLOCK_HEADER_SIZE] {
+        let mut buf = [0; BLOCK_HEADER_SIZE];
+        buf[..8].copy_from_slice(&self.id.to_le_bytes());
+        for (i, f) in [self.extent, self.len, self.checksum, self.flags]
+            .iter()
+            .enumerate()
+        {
+            buf[8 + i * 4..12 + i * 4].copy_from_slice(&f.to_le_bytes());
+        }
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        let field = |i: usize| {
+            u32::from_le_bytes(buf[8 + i * 4..12 + i * 4].try_into().unwrap())
+        };
+        Self {
+            id: u64::from_le_bytes(buf[..8].try_into().unwrap()),
+            extent: field(0),
+            len: field(1),
+            checksum: field(2),
+            flags: field(3),
+        }
+    }
+}
+
+/// Region of the spill file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Block {
+    /// Offset from file start
+    offset: u64,
+
+    /// Size of the region in bytes, including the header
+    extent: usize,
+
+    /// Size of the stored data
+    len: usize,
+}
+
+/// Read exactly `buf.len()` bytes from the file at `offset`.
+///
+/// Safe to call concurrently with other positioned reads and writes.
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+/// Read exactly `buf.len()` bytes from the file at `offset`.
+///
+/// Safe to call concurrently with other positioned reads and writes.
+#[cfg(windows)]
+fn read_exact_at(
+    file: &File,
+    mut buf: &mut [u8],
+    mut offset: u64,
+) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Write all of `data` to the file at `offset`.
+///
+/// Safe to call concurrently with other positioned reads and writes.
+#[cfg(unix)]
+fn write_all_at(file: &File, data: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, data, offset)
+}
+
+/// Write all of `data` to the file at `offset`.
+///
+/// Safe to call concurrently with other positioned reads and writes.
+#[cfg(windows)]
+fn write_all_at(
+    file: &File,
+    mut data: &[u8],
+    mut offset: u64,
+) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !data.is_empty() {
+        match file.seek_write(data, offset) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                data = &data[n..];
+                offset += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Construct an error for a malformed or incompatible file
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Encryption state of a spill file.
+///
+/// Encrypted data is stored as the nonce followed by the ciphertext and
+/// authentication tag. The page ID is authenticated, so data can not be moved
+/// between pages.
+struct Cipher {
+    /// Key derived from the configured key and the file's salt
+    key: [u8; KEY_SIZE],
+
+    /// Random prefix of nonces generated by this instance of the file, so
+    /// nonces are not reused after reopening it. At 16 bytes, prefixes of
+    /// different instances do not collide in practice.
+    prefix: [u8; NONCE_SIZE - 8],
+
+    /// Counter part of the next nonce
+    next: u64,
+}
+
+impl Cipher {
+    fn new(key: &EncryptionKey, salt: &[u8]) -> io::Result<Self> {
+        let mut prefix = [0; NONCE_SIZE - 8];
+        aead::random_bytes(&mut prefix)?;
+        Ok(Self {
+            key: aead::derive_key(&key.0, salt),
+            prefix,
+            next: 0,
+        })
+    }
+
+    /// Encrypt the data of a page
+    fn seal(&mut self, id: PageId, data: &[u8]) -> Vec<u8> {
+        let mut nonce = [0; NONCE_SIZE];
+        nonce[..self.prefix.len()].copy_from_slice(&self.prefix);
+        nonce[self.prefix.len()..].copy_from_slice(&self.next.to_le_bytes());
+        self.next += 1;
+
+        let mut out = Vec::with_capacity(NONCE_SIZE + data.len() + TAG_SIZE);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(data);
+        let tag = aead::seal(
+            &self.key,
+            &nonce,
+            &id.to_le_bytes(),
+            &mut out[NONCE_SIZE..],
+        );
+        out.extend_from_slice(&tag);
+        out
+    }
+}
+
+/// Authenticate and decrypt the data of a page encrypted with `Cipher::seal()`
+fn unseal(
+    key: &[u8; KEY_SIZE],
+    id: PageId,
+    mut data: Vec<u8>,
+) -> io::Result<Vec<u8>> {
+    let fail = || {
+        invalid_data(format!(
+            "spill block authentication failed for page {}",
+            id
+        ))
+    };
+    if data.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(fail());
+    }
+    let tag = data.split_off(data.len() - TAG_SIZE);
+    let mut ct = data.split_off(NONCE_SIZE);
+    if !aead::open(
+        key,
+        data[..].try_into().unwrap(),
+        &id.to_le_bytes(),
+        &mut ct,
+        &tag,
+    ) {
+        return Err(fail());
+    }
+    Ok(ct)
+}
+
+/// File for dumping compressed pages out of memory.
+///
+/// The file starts with a versioned header describing the format of the
+/// stored pages. Each page is stored as an independently readable block
+/// prefixed with the page's ID and checksum, so any page can be read back and
+/// validated without touching the rest of the file.
+pub struct SpillFile {
+    /// Location of the file on disk. The file is removed on drop, unless
+    /// persistent.
+    path: PathBuf,
+
+    /// Keep the file on drop, so its pages can be recovered by reopening it
+    persistent: bool,
+
+    /// Overwrite the data of blocks with zeros, before they are reused
+    scrub: bool,
+
+    /// Shared with pending reads performed outside of the allocator lock
+    file: Arc<File>,
+
+    /// Performs reads and writes, so that any number of page faults can be
+    /// serviced concurrently. Falls back to blocking I/O, if io_uring is not
+    /// available.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    ring: Option<Arc<Ring>>,
+
+    /// Blocks of the spilled pages
+    index: HashMap<PageId, Block>,
+
+    /// Number of reads in progress by block offset. Blocks must not be reused
+    /// until all of their reads are finished.
+    reading: HashMap<u64, usize>,
+
+    /// Blocks freed by removed pages, that can be reused for new writes
+    free: Vec<Block>,
+
+    /// End of the used region of the file
+    end: u64,
+
+    /// Encrypts stored page data, if a key is configured
+    cipher: Option<Cipher>,
+}
+
+impl SpillFile {
+    /// Create a new empty spill file at `path` for pages of `page_size`,
+    /// truncating any existing file.
+    ///
+    /// Page data is encrypted, if `key` is set.
+    pub fn create(
+        path: PathBuf,
+        page_size: usize,
+        key: Option<&EncryptionKey>,
+    ) -> io::Result<Self> {
+        let mut header = [0; FILE_HEADER_SIZE as usize];
+        header[..8].copy_from_slice(MAGIC);
+        let cipher = match key {
+            Some(_) => CIPHER_XCHACHA20_POLY1305,
+            None => CIPHER_NONE,
+        };
+        for (i, f) in [VERSION, page_size as u32, CODEC_LZ4, cipher]
+            .iter()
+            .enumerate()
+        {
+            header[8 + i * 4..12 + i * 4].copy_from_slice(&f.to_le_bytes());
+        }
+        let salt = &mut header[24..24 + SALT_SIZE];
+        aead::random_bytes(salt)?;
+        let cipher = key.map(|k| Cipher::new(k, salt)).transpose()?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        write_all_at(&file, &header, 0)?;
+
+        let mut s = Self::new(path, file, FILE_HEADER_SIZE);
+        s.cipher = cipher;
+        Ok(s)
+    }
+
+    /// Open an existing spill file at `path` and rebuild its index.
+    ///
+    /// Files of a different format version, page size or codec are rejected
+    /// with `io::ErrorKind::InvalidData`. So are encrypted files, if no `key`
+    /// is passed, and unencrypted files, if it is. Data encrypted with a
+    /// different key fails authentication, when read.
+    pub fn open(
+        path: PathBuf,
+        page_size: usize,
+        key: Option<&EncryptionKey>,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+
+        let mut header = [0; FILE_HEADER_SIZE as usize];
+        read_exact_at(&file, &mut header, 0)?;
+        if &header[..8] != MAGIC {
+            return Err(invalid_data("not a spill file".into()));
+        }
+        let field = |i: usize| {
+            u32::from_le_bytes(
+                header[8 + i * 4..12 + i * 4].try_into().unwrap(),
+            )
+        };
+        if field(0) != 1 && field(0) != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported spill file version: {}",
+                field(0)
+            )));
+        }
+        if field(1) as usize != page_size {
+            return Err(invalid_data(format!(
+                "spill file page size mismatch: {}",
+                field(1)
+            )));
+        }
+        if field(2) != CODEC_LZ4 {
+            return Err(invalid_data(format!(
+                "unsupported spill file codec: {}",
+                field(2)
+            )));
+        }
+        let cipher = match (field(3), key) {
+            (CIPHER_NONE, None) => None,
+            (CIPHER_XCHACHA20_POLY1305, Some(key)) => {
+                Some(Cipher::new(key, &header[24..24 + SALT_SIZE])?)
+            }
+            (CIPHER_NONE, Some(_)) => {
+                return Err(invalid_data("spill file not encrypted".into()));
+            }
+            (CIPHER_XCHACHA20_POLY1305, None) => {
+                return Err(invalid_data(
+                    "spill file encrypted, but no key provided".into(),
+                ));
+            }
+            (c, _) => {
+                return Err(invalid_data(format!(
+                    "unsupported spill file cipher: {}",
+                    c
+                )));
+            }
+        };
+
+        let end = file.metadata()?.len();
+        let mut s = Self::new(path, file, end);
+        s.cipher = cipher;
+        let mut offset = FILE_HEADER_SIZE;
+        let mut buf = [0; BLOCK_HEADER_SIZE];
+        while offset < end {
+            read_exact_at(&s.file, &mut buf, offset)?;
+            let h = BlockHeader::decode(&buf);
+            let extent = h.extent as usize;
+            if extent < BLOCK_HEADER_SIZE + h.len as usize
+                || offset + extent as u64 > end
+            {
+                return Err(invalid_data(format!(
+                    "malformed spill block at offset {}",
+                    offset
+                )));
+            }
+
+            let block = Block {
+                offset,
+                extent,
+                len: h.len as usize,
+            };
+            if h.flags & FLAG_LIVE == 0 {
+                s.free.push(block);
+            } else if s.index.insert(h.id, block).is_some() {
+                return Err(invalid_data(format!(
+                    "duplicate spill block for page {}",
+                    h.id
+                )));
+            }
+            offset += extent as u64;
+        }
+
+        Ok(s)
+    }
+
+    fn new(path: PathBuf, file: File, end: u64) -> Self {
+        Self {
+            file: Arc::new(file),
+            path,
+            persistent: false,
+            scrub: false,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            ring: Ring::new(RING_ENTRIES).ok().map(Arc::new),
+            index: HashMap::new(),
+            reading: HashMap::new(),
+            free: Vec::new(),
+            end,
+            cipher: None,
+        }
+    }
+
+    /// Returns, if the page is stored in the file
+    #[inline]
+    pub fn contains(&self, id: PageId) -> bool {
+        self.index.contains_key(&id)
+    }
+
+    /// Returns the number of pages stored in the file
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns the IDs of the pages stored in the file in no particular order
+    pub fn ids(&self) -> impl Iterator<Item = PageId> + '_ {
+        self.index.keys().copied()
+    }
+
+    /// Flush all written data and metadata to disk
+    #[inline]
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+
+    /// Keep the file on drop instead of removing it
+    #[inline]
+    pub fn persist(&mut self) {
+        self.persistent = true;
+    }
+
+    /// Set overwriting the data of removed pages with zeros, so it does not
+    /// linger on disk until the block is reused
+    #[inline]
+    pub fn set_scrub(&mut self, scrub: bool) {
+        self.scrub = scrub;
+    }
+
+    /// Write a page's compressed data to the file, replacing any previous
+    /// data for the page
+    pub fn write(&mut self, id: PageId, data: &[u8]) -> io::Result<()> {
+        self.remove(id);
+
+        let sealed;
+        let data = match &mut self.cipher {
+            Some(c) => {
+                sealed = c.seal(id, data);
+                &sealed[..]
+            }
+            None => data,
+        };
+
+        // First fit reuse of freed blocks
+        let need = BLOCK_HEADER_SIZE + data.len();
+        let block = match self.free.iter().position(|b| b.extent >= need) {
+            Some(i) => {
+                let b = self.free[i];
+                if b.extent - need >= BLOCK_HEADER_SIZE {
+                    // Split off the remainder as a new free block
+                    let rest = Block {
+                        offset: b.offset + need as u64,
+                        extent: b.extent - need,
+                        len: 0,
+                    };
+                    self.write_header(rest, &BlockHeader::default())?;
+                    self.free[i] = rest;
+                    Block {
+                        offset: b.offset,
+                        extent: need,
+                        len: data.len(),
+                    }
+                } else {
+                    // Remainder too small to be scanned, so pad the block
+                    self.free.swap_remove(i);
+                    Block {
+                        len: data.len(),
+                        ..b
+                    }
+                }
+            }
+            None => {
+                let block = Block {
+                    offset: self.end,
+                    extent: need,
+                    len: data.len(),
+                };
+                self.end += need as u64;
+                block
+            }
+        };
+
+        let mut buf = Vec::with_capacity(need);
+        buf.extend_from_slice(
+            &BlockHeader {
+                id,
+                extent: block.extent as u32,
+                len: data.len() as u32,
+                checksum: crc32::checksum(data),
+                flags: FLAG_LIVE,
+            }
+            .encode(),
+        );
+        buf.extend_from_slice(data);
+        let res = if sim::triggered(Fault::SpillWrite) {
+            Err(io::Error::other("injected write failure"))
+        } else {
+            self.write_at(&buf, block.offset)
+        };
+        if let Err(err) = res {
+            self.mark_free(block);
+            self.release(block);
+            return Err(err);
+        }
+        self.index.insert(id, block);
+        Ok(())
+    }
+
+    /// Write a free block header
+    fn write_header(&self, b: Block, h: &BlockHeader) -> io::Result<()> {
+        self.write_at(
+            &BlockHeader {
+                extent: b.extent as u32,
+                ..*h
+            }
+            .encode(),
+            b.offset,
+        )
+    }
+
+    /// Clear the live flag of a block, so it is not indexed on reopening the
+    /// file.
+    ///
+    /// Best effort. Only the flags are overwritten, so reads of the block in
+    /// progress are not affected.
+    fn mark_free(&self, b: Block) {
+        self.write_at(&0u32.to_le_bytes(), b.offset + FLAGS_OFFSET)
+            .ok();
+    }
+
+    /// Make a block available for reuse, scrubbing its data, if enabled.
+    /// The block must not be read anymore.
+    fn release(&mut self, b: Block) {
+        if self.scrub {
+            // Best effort. Failing only leaves the data on disk longer.
+            self.write_at(
+                &vec![0; b.extent - BLOCK_HEADER_SIZE],
+                b.offset + BLOCK_HEADER_SIZE as u64,
+            )
+            .ok();
+        }
+        self.free.push(b);
+    }
+
+    /// Write all of `data` to the file at `offset`
+    fn write_at(&self, data: &[u8], offset: u64) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(ring) = &self.ring {
+            return ring.write_all_at(&self.file, data, offset);
+        }
+        write_all_at(&self.file, data, offset)
+    }
+
+    /// Start reading a page's compressed data, that can be completed without
+    /// holding a reference to the `SpillFile`.
+    ///
+    /// The page's block is not reused until the read is passed to
+    /// `finish_read()`. Any number of reads of the same page can be in
+    /// progress.
+    pub fn begin_read(&mut self, id: PageId) -> Option<PendingRead> {
+        let block = *self.index.get(&id)?;
+        *self.reading.entry(block.offset).or_default() += 1;
+        Some(PendingRead {
+            id,
+            file: self.file.clone(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            ring: self.ring.clone(),
+            block,
+            key: self.cipher.as_ref().map(|c| c.key),
+        })
+    }
+
+    /// Mark a read started with `begin_read()` as finished.
+    ///
+    /// Returns false, if the page was removed or rewritten since the read was
+    /// started, in which case the read data is stale.
+    pub fn finish_read(&mut self, read: PendingRead) -> bool {
+        let b = read.block;
+        let current = self.index.get(&read.id) == Some(&b);
+        if let Some(n) = self.reading.get_mut(&b.offset) {
+            *n -= 1;
+            if *n == 0 {
+                self.reading.remove(&b.offset);
+                if !current {
+                    self.release(b);
+                }
+            }
+        }
+        current
+    }
+
+    /// Remove a page from the file and make its block available for reuse.
+    /// Returns false, if the page was not stored in the file.
+    pub fn remove(&mut self, id: PageId) -> bool {
+        // TODO: coalesce adjacent free blocks and truncate the file, when the
+        // last block is freed
+        match self.index.remove(&id) {
+            Some(b) => {
+                self.mark_free(b);
+                if !self.reading.contains_key(&b.offset) {
+                    self.release(b);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Read of a page's compressed data started with `SpillFile::begin_read()`
+pub struct PendingRead {
+    /// Page being read
+    id: PageId,
+
+    file: Arc<File>,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    ring: Option<Arc<Ring>>,
+    block: Block,
+
+    /// Key for decrypting the data, if the file is encrypted
+    key: Option<[u8; KEY_SIZE]>,
+}
+
+impl PendingRead {
+    /// Perform the read, validate the block belongs to the page and is intact
+    /// and decrypt it, if encrypted
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; BLOCK_HEADER_SIZE + self.block.len];
+        self.read_block(&mut buf)?;
+
+        let h = BlockHeader::decode(&buf);
+        let data = buf.split_off(BLOCK_HEADER_SIZE);
+        if h.id != self.id || h.len as usize != self.block.len {
+            return Err(invalid_data(format!(
+                "spill block header mismatch for page {}",
+                self.id
+            )));
+        }
+        if crc32::checksum(&data) != h.checksum {
+            return Err(invalid_data(format!(
+                "spill block checksum mismatch for page {}",
+                self.id
+            )));
+        }
+        match &self.key {
+            Some(key) => unseal(key, self.id, data),
+            None => Ok(data),
+        }
+    }
+
+    fn read_block(&self, buf: &mut [u8]) -> io::Result<()> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(ring) = &self.ring {
+            return ring.read_exact_at(&self.file, buf, self.block.offset);
+        }
+        read_exact_at(&self.file, buf, self.block.offset)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if !self.persistent {
+            std::fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "pdb-spill-test-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    fn create(name: &str) -> SpillFile {
+        SpillFile::create(path(name), 4096, None).unwrap()
+    }
+
+    fn page(id: PageId) -> Vec<u8> {
+        vec![id as u8; 100 + id as usize]
+    }
+
+    #[test]
+    fn write_and_read() {
+        let mut f = create("write_and_read");
+        for id in 0..8 {
+            f.write(id, &page(id)).unwrap();
+        }
+        assert_eq!(f.len(), 8);
+
+        for id in (0..8).rev() {
+            let r = f.begin_read(id).unwrap();
+            assert_eq!(r.read().unwrap(), page(id));
+            assert!(f.finish_read(r));
+            if id % 2 == 0 {
+                f.remove(id);
+            }
+        }
+        assert_eq!(f.len(), 4);
+        assert!(!f.contains(0));
+        assert!(f.contains(1));
+    }
+
+    #[test]
+    fn concurrent_reads() {
+        let mut f = create("concurrent_reads");
+        f.write(1, &page(1)).unwrap();
+        let a = f.begin_read(1).unwrap();
+        let b = f.begin_read(1).unwrap();
+
+        // Block must not be reused, while any read is in progress
+        f.remove(1);
+        f.write(2, &page(1)).unwrap();
+        assert!(!f.finish_read(a));
+        f.write(3, &page(1)).unwrap();
+        assert_eq!(b.read().unwrap(), page(1));
+        assert!(!f.finish_read(b));
+
+        f.write(4, &page(1)).unwrap();
+        assert_eq!(f.index[&4].offset, FILE_HEADER_SIZE);
+    }
+
+    #[test]
+    fn header_mismatch() {
+        let mut f = create("header_mismatch");
+        f.write(1, &[1; 64]).unwrap();
+
+        // Overwrite the block with another page's header
+        let block = f.index[&1];
+        f.index.remove(&1);
+        f.free.push(block);
+        f.write(2, &[2; 64]).unwrap();
+        f.index.insert(1, block);
+
+        let err = f.begin_read(1).unwrap().read().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut f = create("checksum_mismatch");
+        f.write(1, &[1; 64]).unwrap();
+        let offset = f.index[&1].offset + BLOCK_HEADER_SIZE as u64 + 10;
+        write_all_at(&f.file, &[0], offset).unwrap();
+
+        let err = f.begin_read(1).unwrap().read().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reopen() {
+        let mut f = create("reopen");
+        for id in 0..16 {
+            f.write(id, &page(id)).unwrap();
+        }
+        for id in (0..16).step_by(3) {
+            f.remove(id);
+        }
+
+        // Reuse freed blocks with both splitting and padding
+        f.write(100, &[7; 32]).unwrap();
+        f.write(101, &[8; 100 + 3 - 4]).unwrap();
+
+        let copy = path("reopen-copy");
+        std::fs::copy(&f.path, &copy).unwrap();
+        let mut reopened = SpillFile::open(copy, 4096, None).unwrap();
+        assert_eq!(reopened.index, f.index);
+        assert_eq!(reopened.end, f.end);
+        let mut free = reopened.free.clone();
+        let mut expected = f.free.clone();
+        free.sort_by_key(|b| b.offset);
+        expected.sort_by_key(|b| b.offset);
+        assert_eq!(free, expected);
+
+        for id in (0..16).filter(|id| id % 3 != 0) {
+            let r = reopened.begin_read(id).unwrap();
+            assert_eq!(r.read().unwrap(), page(id));
+            reopened.finish_read(r);
+        }
+        assert_eq!(
+            reopened.begin_read(100).unwrap().read().unwrap(),
+            vec![7; 32]
+        );
+    }
+
+    #[test]
+    fn scrub() {
+        let mut f = create("scrub");
+        f.set_scrub(true);
+        f.write(1, &[1; 64]).unwrap();
+        let r = f.begin_read(1).unwrap();
+        f.remove(1);
+
+        // Blocks are only scrubbed, once no longer read
+        let b = r.block;
+        let mut buf = vec![0; b.len];
+        let data = b.offset + BLOCK_HEADER_SIZE as u64;
+        read_exact_at(&f.file, &mut buf, data).unwrap();
+        assert_eq!(buf, [1; 64]);
+
+        f.finish_read(r);
+        read_exact_at(&f.file, &mut buf, data).unwrap();
+        assert_eq!(buf, [0; 64]);
+    }
+
+    #[test]
+    fn reject_incompatible() {
+        let f = create("reject_incompatible");
+        let copy = path("reject_incompatible-copy");
+        std::fs::copy(&f.path, &copy).unwrap();
+
+        let err = SpillFile::open(copy.clone(), 8192, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Future version
+        write_all_at(
+            &File::options().write(true).open(&copy).unwrap(),
+            &(VERSION + 1).to_le_bytes(),
+            8,
+        )
+        .unwrap();
+        let err = SpillFile::open(copy.clone(), 4096, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(copy).unwrap();
+    }
+
+    #[test]
+    fn encryption() {
+        let key = EncryptionKey::new([7; KEY_SIZE]);
+        let mut f =
+            SpillFile::create(path("encryption"), 4096, Some(&key)).unwrap();
+        let data = b"plaintext page data".repeat(4);
+        f.write(1, &data).unwrap();
+        f.write(2, &data).unwrap();
+
+        let stored = std::fs::read(&f.path).unwrap();
+        assert!(!stored.windows(9).any(|w| w == b"plaintext"));
+
+        let r = f.begin_read(1).unwrap();
+        assert_eq!(r.read().unwrap(), data);
+        f.finish_read(r);
+
+        // Reopening requires the same key and does not reuse nonces
+        let copy = |name: &str| {
+            let copy = path(name);
+            std::fs::copy(&f.path, &copy).unwrap();
+            copy
+        };
+        let wrong_key = EncryptionKey::new([8; KEY_SIZE]);
+        let mut wrong =
+            SpillFile::open(copy("encryption-wrong"), 4096, Some(&wrong_key))
+                .unwrap();
+        let r = wrong.begin_read(1).unwrap();
+        assert_eq!(r.read().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        wrong.finish_read(r);
+        drop(wrong);
+
+        let copy = copy("encryption-copy");
+        let mut reopened = SpillFile::open(copy, 4096, Some(&key)).unwrap();
+        assert_ne!(
+            reopened.cipher.as_ref().unwrap().prefix,
+            f.cipher.as_ref().unwrap().prefix
+        );
+        reopened.write(3, &data).unwrap();
+        for id in 1..=3 {
+            let r = reopened.begin_read(id).unwrap();
+            assert_eq!(r.read().unwrap(), data);
+            reopened.finish_read(r);
+        }
+
+        // Data can not be moved between pages
+        let b1 = f.index[&1];
+        let b2 = f.index[&2];
+        f.index.insert(1, b2);
+        write_all_at(&f.file, &1u64.to_le_bytes(), b2.offset).unwrap();
+        let r = f.begin_read(1).unwrap();
+        assert_eq!(r.read().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        f.finish_read(r);
+        f.index.insert(1, b1);
+
+        let err = SpillFile::open(path("encryption"), 4096, None)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,137 @@
+//! Recording of page acquisitions for diagnosing which consumers are holding
+//! memory. Only active with the `alloc-tracing` feature.
+
+#[cfg(feature = "alloc-tracing")]
+use super::PageId;
+#[cfg(feature = "alloc-tracing")]
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    panic::Location,
+    time::{Duration, Instant},
+};
+
+/// Maximum number of recorded acquisitions. The oldest records are dropped,
+/// when exceeded.
+#[cfg(feature = "alloc-tracing")]
+const CAPACITY: usize = 1 << 12;
+
+/// Call site of a page acquisition
+#[derive(Clone, Copy)]
+pub(super) struct CallSite {
+    #[cfg(feature = "alloc-tracing")]
+    location: &'static Location<'static>,
+}
+
+impl CallSite {
+    /// Capture the call site of the calling function, which must be annotated
+    /// with `#[track_caller]` to be meaningful
+    #[inline]
+    #[track_caller]
+    pub fn caller() -> Self {
+        Self {
+            #[cfg(feature = "alloc-tracing")]
+            location: Location::caller(),
+        }
+    }
+}
+
+/// Record of a single page acquisition
+#[cfg(feature = "alloc-tracing")]
+#[derive(Clone, Debug)]
+pub struct TraceRecord {
+    /// Acquired page
+    pub page: PageId,
+
+    /// Name of the scope the page was acquired through, if any
+    pub label: Option<String>,
+
+    /// Source location of the acquisition
+    pub location: &'static Location<'static>,
+
+    /// Size of the page in bytes
+    pub size: usize,
+
+    /// Time of acquisition
+    pub acquired: Instant,
+
+    /// Time the page was held for before being released. None, if still
+    /// held.
+    pub lifetime: Option<Duration>,
+}
+
+#[cfg(feature = "alloc-tracing")]
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "page {} ({} B) acquired by {} at {}",
+            self.page,
+            self.size,
+            self.label.as_deref().unwrap_or("<unscoped>"),
+            self.location
+        )?;
+        match self.lifetime {
+            Some(d) => write!(f, ", released after {:?}", d),
+            None => write!(f, ", held for {:?}", self.acquired.elapsed()),
+        }
+    }
+}
+
+/// Ring buffer of the most recent page acquisitions
+#[cfg(feature = "alloc-tracing")]
+#[derive(Default)]
+pub(super) struct Trace {
+    records: VecDeque<TraceRecord>,
+
+    /// Sequence number of the first record in `records`
+    first: u64,
+
+    /// Sequence numbers of the records of held pages
+    held: HashMap<PageId, u64>,
+}
+
+#[cfg(feature = "alloc-tracing")]
+impl Trace {
+    /// Record a page acquisition
+    pub fn acquire(
+        &mut self,
+        page: PageId,
+        label: Option<String>,
+        site: CallSite,
+        size: usize,
+    ) {
+        if self.records.len() == CAPACITY {
+            self.records.pop_front();
+            self.first += 1;
+        }
+        self.held
+            .insert(page, self.first + self.records.len() as u64);
+        self.records.push_back(TraceRecord {
+            page,
+            label,
+            location: site.location,
+            size,
+            acquired: Instant::now(),
+            lifetime: None,
+        });
+    }
+
+    /// Record a page being released
+    pub fn release(&mut self, page: PageId) {
+        if let Some(seq) = self.held.remove(&page) {
+            // Record might have been dropped already
+            if let Some(r) = seq
+                .checked_sub(self.first)
+                .and_then(|i| self.records.get_mut(i as usize))
+            {
+                r.lifetime = Some(r.acquired.elapsed());
+            }
+        }
+    }
+
+    /// Returns the recorded acquisitions from oldest to newest
+    pub fn records(&self) -> Vec<TraceRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
@@ -0,0 +1,479 @@
+//! Minimal io_uring wrapper for servicing many concurrent spill file reads and
+//! writes without a thread per outstanding operation
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    os::unix::{fs::FileExt, io::AsRawFd},
+    ptr::null_mut,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Condvar, Mutex,
+    },
+};
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// Submission queue entry
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+/// Completion queue entry
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// Memory mapped region of the ring
+struct Mmap {
+    ptr: *mut u8,
+    size: usize,
+}
+
+impl Mmap {
+    unsafe fn new(
+        fd: i32,
+        size: usize,
+        offset: libc::off_t,
+    ) -> io::Result<Self> {
+        let ptr = libc::mmap(
+            null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_POPULATE,
+            fd,
+            offset,
+        );
+        if ptr == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Self {
+                ptr: ptr as *mut u8,
+                size,
+            })
+        }
+    }
+
+    /// Returns a pointer to a value at a byte offset into the region
+    #[inline]
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize) as *mut T }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.size);
+        }
+    }
+}
+
+/// State of the ring protected by a mutex
+struct State {
+    /// Token to assign to the next submitted operation
+    next_token: u64,
+
+    /// Number of submitted operations, whose completions have not been reaped
+    in_flight: u32,
+
+    /// Reaped results of completed operations by token
+    done: HashMap<u64, i32>,
+
+    /// A thread is blocked waiting for completions on behalf of all waiters
+    reaping: bool,
+}
+
+/// io_uring instance shared between all threads accessing the spill file.
+///
+/// Any number of threads can submit operations. One of the waiting threads
+/// blocks on the ring and hands completions over to the others.
+pub struct Ring {
+    fd: i32,
+
+    /// Number of submission queue entries
+    entries: u32,
+
+    sq: Mmap,
+    cq: Mmap,
+    sqes: Mmap,
+    params: Params,
+
+    state: Mutex<State>,
+
+    /// Notified, when completions are reaped or submission slots are freed
+    cond: Condvar,
+}
+
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    /// Create a new ring with at least `entries` submission queue entries.
+    ///
+    /// Fails, if io_uring is not supported by the kernel or not permitted.
+    pub fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries,
+                &mut params as *mut Params,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = fd as i32;
+
+        let map = || unsafe {
+            Ok((
+                Mmap::new(
+                    fd,
+                    params.sq_off.array as usize
+                        + params.sq_entries as usize * 4,
+                    IORING_OFF_SQ_RING,
+                )?,
+                Mmap::new(
+                    fd,
+                    params.cq_off.cqes as usize
+                        + params.cq_entries as usize
+                            * std::mem::size_of::<Cqe>(),
+                    IORING_OFF_CQ_RING,
+                )?,
+                Mmap::new(
+                    fd,
+                    params.sq_entries as usize * std::mem::size_of::<Sqe>(),
+                    IORING_OFF_SQES,
+                )?,
+            ))
+        };
+        let (sq, cq, sqes) = match map() {
+            Ok(m) => m,
+            Err(err) => {
+                unsafe { libc::close(fd) };
+                return Err(err);
+            }
+        };
+
+        Ok(Self {
+            fd,
+            entries: params.sq_entries,
+            sq,
+            cq,
+            sqes,
+            params,
+            state: Mutex::new(State {
+                next_token: 0,
+                in_flight: 0,
+                done: HashMap::new(),
+                reaping: false,
+            }),
+            cond: Condvar::new(),
+        })
+    }
+
+    /// Read exactly `buf.len()` bytes from `file` at `offset`
+    pub fn read_exact_at(
+        &self,
+        file: &File,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> io::Result<()> {
+        let n = self.perform(
+            IORING_OP_READ,
+            file,
+            buf.as_mut_ptr(),
+            buf.len(),
+            offset,
+        )?;
+
+        // Complete short reads synchronously
+        if n < buf.len() {
+            file.read_exact_at(&mut buf[n..], offset + n as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Write all of `buf` to `file` at `offset`
+    pub fn write_all_at(
+        &self,
+        file: &File,
+        buf: &[u8],
+        offset: u64,
+    ) -> io::Result<()> {
+        // The kernel does not write to the buffer
+        let n = self.perform(
+            IORING_OP_WRITE,
+            file,
+            buf.as_ptr() as *mut u8,
+            buf.len(),
+            offset,
+        )?;
+
+        // Complete short writes synchronously
+        if n < buf.len() {
+            file.write_all_at(&buf[n..], offset + n as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Submit an operation and block until it completes.
+    /// Returns the number of bytes transferred.
+    fn perform(
+        &self,
+        opcode: u8,
+        file: &File,
+        buf: *mut u8,
+        len: usize,
+        offset: u64,
+    ) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+
+        // Never submit more operations than the completion queue can hold
+        while state.in_flight >= self.entries {
+            state = self.cond.wait(state).unwrap();
+        }
+        let token = state.next_token;
+        state.next_token += 1;
+        unsafe {
+            self.push(Sqe {
+                opcode,
+                fd: file.as_raw_fd(),
+                off: offset,
+                addr: buf as u64,
+                len: len as u32,
+                user_data: token,
+                ..Default::default()
+            });
+        }
+        if let Err(err) = self.enter(1, 0, 0) {
+            unsafe { self.unpush() };
+            return Err(err);
+        }
+        state.in_flight += 1;
+
+        loop {
+            if let Some(res) = state.done.remove(&token) {
+                return if res < 0 {
+                    Err(io::Error::from_raw_os_error(-res))
+                } else {
+                    Ok(res as usize)
+                };
+            }
+
+            if state.reaping {
+                state = self.cond.wait(state).unwrap();
+                continue;
+            }
+
+            // Block on the ring on behalf of all waiting threads. The
+            // operation of this thread is outstanding, so a completion is
+            // guaranteed to arrive.
+            state.reaping = true;
+            drop(state);
+            //
+            // The buffer must not be released before the kernel is done with
+            // it, so errors like interrupts are retried.
+            self.enter(0, 1, IORING_ENTER_GETEVENTS).ok();
+            state = self.state.lock().unwrap();
+            state.reaping = false;
+            unsafe { self.reap(&mut state) };
+            self.cond.notify_all();
+        }
+    }
+
+    /// Push an entry to the submission queue.
+    /// Must be called with the state lock held and a free slot available.
+    unsafe fn push(&self, sqe: Sqe) {
+        let off = &self.params.sq_off;
+        let tail = &*self.sq.at::<AtomicU32>(off.tail);
+        let mask = *self.sq.at::<u32>(off.ring_mask);
+
+        let t = tail.load(Ordering::Relaxed);
+        let i = t & mask;
+        self.sqes.at::<Sqe>(0).add(i as usize).write(sqe);
+        *self.sq.at::<u32>(off.array).add(i as usize) = i;
+        tail.store(t.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Retract the last pushed entry, that the kernel has not consumed.
+    /// Must be called with the state lock held.
+    unsafe fn unpush(&self) {
+        let off = &self.params.sq_off;
+        let head = &*self.sq.at::<AtomicU32>(off.head);
+        let tail = &*self.sq.at::<AtomicU32>(off.tail);
+        let t = tail.load(Ordering::Relaxed);
+        if head.load(Ordering::Acquire) != t {
+            tail.store(t.wrapping_sub(1), Ordering::Release);
+        }
+    }
+
+    /// Move all available completions to the reaped results.
+    /// Must be called with the state lock held.
+    unsafe fn reap(&self, state: &mut State) {
+        let off = &self.params.cq_off;
+        let head = &*self.cq.at::<AtomicU32>(off.head);
+        let tail = &*self.cq.at::<AtomicU32>(off.tail);
+        let mask = *self.cq.at::<u32>(off.ring_mask);
+
+        let mut h = head.load(Ordering::Relaxed);
+        let t = tail.load(Ordering::Acquire);
+        while h != t {
+            let cqe = &*self.cq.at::<Cqe>(off.cqes).add((h & mask) as usize);
+            state.done.insert(cqe.user_data, cqe.res);
+            state.in_flight -= 1;
+            h = h.wrapping_add(1);
+        }
+        head.store(h, Ordering::Release);
+    }
+
+    fn enter(
+        &self,
+        to_submit: u32,
+        min_complete: u32,
+        flags: libc::c_uint,
+    ) -> io::Result<()> {
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd,
+                to_submit,
+                min_complete,
+                flags,
+                null_mut::<libc::c_void>(),
+                0usize,
+            )
+        };
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ring;
+    use std::{fs::OpenOptions, sync::Arc};
+
+    #[test]
+    fn concurrent_reads_and_writes() {
+        let ring = match Ring::new(8) {
+            Ok(r) => Arc::new(r),
+            // Not supported by the kernel or blocked by the sandbox
+            Err(_) => return,
+        };
+        let path = std::env::temp_dir()
+            .join(format!("pdb-uring-test-{}", std::process::id()));
+        let file = Arc::new(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap(),
+        );
+
+        // More threads than entries to exercise waiting for free slots
+        let threads: Vec<_> = (0..32u8)
+            .map(|i| {
+                let ring = ring.clone();
+                let file = file.clone();
+                std::thread::spawn(move || {
+                    let data = vec![i; 4096];
+                    let offset = i as u64 * 4096;
+                    ring.write_all_at(&file, &data, offset).unwrap();
+
+                    let mut buf = vec![0; 4096];
+                    ring.read_exact_at(&file, &mut buf, offset).unwrap();
+                    assert_eq!(buf, data);
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use super::{EngineError, Value};
+#[cfg(test)]
+use crate::alloc::PageId;
+use crate::alloc::{Allocator, Page};
+
+/// Values of a single table column.
+///
+/// Non-null values are appended to the column's own chain of pages, so scans
+/// only load the pages of the columns they read. Values do not span pages.
+pub(super) struct Column {
+    /// Pages holding the encoded non-null values with the number of bytes
+    /// used in each
+    pages: Vec<(Page, usize)>,
+
+    /// Bit set for each row with a null value
+    nulls: Vec<u64>,
+
+    /// Page index and offset in the page of the value of each row. Null rows
+    /// point to where the next value is stored.
+    offsets: Vec<(u32, u32)>,
+
+    /// Size of the pages holding values. Set, once the first page is
+    /// acquired.
+    page_size: usize,
+}
+
+impl Column {
+    pub fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            nulls: Vec::new(),
+            offsets: Vec::new(),
+            page_size: 0,
+        }
+    }
+
+    /// Returns, if the value of the row is null
+    #[inline]
+    pub fn is_null(&self, row: usize) -> bool {
+        self.nulls[row / 64] & (1 << (row % 64)) != 0
+    }
+
+    /// Returns the number of pages holding values
+    #[inline]
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns the ID of the page at index `i`
+    #[cfg(test)]
+    pub fn page_id(&self, i: usize) -> PageId {
+        self.pages[i].0.id()
+    }
+
+    /// Ensure the last page has room for `size` more bytes, acquiring a new
+    /// page from `alloc`, if not.
+    ///
+    /// Existing pages are not accessed, so they are not loaded back into
+    /// resident memory.
+    pub fn reserve(
+        &mut self,
+        alloc: &Allocator,
+        size: usize,
+    ) -> Result<(), EngineError> {
+        if let Some((_, used)) = self.pages.last() {
+            if used + size <= self.page_size {
+                return Ok(());
+            }
+        }
+
+        let page = alloc.get_page()?;
+        self.page_size = page.read()?.len();
+        if size > self.page_size {
+            return Err(EngineError::ValueTooLarge(size));
+        }
+        self.pages.push((page, 0));
+        Ok(())
+    }
+
+    /// Write the encoded value of the next row past the used part of the
+    /// last page without storing it.
+    ///
+    /// Room for the encoded value must be reserved with `reserve()` first.
+    /// The value is only stored by a following `commit()`, so writing the
+    /// values of all columns of a row first prevents partially inserted rows.
+    pub fn write(&self, encoded: &[u8]) -> Result<(), EngineError> {
+        let (page, used) = self.pages.last().unwrap();
+        page.write()?[*used..*used + encoded.len()].copy_from_slice(encoded);
+        Ok(())
+    }
+
+    /// Store the value of the next row. Non-null values must have been
+    /// written with `write()` first. `size` is ignored for null values.
+    pub fn commit(&mut self, val: &Value, size: usize) {
+        let row = self.offsets.len();
+        if row.is_multiple_of(64) {
+            self.nulls.push(0);
+        }
+
+        let i = self.pages.len().saturating_sub(1);
+        let used = self.pages.last().map(|(_, used)| *used).unwrap_or(0);
+        self.offsets.push((i as u32, used as u32));
+        if let Value::Null = val {
+            self.nulls[row / 64] |= 1 << (row % 64);
+        } else {
+            self.pages.last_mut().unwrap().1 += size;
+        }
+    }
+
+    /// Decode all non-null values stored in the page at index `i`
+    pub fn read_page(&self, i: usize) -> Result<Vec<Value>, EngineError> {
+        let (page, used) = &self.pages[i];
+        let data = page.read()?;
+        let mut buf = &data[..*used];
+        let mut vals = Vec::new();
+        while !buf.is_empty() {
+            vals.push(Value::decode(&mut buf)?);
+        }
+        Ok(vals)
+    }
+
+    /// Decode the value of a single row
+    pub fn get(&self, row: usize) -> Result<Value, EngineError> {
+        if self.is_null(row) {
+            return Ok(Value::Null);
+        }
+        let (i, offset) = self.offsets[row];
+        let (page, used) = &self.pages[i as usize];
+        let data = page.read()?;
+        Value::decode(&mut &data[offset as usize..*used])
+    }
+}
@@ -0,0 +1,122 @@
+use super::{Value, ValueType};
+use crate::alloc::AllocError;
+use std::fmt;
+
+/// Error returned by table storage operations
+#[derive(Debug)]
+pub enum EngineError {
+    /// Storing or loading table data failed in the page allocator
+    Alloc(AllocError),
+
+    /// Table with the same name already exists
+    TableExists(String),
+
+    /// Table with the name does not exist
+    NoSuchTable(String),
+
+    /// Number of values in a row does not match the number of table columns
+    ColumnCount { expected: usize, got: usize },
+
+    /// Encoded value does not fit into a single page
+    ValueTooLarge(usize),
+
+    /// Table has no column with the name
+    NoSuchColumn(String),
+
+    /// Table definition has multiple columns with the name or a row sets the
+    /// same column multiple times
+    DuplicateColumn(String),
+
+    /// Value type does not match the declared column type
+    TypeMismatch {
+        column: String,
+        expected: ValueType,
+        got: ValueType,
+    },
+
+    /// Null value for a column, that is not nullable
+    NullValue(String),
+
+    /// No value passed for a column, that has no default value
+    MissingValue(String),
+
+    /// Primary key column can not be used as part of a primary key
+    InvalidPrimaryKey {
+        column: String,
+        reason: &'static str,
+    },
+
+    /// Table has no primary key to look up rows by
+    NoPrimaryKey(String),
+
+    /// Row with the same primary key already exists in the table
+    DuplicateKey { table: String, key: Vec<Value> },
+
+    /// Stored table data could not be decoded
+    Corrupted(&'static str),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Alloc(err) => write!(f, "page allocation: {}", err),
+            Self::TableExists(name) => {
+                write!(f, "table {} already exists", name)
+            }
+            Self::NoSuchTable(name) => write!(f, "no table named {}", name),
+            Self::ColumnCount { expected, got } => write!(
+                f,
+                "row has {} values, but table has {} columns",
+                got, expected
+            ),
+            Self::ValueTooLarge(size) => {
+                write!(f, "value of {} bytes does not fit into a page", size)
+            }
+            Self::NoSuchColumn(name) => write!(f, "no column named {}", name),
+            Self::DuplicateColumn(name) => {
+                write!(f, "duplicate column {}", name)
+            }
+            Self::TypeMismatch {
+                column,
+                expected,
+                got,
+            } => write!(
+                f,
+                "column {} has type {}, but value has type {}",
+                column, expected, got
+            ),
+            Self::NullValue(name) => {
+                write!(f, "column {} is not nullable", name)
+            }
+            Self::MissingValue(name) => {
+                write!(f, "no value for column {} without a default", name)
+            }
+            Self::InvalidPrimaryKey { column, reason } => {
+                write!(f, "invalid primary key column {}: {}", column, reason)
+            }
+            Self::NoPrimaryKey(name) => {
+                write!(f, "table {} has no primary key", name)
+            }
+            Self::DuplicateKey { table, key } => {
+                write!(f, "duplicate primary key {:?} in table {}", key, table)
+            }
+            Self::Corrupted(msg) => write!(f, "table data corrupted: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Alloc(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<AllocError> for EngineError {
+    #[inline]
+    fn from(err: AllocError) -> Self {
+        Self::Alloc(err)
+    }
+}
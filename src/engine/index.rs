@@ -0,0 +1,55 @@
+use super::Value;
+use std::collections::HashMap;
+
+/// Maps the primary keys of the rows of a table to their row indices.
+///
+/// Keys are stored encoded, so values of equal keys are equal byte for byte.
+pub(super) struct PrimaryKeyIndex {
+    /// Indices of the key columns in rows in key order
+    columns: Vec<usize>,
+
+    /// Row indices by encoded key
+    rows: HashMap<Vec<u8>, usize>,
+}
+
+impl PrimaryKeyIndex {
+    pub fn new(columns: Vec<usize>) -> Self {
+        Self {
+            columns,
+            rows: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of key columns
+    #[inline]
+    pub fn key_len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Returns the key values of a row in key order
+    pub fn key_of<'a>(&self, row: &'a [Value]) -> Vec<&'a Value> {
+        self.columns.iter().map(|&i| &row[i]).collect()
+    }
+
+    /// Encode key values in key order for lookup
+    pub fn encode<'a>(key: impl IntoIterator<Item = &'a Value>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for v in key {
+            v.encode(&mut buf);
+        }
+        buf
+    }
+
+    /// Returns the index of the row with the encoded key
+    #[inline]
+    pub fn get(&self, key: &[u8]) -> Option<usize> {
+        self.rows.get(key).copied()
+    }
+
+    /// Map an encoded key, that is not yet in the index, to a row
+    #[inline]
+    pub fn insert(&mut self, key: Vec<u8>, row: usize) {
+        let prev = self.rows.insert(key, row);
+        debug_assert!(prev.is_none(), "duplicate key inserted into index");
+    }
+}
@@ -0,0 +1,464 @@
+//! Table storage engine.
+//!
+//! Stores the rows of named tables in pages of the page allocator and reads
+//! them back with full table scans. Each column is stored in its own chain of
+//! pages, so scans only load the pages of the columns they read.
+
+mod column;
+mod error;
+mod index;
+mod schema;
+mod table;
+mod value;
+
+pub use self::{
+    error::EngineError,
+    schema::{ColumnDef, TableDef, ValueType},
+    value::Value,
+};
+
+use self::{column::Column, table::Table};
+use crate::alloc::Allocator;
+use std::collections::HashMap;
+
+/// Values of a single table row in column order
+pub type Row = Vec<Value>;
+
+/// Named tables stored in pages of an allocator
+pub struct Engine {
+    /// Allocator to acquire pages for table data from
+    alloc: Allocator,
+
+    /// Tables by name
+    tables: HashMap<String, Table>,
+}
+
+impl Engine {
+    /// Create an engine with no tables, storing table data in pages of `alloc`
+    pub fn new(alloc: Allocator) -> Self {
+        Self {
+            alloc,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Create an empty table from its definition
+    pub fn create_table(&mut self, def: TableDef) -> Result<(), EngineError> {
+        if self.tables.contains_key(&def.name) {
+            return Err(EngineError::TableExists(def.name));
+        }
+        def.validate()?;
+        self.tables.insert(def.name.clone(), Table::new(def));
+        Ok(())
+    }
+
+    /// Returns the definition of a table for validating and planning queries
+    pub fn schema(&self, table: &str) -> Result<&TableDef, EngineError> {
+        Ok(self.table(table)?.def())
+    }
+
+    /// Returns the definition of a table column
+    pub fn column(
+        &self,
+        table: &str,
+        column: &str,
+    ) -> Result<&ColumnDef, EngineError> {
+        self.schema(table)?
+            .column(column)
+            .ok_or_else(|| EngineError::NoSuchColumn(column.into()))
+    }
+
+    /// Returns the number of rows in a table
+    pub fn row_count(&self, table: &str) -> Result<usize, EngineError> {
+        Ok(self.table(table)?.len())
+    }
+
+    /// Append a row with the values of all columns in order to a table.
+    ///
+    /// The values must conform to the table definition.
+    pub fn insert(&mut self, table: &str, row: Row) -> Result<(), EngineError> {
+        let (t, alloc) = self.table_mut(table)?;
+        t.insert(alloc, &row)
+    }
+
+    /// Append a row with the values of the passed columns to a table, filling
+    /// the other columns with their default values
+    pub fn insert_columns(
+        &mut self,
+        table: &str,
+        columns: &[&str],
+        vals: Row,
+    ) -> Result<(), EngineError> {
+        let (t, alloc) = self.table_mut(table)?;
+        let row = t.def().fill(columns, vals)?;
+        t.insert(alloc, &row)
+    }
+
+    /// Look up a row of a table by the values of its primary key in key order
+    pub fn get(
+        &self,
+        table: &str,
+        key: &[Value],
+    ) -> Result<Option<Row>, EngineError> {
+        self.table(table)?.get(key)
+    }
+
+    /// Iterate over all rows of a table in insertion order.
+    ///
+    /// Values are decoded a page at a time, so only one page of each column
+    /// needs to be resident at once.
+    pub fn scan(&self, table: &str) -> Result<Scan<'_>, EngineError> {
+        let table = self.table(table)?;
+        Ok(Scan::new(table, table.columns().iter().collect()))
+    }
+
+    /// Iterate over the values of the passed columns of all rows of a table in
+    /// insertion order.
+    ///
+    /// Returned rows contain only the values of the passed columns in the
+    /// passed order. Pages of other columns are not accessed.
+    pub fn scan_columns(
+        &self,
+        table: &str,
+        columns: &[&str],
+    ) -> Result<Scan<'_>, EngineError> {
+        let table = self.table(table)?;
+        let columns = columns
+            .iter()
+            .map(|name| {
+                table
+                    .def()
+                    .column_index(name)
+                    .map(|i| &table.columns()[i])
+                    .ok_or_else(|| EngineError::NoSuchColumn(name.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Scan::new(table, columns))
+    }
+
+    /// Look up a table by name
+    fn table(&self, name: &str) -> Result<&Table, EngineError> {
+        self.tables
+            .get(name)
+            .ok_or_else(|| EngineError::NoSuchTable(name.into()))
+    }
+
+    /// Look up a table by name for modification together with the allocator
+    /// for its pages
+    fn table_mut(
+        &mut self,
+        name: &str,
+    ) -> Result<(&mut Table, &Allocator), EngineError> {
+        match self.tables.get_mut(name) {
+            Some(t) => Ok((t, &self.alloc)),
+            None => Err(EngineError::NoSuchTable(name.into())),
+        }
+    }
+}
+
+/// Full scan over the rows of a table
+pub struct Scan<'a> {
+    /// Index of the next row
+    row: usize,
+
+    /// Number of rows in the table
+    rows: usize,
+
+    /// Scanned columns in output order
+    columns: Vec<ColumnScan<'a>>,
+}
+
+impl<'a> Scan<'a> {
+    fn new(table: &'a Table, columns: Vec<&'a Column>) -> Self {
+        Self {
+            row: 0,
+            rows: table.len(),
+            columns: columns
+                .into_iter()
+                .map(|column| ColumnScan {
+                    column,
+                    page: 0,
+                    vals: Vec::new().into_iter(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Position of a scan in a single column
+struct ColumnScan<'a> {
+    column: &'a Column,
+
+    /// Index of the next page to decode
+    page: usize,
+
+    /// Remaining decoded values of the current page
+    vals: std::vec::IntoIter<Value>,
+}
+
+impl<'a> ColumnScan<'a> {
+    /// Returns the value of the next row
+    fn next(&mut self, row: usize) -> Result<Value, EngineError> {
+        if self.column.is_null(row) {
+            return Ok(Value::Null);
+        }
+        loop {
+            if let Some(v) = self.vals.next() {
+                return Ok(v);
+            }
+            if self.page == self.column.page_count() {
+                return Err(EngineError::Corrupted("missing column values"));
+            }
+            self.vals = self.column.read_page(self.page)?.into_iter();
+            self.page += 1;
+        }
+    }
+}
+
+impl<'a> Iterator for Scan<'a> {
+    type Item = Result<Row, EngineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.row == self.rows {
+            return None;
+        }
+        let row = self.row;
+        self.row += 1;
+
+        let vals: Result<Row, _> =
+            self.columns.iter_mut().map(|c| c.next(row)).collect();
+        if vals.is_err() {
+            // Stop after the first error
+            self.row = self.rows;
+        }
+        Some(vals)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.rows - self.row, Some(self.rows - self.row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::AllocError;
+
+    fn engine() -> Engine {
+        Engine::new(Allocator::new(Default::default()).unwrap())
+    }
+
+    #[test]
+    fn create_insert_scan() {
+        let mut e = engine();
+        let def = TableDef::new(
+            "users",
+            vec![
+                ColumnDef::new("id", ValueType::Int),
+                ColumnDef::new("name", ValueType::Text),
+                ColumnDef {
+                    nullable: true,
+                    ..ColumnDef::new("score", ValueType::Float)
+                },
+            ],
+        );
+        e.create_table(def.clone()).unwrap();
+        assert!(matches!(
+            e.create_table(TableDef::new("users", vec![])),
+            Err(EngineError::TableExists(_))
+        ));
+        assert_eq!(e.schema("users").unwrap(), &def);
+        assert_eq!(e.column("users", "name").unwrap().ty, ValueType::Text);
+        assert!(matches!(
+            e.column("users", "missing"),
+            Err(EngineError::NoSuchColumn(_))
+        ));
+
+        // Enough rows to span multiple pages
+        let rows: Vec<Row> = (0..1000)
+            .map(|i| {
+                vec![
+                    Value::Int(i),
+                    Value::Text(format!("user {}", i)),
+                    if i % 3 == 0 {
+                        Value::Null
+                    } else {
+                        Value::Float(i as f64 / 2.0)
+                    },
+                ]
+            })
+            .collect();
+        for row in &rows {
+            e.insert("users", row.clone()).unwrap();
+        }
+        assert_eq!(e.row_count("users").unwrap(), rows.len());
+        assert!(e.tables["users"].columns()[1].page_count() > 1);
+
+        let scanned: Vec<Row> =
+            e.scan("users").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(scanned, rows);
+
+        let scanned: Vec<Row> = e
+            .scan_columns("users", &["score", "id"])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            scanned,
+            rows.iter()
+                .map(|r| vec![r[2].clone(), r[0].clone()])
+                .collect::<Vec<_>>()
+        );
+        assert!(matches!(
+            e.scan_columns("users", &["missing"]),
+            Err(EngineError::NoSuchColumn(_))
+        ));
+
+        let c = &e.tables["users"].columns()[2];
+        assert_eq!(c.get(3).unwrap(), Value::Null);
+        assert_eq!(c.get(999).unwrap(), rows[999][2]);
+    }
+
+    #[test]
+    fn failed_insert() {
+        let mut e = engine();
+        e.create_table(TableDef::new(
+            "t",
+            vec![
+                ColumnDef::new("a", ValueType::Int),
+                ColumnDef::new("b", ValueType::Text),
+            ],
+        ))
+        .unwrap();
+        let row = |i| vec![Value::Int(i), Value::Text(format!("row {}", i))];
+        e.insert("t", row(0)).unwrap();
+
+        // Fail loading the page of the second column back into memory
+        e.alloc
+            .zswap(e.tables["t"].columns()[1].page_id(0))
+            .unwrap();
+        crate::alloc::inject(crate::alloc::Fault::Checksum, 1);
+        assert!(matches!(
+            e.insert("t", row(1)),
+            Err(EngineError::Alloc(AllocError::Corrupted(_)))
+        ));
+        assert_eq!(e.row_count("t").unwrap(), 1);
+
+        e.insert("t", row(2)).unwrap();
+        let scanned: Vec<Row> =
+            e.scan("t").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(scanned, [row(0), row(2)]);
+    }
+
+    #[test]
+    fn primary_key() {
+        let mut e = engine();
+        e.create_table(TableDef {
+            primary_key: vec!["region".into(), "id".into()],
+            ..TableDef::new(
+                "t",
+                vec![
+                    ColumnDef::new("id", ValueType::Int),
+                    ColumnDef::new("region", ValueType::Text),
+                    ColumnDef {
+                        nullable: true,
+                        ..ColumnDef::new("val", ValueType::Int)
+                    },
+                ],
+            )
+        })
+        .unwrap();
+
+        let row = |id, region: &str, val| {
+            vec![Value::Int(id), Value::Text(region.into()), val]
+        };
+        for i in 0..100 {
+            e.insert("t", row(i, "eu", Value::Int(i * 2))).unwrap();
+            e.insert("t", row(i, "us", Value::Null)).unwrap();
+        }
+        match e.insert("t", row(7, "eu", Value::Int(0))) {
+            Err(EngineError::DuplicateKey { table, key }) => {
+                assert_eq!(table, "t");
+                assert_eq!(key, [Value::Text("eu".into()), Value::Int(7)]);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(e.row_count("t").unwrap(), 200);
+
+        let key =
+            |region: &str, id| [Value::Text(region.into()), Value::Int(id)];
+        assert_eq!(
+            e.get("t", &key("eu", 7)).unwrap(),
+            Some(row(7, "eu", Value::Int(14)))
+        );
+        assert_eq!(
+            e.get("t", &key("us", 99)).unwrap(),
+            Some(row(99, "us", Value::Null))
+        );
+        assert_eq!(e.get("t", &key("eu", 100)).unwrap(), None);
+        assert!(matches!(
+            e.get("t", &[Value::Int(1)]),
+            Err(EngineError::ColumnCount { .. })
+        ));
+
+        e.create_table(TableDef::new(
+            "no_key",
+            vec![ColumnDef::new("a", ValueType::Int)],
+        ))
+        .unwrap();
+        e.insert("no_key", vec![Value::Int(1)]).unwrap();
+        e.insert("no_key", vec![Value::Int(1)]).unwrap();
+        assert!(matches!(
+            e.get("no_key", &[Value::Int(1)]),
+            Err(EngineError::NoPrimaryKey(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_rows() {
+        let mut e = engine();
+        assert!(matches!(
+            e.insert("missing", vec![]),
+            Err(EngineError::NoSuchTable(_))
+        ));
+        assert!(matches!(
+            e.scan("missing"),
+            Err(EngineError::NoSuchTable(_))
+        ));
+
+        e.create_table(TableDef::new(
+            "t",
+            vec![ColumnDef {
+                default: Some(Value::Text("x".into())),
+                ..ColumnDef::new("a", ValueType::Text)
+            }],
+        ))
+        .unwrap();
+        assert!(matches!(
+            e.insert("t", vec![Value::Int(1), Value::Int(2)]),
+            Err(EngineError::ColumnCount {
+                expected: 1,
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            e.insert("t", vec![Value::Text("a".repeat(1 << 20))]),
+            Err(EngineError::ValueTooLarge(_))
+        ));
+        assert!(matches!(
+            e.insert("t", vec![Value::Int(1)]),
+            Err(EngineError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            e.insert("t", vec![Value::Null]),
+            Err(EngineError::NullValue(_))
+        ));
+        assert_eq!(e.scan("t").unwrap().count(), 0);
+
+        e.insert_columns("t", &[], vec![]).unwrap();
+        assert_eq!(
+            e.scan("t").unwrap().next().unwrap().unwrap(),
+            vec![Value::Text("x".into())]
+        );
+    }
+}
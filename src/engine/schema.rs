@@ -0,0 +1,293 @@
+use super::{EngineError, Row, Value};
+use std::fmt;
+
+/// Type of the non-null values of a column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    Int,
+    Float,
+    Text,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::Text => "text",
+        })
+    }
+}
+
+/// Definition of a table column
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+
+    /// Type of the column's non-null values
+    pub ty: ValueType,
+
+    /// Column accepts null values
+    pub nullable: bool,
+
+    /// Value stored, if none is passed on insert. Nullable columns default to
+    /// null without one.
+    pub default: Option<Value>,
+}
+
+impl ColumnDef {
+    /// Define a non-nullable column without a default value
+    pub fn new(name: &str, ty: ValueType) -> Self {
+        Self {
+            name: name.into(),
+            ty,
+            nullable: false,
+            default: None,
+        }
+    }
+
+    /// Check, if a value can be stored in the column
+    pub fn check(&self, val: &Value) -> Result<(), EngineError> {
+        match val.value_type() {
+            None if !self.nullable => {
+                Err(EngineError::NullValue(self.name.clone()))
+            }
+            Some(ty) if ty != self.ty => Err(EngineError::TypeMismatch {
+                column: self.name.clone(),
+                expected: self.ty,
+                got: ty,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Definition of a table and its columns
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableDef {
+    pub name: String,
+
+    /// Columns in the order of row values
+    pub columns: Vec<ColumnDef>,
+
+    /// Names of the columns, that uniquely identify a row, in key order.
+    /// Empty, if the table has no primary key.
+    pub primary_key: Vec<String>,
+}
+
+impl TableDef {
+    pub fn new(name: &str, columns: Vec<ColumnDef>) -> Self {
+        Self {
+            name: name.into(),
+            columns,
+            primary_key: Vec::new(),
+        }
+    }
+
+    /// Look up a column by name
+    pub fn column(&self, name: &str) -> Option<&ColumnDef> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Returns the index of a column in rows
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// Returns the indices of the primary key columns in rows in key order
+    pub fn primary_key_indices(&self) -> Vec<usize> {
+        self.primary_key
+            .iter()
+            .filter_map(|name| self.column_index(name))
+            .collect()
+    }
+
+    /// Check column names are unique, default values conform to their columns
+    /// and the primary key consists of distinct, non-nullable columns
+    pub(super) fn validate(&self) -> Result<(), EngineError> {
+        for (i, c) in self.columns.iter().enumerate() {
+            if self.columns[..i].iter().any(|prev| prev.name == c.name) {
+                return Err(EngineError::DuplicateColumn(c.name.clone()));
+            }
+            if let Some(default) = &c.default {
+                c.check(default)?;
+            }
+        }
+
+        for (i, name) in self.primary_key.iter().enumerate() {
+            let c = self
+                .column(name)
+                .ok_or_else(|| EngineError::NoSuchColumn(name.clone()))?;
+            let reason = if self.primary_key[..i].contains(name) {
+                "column listed multiple times"
+            } else if c.nullable {
+                "column is nullable"
+            } else if c.ty == ValueType::Float {
+                // Equal floats can have different encodings and NaN is not
+                // equal to itself
+                "floating point column"
+            } else {
+                continue;
+            };
+            return Err(EngineError::InvalidPrimaryKey {
+                column: name.clone(),
+                reason,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check, if a row with the values of all columns in order conforms to the
+    /// table
+    pub fn check_row(&self, row: &[Value]) -> Result<(), EngineError> {
+        if row.len() != self.columns.len() {
+            return Err(EngineError::ColumnCount {
+                expected: self.columns.len(),
+                got: row.len(),
+            });
+        }
+        for (c, v) in self.columns.iter().zip(row) {
+            c.check(v)?;
+        }
+        Ok(())
+    }
+
+    /// Build a row from the values of the passed columns, filling the other
+    /// columns with their default values
+    pub fn fill(
+        &self,
+        columns: &[&str],
+        vals: Row,
+    ) -> Result<Row, EngineError> {
+        if columns.len() != vals.len() {
+            return Err(EngineError::ColumnCount {
+                expected: columns.len(),
+                got: vals.len(),
+            });
+        }
+
+        let mut row: Vec<Option<Value>> = vec![None; self.columns.len()];
+        for (name, v) in columns.iter().zip(vals) {
+            let i = self
+                .column_index(name)
+                .ok_or_else(|| EngineError::NoSuchColumn(name.to_string()))?;
+            if row[i].is_some() {
+                return Err(EngineError::DuplicateColumn(name.to_string()));
+            }
+            row[i] = Some(v);
+        }
+        row.into_iter()
+            .zip(&self.columns)
+            .map(|(v, c)| match (v, &c.default) {
+                (Some(v), _) => Ok(v),
+                (None, Some(default)) => Ok(default.clone()),
+                (None, None) if c.nullable => Ok(Value::Null),
+                (None, None) => Err(EngineError::MissingValue(c.name.clone())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def() -> TableDef {
+        TableDef::new(
+            "t",
+            vec![
+                ColumnDef::new("id", ValueType::Int),
+                ColumnDef {
+                    nullable: true,
+                    ..ColumnDef::new("name", ValueType::Text)
+                },
+                ColumnDef {
+                    default: Some(Value::Float(1.0)),
+                    ..ColumnDef::new("score", ValueType::Float)
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn check_row() {
+        let def = def();
+        def.check_row(&[Value::Int(1), Value::Null, Value::Float(2.0)])
+            .unwrap();
+        assert!(matches!(
+            def.check_row(&[Value::Null, Value::Null, Value::Float(2.0)]),
+            Err(EngineError::NullValue(_))
+        ));
+        assert!(matches!(
+            def.check_row(&[Value::Int(1), Value::Null, Value::Int(2)]),
+            Err(EngineError::TypeMismatch {
+                expected: ValueType::Float,
+                got: ValueType::Int,
+                ..
+            })
+        ));
+        assert!(matches!(
+            def.check_row(&[Value::Int(1)]),
+            Err(EngineError::ColumnCount { .. })
+        ));
+    }
+
+    #[test]
+    fn fill() {
+        let def = def();
+        assert_eq!(
+            def.fill(&["id"], vec![Value::Int(1)]).unwrap(),
+            vec![Value::Int(1), Value::Null, Value::Float(1.0)]
+        );
+        assert!(matches!(
+            def.fill(&["name"], vec![Value::Text("a".into())]),
+            Err(EngineError::MissingValue(_))
+        ));
+        assert!(matches!(
+            def.fill(&["id", "id"], vec![Value::Int(1), Value::Int(2)]),
+            Err(EngineError::DuplicateColumn(_))
+        ));
+        assert!(matches!(
+            def.fill(&["x"], vec![Value::Int(1)]),
+            Err(EngineError::NoSuchColumn(_))
+        ));
+    }
+
+    #[test]
+    fn validate() {
+        def().validate().unwrap();
+
+        let mut def = def();
+        def.columns[2].default = Some(Value::Null);
+        assert!(matches!(def.validate(), Err(EngineError::NullValue(_))));
+
+        def.columns[2] = ColumnDef::new("id", ValueType::Int);
+        assert!(matches!(
+            def.validate(),
+            Err(EngineError::DuplicateColumn(_))
+        ));
+    }
+
+    #[test]
+    fn validate_primary_key() {
+        let with_key = |key: &[&str]| TableDef {
+            primary_key: key.iter().map(|k| k.to_string()).collect(),
+            ..def()
+        };
+        with_key(&["id"]).validate().unwrap();
+        assert_eq!(with_key(&["id"]).primary_key_indices(), [0]);
+
+        assert!(matches!(
+            with_key(&["x"]).validate(),
+            Err(EngineError::NoSuchColumn(_))
+        ));
+        for key in [&["id", "id"][..], &["name"], &["score"]] {
+            assert!(matches!(
+                with_key(key).validate(),
+                Err(EngineError::InvalidPrimaryKey { .. })
+            ));
+        }
+    }
+}
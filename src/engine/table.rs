@@ -0,0 +1,128 @@
+use super::{
+    column::Column, index::PrimaryKeyIndex, EngineError, Row, TableDef, Value,
+};
+use crate::alloc::Allocator;
+
+/// Rows of a table stored column by column
+pub(super) struct Table {
+    def: TableDef,
+
+    /// Stored values of the columns in the order of `def`
+    columns: Vec<Column>,
+
+    /// Index of the primary key, if the table has one
+    index: Option<PrimaryKeyIndex>,
+
+    /// Number of rows in the table
+    rows: usize,
+}
+
+impl Table {
+    pub fn new(def: TableDef) -> Self {
+        Self {
+            columns: def.columns.iter().map(|_| Column::new()).collect(),
+            index: if def.primary_key.is_empty() {
+                None
+            } else {
+                Some(PrimaryKeyIndex::new(def.primary_key_indices()))
+            },
+            def,
+            rows: 0,
+        }
+    }
+
+    /// Returns the definition of the table
+    #[inline]
+    pub fn def(&self) -> &TableDef {
+        &self.def
+    }
+
+    /// Returns the columns in order
+    #[inline]
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the number of rows in the table
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    /// Append a row, acquiring new column pages from `alloc` as needed.
+    ///
+    /// Either all or none of the row's values are stored.
+    pub fn insert(
+        &mut self,
+        alloc: &Allocator,
+        row: &Row,
+    ) -> Result<(), EngineError> {
+        self.def.check_row(row)?;
+
+        let key = match &self.index {
+            Some(index) => {
+                let vals = index.key_of(row);
+                let key = PrimaryKeyIndex::encode(vals.iter().copied());
+                if index.get(&key).is_some() {
+                    return Err(EngineError::DuplicateKey {
+                        table: self.def.name.clone(),
+                        key: vals.into_iter().cloned().collect(),
+                    });
+                }
+                Some(key)
+            }
+            None => None,
+        };
+
+        let encoded: Vec<Vec<u8>> = row
+            .iter()
+            .map(|v| {
+                let mut buf = Vec::new();
+                v.encode(&mut buf);
+                buf
+            })
+            .collect();
+        for ((c, v), buf) in self.columns.iter_mut().zip(row).zip(&encoded) {
+            if *v != Value::Null {
+                c.reserve(alloc, buf.len())?;
+            }
+        }
+        for ((c, v), buf) in self.columns.iter().zip(row).zip(&encoded) {
+            if *v != Value::Null {
+                c.write(buf)?;
+            }
+        }
+        for ((c, v), buf) in self.columns.iter_mut().zip(row).zip(&encoded) {
+            c.commit(v, buf.len());
+        }
+        if let (Some(index), Some(key)) = (&mut self.index, key) {
+            index.insert(key, self.rows);
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Look up a row by the values of its primary key in key order
+    pub fn get(&self, key: &[Value]) -> Result<Option<Row>, EngineError> {
+        let index = self
+            .index
+            .as_ref()
+            .ok_or_else(|| EngineError::NoPrimaryKey(self.def.name.clone()))?;
+        if key.len() != index.key_len() {
+            return Err(EngineError::ColumnCount {
+                expected: index.key_len(),
+                got: key.len(),
+            });
+        }
+
+        match index.get(&PrimaryKeyIndex::encode(key)) {
+            Some(row) => self
+                .columns
+                .iter()
+                .map(|c| c.get(row))
+                .collect::<Result<_, _>>()
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::{EngineError, ValueType};
+use std::convert::TryInto;
+
+/// Single value stored in a table cell
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Type tags of encoded values
+const NULL: u8 = 0;
+const BOOL: u8 = 1;
+const INT: u8 = 2;
+const FLOAT: u8 = 3;
+const TEXT: u8 = 4;
+
+impl Value {
+    /// Returns the type of the value or None, if it is null
+    pub fn value_type(&self) -> Option<ValueType> {
+        match self {
+            Self::Null => None,
+            Self::Bool(_) => Some(ValueType::Bool),
+            Self::Int(_) => Some(ValueType::Int),
+            Self::Float(_) => Some(ValueType::Float),
+            Self::Text(_) => Some(ValueType::Text),
+        }
+    }
+
+    /// Append the encoded value to `buf`
+    pub(super) fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Self::Null => buf.push(NULL),
+            Self::Bool(b) => buf.extend_from_slice(&[BOOL, *b as u8]),
+            Self::Int(i) => {
+                buf.push(INT);
+                buf.extend_from_slice(&i.to_le_bytes());
+            }
+            Self::Float(f) => {
+                buf.push(FLOAT);
+                buf.extend_from_slice(&f.to_bits().to_le_bytes());
+            }
+            Self::Text(s) => {
+                buf.push(TEXT);
+                buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                buf.extend_from_slice(s.as_bytes());
+            }
+        }
+    }
+
+    /// Decode a value from the start of `buf` and advance it past the value
+    pub(super) fn decode(buf: &mut &[u8]) -> Result<Self, EngineError> {
+        let tag = take(buf, 1)?[0];
+        Ok(match tag {
+            NULL => Self::Null,
+            BOOL => Self::Bool(take(buf, 1)?[0] != 0),
+            INT => {
+                Self::Int(i64::from_le_bytes(take(buf, 8)?.try_into().unwrap()))
+            }
+            FLOAT => Self::Float(f64::from_bits(u64::from_le_bytes(
+                take(buf, 8)?.try_into().unwrap(),
+            ))),
+            TEXT => {
+                let len = u32::from_le_bytes(take(buf, 4)?.try_into().unwrap());
+                let s = take(buf, len as usize)?;
+                Self::Text(
+                    String::from_utf8(s.to_vec())
+                        .map_err(|_| EngineError::Corrupted("invalid UTF-8"))?,
+                )
+            }
+            _ => return Err(EngineError::Corrupted("unknown value type")),
+        })
+    }
+}
+
+/// Split `n` bytes off the start of `buf`
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8], EngineError> {
+    if buf.len() < n {
+        return Err(EngineError::Corrupted("truncated value"));
+    }
+    let (head, tail) = buf.split_at(n);
+    *buf = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let vals = [
+            Value::Null,
+            Value::Bool(true),
+            Value::Int(-7),
+            Value::Float(1.5),
+            Value::Text("ĉu vi?".into()),
+            Value::Text(String::new()),
+        ];
+        let mut buf = Vec::new();
+        for v in &vals {
+            v.encode(&mut buf);
+        }
+
+        let mut rest = &buf[..];
+        for v in &vals {
+            assert_eq!(&Value::decode(&mut rest).unwrap(), v);
+        }
+        assert!(rest.is_empty());
+
+        let mut truncated = &buf[..buf.len() - 1];
+        for _ in 0..vals.len() - 1 {
+            Value::decode(&mut truncated).unwrap();
+        }
+        assert!(Value::decode(&mut truncated).is_err());
+    }
+}
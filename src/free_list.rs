@@ -0,0 +1,900 @@
+use crate::linked_list::{CheckedRef, CursorMut, LinkedList};
+use alloc_crate::vec::Vec;
+use core::convert::TryInto;
+
+/// Alignment of all free ranges
+const WORD: usize = core::mem::size_of::<usize>();
+
+/// Size of an encoded free range
+const ENCODED_RANGE_SIZE: usize = 8;
+
+/// Range of memory in a buffer
+#[derive(Clone, Eq, PartialEq)]
+struct Range {
+    /// Offset from page or segment start
+    offset: usize,
+
+    /// Size of range in bytes
+    size: usize,
+}
+
+impl Range {
+    /// Modifying the range to allocate a buffer in its start and return the
+    // allocation's offset.
+    /// The caller must ensure the range has more capacity than needed.
+    fn allocate(&mut self, size: usize) -> usize {
+        let off = self.offset;
+        self.offset += size;
+        self.size -= size;
+        off
+    }
+}
+
+/// Doubly linked list for keeping track of free memory ranges in a page
+pub struct FreeList {
+    /// Underlying free range linked list
+    list: LinkedList<Range>,
+
+    /// Last inserted into free memory range
+    last_used: Option<CheckedRef<Range>>,
+
+    /// Strategy used by `allocate()`
+    fit: Fit,
+
+    /// Size of the tracked memory in bytes
+    cap: usize,
+}
+
+/// Result of an `insert()` call to the FreeList
+pub enum AllocationResult {
+    /// Successfully allocated. Contains the offset of the allocation.
+    Allocated(usize),
+
+    /// Space large enough for the allocation not found. Contains the size of
+    /// the largest free memory region encountered.
+    NotFound(usize),
+}
+
+/// Strategy for choosing the free range to allocate from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fit {
+    /// First range large enough, trying the last used one before scanning.
+    /// Expect faster lookup times to outweigh the possible greater
+    /// fragmentation.
+    First,
+
+    /// Smallest range large enough. Scans all ranges, but keeps large ranges
+    /// intact for later allocations, when allocation sizes are mixed.
+    Best,
+
+    /// First range large enough, scanning from the last used one and wrapping
+    /// around, so allocations are spread over the entire capacity
+    Next,
+}
+
+impl Default for Fit {
+    #[inline]
+    fn default() -> Self {
+        Self::First
+    }
+}
+
+impl FreeList {
+    /// Creates a new `FreeList` with the passed capacity using first-fit
+    /// allocation
+    pub fn new(cap: usize) -> Self {
+        Self::with_fit(cap, Fit::First)
+    }
+
+    /// Creates a new `FreeList` with the passed capacity and allocation
+    /// strategy
+    pub fn with_fit(cap: usize, fit: Fit) -> Self {
+        let mut ll = LinkedList::new();
+        Self {
+            fit,
+            cap,
+            last_used: {
+                let mut c = ll.cursor_mut();
+                c.insert_after(Range {
+                    offset: 0,
+                    size: cap,
+                });
+                c.checked_reference()
+            },
+            list: ll,
+        }
+    }
+
+    /// Encode the free ranges as little-endian 32 bit offset and size pairs
+    /// sorted by offset
+    pub fn encode(&mut self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.list.len() * ENCODED_RANGE_SIZE);
+        for r in self.list.iter_mut() {
+            buf.extend_from_slice(&(r.offset as u32).to_le_bytes());
+            buf.extend_from_slice(&(r.size as u32).to_le_bytes());
+        }
+        buf
+    }
+
+    /// Reconstruct a `FreeList` with the passed capacity and allocation
+    /// strategy from free ranges encoded with `encode()`
+    pub fn decode(
+        cap: usize,
+        fit: Fit,
+        buf: &[u8],
+    ) -> Result<Self, &'static str> {
+        if !buf.len().is_multiple_of(ENCODED_RANGE_SIZE) {
+            return Err("truncated free range");
+        }
+
+        let mut end = None;
+        let mut list = LinkedList::new();
+        let mut c = list.cursor_mut();
+        for b in buf.chunks_exact(ENCODED_RANGE_SIZE) {
+            let field =
+                |i: usize| u32::from_le_bytes(b[i..i + 4].try_into().unwrap());
+            let (offset, size) = (field(0) as usize, field(4) as usize);
+            if !offset.is_multiple_of(WORD) || size == 0 {
+                return Err("invalid free range");
+            }
+            if end.is_some_and(|end| offset <= end) {
+                return Err("free ranges not sorted, overlapping or adjacent");
+            }
+            let e = offset + size;
+            end = Some(e);
+            if e > cap {
+                return Err("free range exceeds capacity");
+            }
+            c.insert_after(Range { offset, size });
+            c.next();
+        }
+
+        Ok(Self {
+            list,
+            last_used: None,
+            fit,
+            cap,
+        })
+    }
+
+    /// Pad size to ensure all free ranges are aligned
+    fn pad_size(size: &mut usize) {
+        *size += WORD - (*size % WORD);
+    }
+
+    /// Tries to register an insertion in the free list and returns the offset
+    // to write the data to, if a space for it can be found.
+    pub fn allocate(&mut self, size: usize) -> AllocationResult {
+        self.allocate_fit(size, self.fit)
+    }
+
+    /// Same as `allocate()`, but overrides the list's strategy for this
+    /// allocation
+    pub fn allocate_fit(
+        &mut self,
+        mut size: usize,
+        fit: Fit,
+    ) -> AllocationResult {
+        Self::pad_size(&mut size);
+        match fit {
+            Fit::First => self.first_fit(size),
+            Fit::Best => self.best_fit(size),
+            Fit::Next => self.next_fit(size),
+        }
+    }
+
+    /// Same as `allocate()`, but the returned offset is a multiple of `align`,
+    /// which must be a power of two.
+    ///
+    /// Ranges are scanned first-fit regardless of the list's strategy. Space
+    /// skipped to align the allocation stays free. `AllocationResult::NotFound`
+    /// might be returned, even if the largest free range is large enough, but
+    /// not at an aligned offset.
+    pub fn allocate_aligned(
+        &mut self,
+        mut size: usize,
+        align: usize,
+    ) -> AllocationResult {
+        assert!(
+            align.is_power_of_two(),
+            "alignment not a power of two: {}",
+            align
+        );
+        if align <= WORD {
+            return self.allocate(size);
+        }
+        Self::pad_size(&mut size);
+
+        let mut c = self.list.cursor_mut();
+        let mut max_size = 0;
+        loop {
+            let range = match c.value() {
+                Some(r) => r,
+                None => return AllocationResult::NotFound(0),
+            };
+            let gap = range.offset.next_multiple_of(align) - range.offset;
+            if range.size >= gap + size {
+                if gap == 0 {
+                    return AllocationResult::Allocated(Self::take(
+                        &mut self.last_used,
+                        c,
+                        size,
+                    ));
+                }
+
+                // Keep the gap as a free range and split off the rest
+                let offset = range.offset + gap;
+                let rest = range.size - gap - size;
+                range.size = gap;
+                if rest != 0 {
+                    c.insert_after(Range {
+                        offset: offset + size,
+                        size: rest,
+                    });
+                }
+                return AllocationResult::Allocated(offset);
+            }
+            max_size = max_size.max(range.size);
+
+            if !c.next() {
+                return AllocationResult::NotFound(max_size);
+            }
+        }
+    }
+
+    /// Allocate from the first range large enough, trying the last used range
+    /// first
+    fn first_fit(&mut self, size: usize) -> AllocationResult {
+        // Hot path
+        let list = &mut self.list;
+        if let Some(mut c) =
+            self.last_used.as_ref().and_then(|r| r.cursor_mut(list))
+        {
+            if c.value().unwrap().size >= size {
+                return AllocationResult::Allocated(Self::take(
+                    &mut self.last_used,
+                    c,
+                    size,
+                ));
+            }
+        }
+
+        let mut c = self.list.cursor_mut();
+        let mut max_size = 0;
+        loop {
+            let range = match c.value() {
+                Some(r) => r,
+                None => return AllocationResult::NotFound(0),
+            };
+            if range.size >= size {
+                return AllocationResult::Allocated(Self::take(
+                    &mut self.last_used,
+                    c,
+                    size,
+                ));
+            }
+            max_size = max_size.max(range.size);
+
+            if !c.next() {
+                return AllocationResult::NotFound(max_size);
+            }
+        }
+    }
+
+    /// Allocate from the smallest range large enough
+    fn best_fit(&mut self, size: usize) -> AllocationResult {
+        // Position and size of the best range found so far
+        let mut best: Option<(usize, usize)> = None;
+        let mut max_size = 0;
+        for (i, range) in self.list.iter_mut().enumerate() {
+            if range.size >= size && best.is_none_or(|(_, s)| range.size < s) {
+                best = Some((i, range.size));
+                if range.size == size {
+                    break;
+                }
+            }
+            max_size = max_size.max(range.size);
+        }
+
+        match best {
+            Some((i, _)) => {
+                let mut c = self.list.cursor_mut();
+                for _ in 0..i {
+                    c.next();
+                }
+                AllocationResult::Allocated(Self::take(
+                    &mut self.last_used,
+                    c,
+                    size,
+                ))
+            }
+            None => AllocationResult::NotFound(max_size),
+        }
+    }
+
+    /// Allocate from the first range large enough, scanning from the last used
+    /// range and wrapping around to the start of the list
+    fn next_fit(&mut self, size: usize) -> AllocationResult {
+        let len = self.list.len();
+        let mut c = match &self.last_used {
+            Some(r) if r.is_valid(&self.list) => {
+                r.cursor_mut(&mut self.list).unwrap()
+            }
+            _ => self.list.cursor_mut(),
+        };
+        let mut max_size = 0;
+        for _ in 0..len {
+            let range = c.value().unwrap();
+            if range.size >= size {
+                return AllocationResult::Allocated(Self::take(
+                    &mut self.last_used,
+                    c,
+                    size,
+                ));
+            }
+            max_size = max_size.max(range.size);
+
+            if !c.next() {
+                c.seek_start();
+            }
+        }
+        AllocationResult::NotFound(max_size)
+    }
+
+    /// Allocate `size` bytes from the start of the range at the cursor, that
+    /// must be large enough, and return the allocation's offset.
+    ///
+    /// The range is removed, if depleted, and its neighbour remembered as the
+    /// last used range instead.
+    fn take(
+        last_used: &mut Option<CheckedRef<Range>>,
+        mut c: CursorMut<'_, Range, 8>,
+        size: usize,
+    ) -> usize {
+        let range = c.value().unwrap();
+        if range.size > size {
+            // Still some space left in the range
+            *last_used = c.checked_reference();
+            return range.allocate(size);
+        }
+
+        // Range depleted
+        let offset = range.offset;
+        c.remove();
+        *last_used = c.checked_reference();
+        offset
+    }
+
+    /// Mark multiple memory regions as free in the list, merging them with
+    /// each other and the existing free ranges, if contiguous.
+    ///
+    /// Sorts the regions and rebuilds the list in a single pass. Nothing is
+    /// freed, if any of the regions overlap.
+    pub fn free_many(
+        &mut self,
+        ranges: &[(usize, usize)],
+    ) -> Result<(), &'static str> {
+        let mut freed: Vec<_> = ranges
+            .iter()
+            .map(|&(offset, mut size)| {
+                Self::pad_size(&mut size);
+                Range { offset, size }
+            })
+            .collect();
+        freed.sort_unstable_by_key(|r| r.offset);
+
+        let mut merged: Vec<Range> =
+            Vec::with_capacity(self.list.len() + freed.len());
+        let mut existing = self.list.iter_mut().map(|r| r.clone()).peekable();
+        let mut freed = freed.into_iter().peekable();
+        loop {
+            let next = match (existing.peek(), freed.peek()) {
+                (Some(a), Some(b)) if a.offset <= b.offset => existing.next(),
+                (_, Some(_)) => freed.next(),
+                (Some(_), None) => existing.next(),
+                (None, None) => break,
+            }
+            .unwrap();
+            match merged.last_mut() {
+                Some(last) if last.offset + last.size > next.offset => {
+                    return Err("new range overlaps with existing range");
+                }
+                Some(last) if last.offset + last.size == next.offset => {
+                    last.size += next.size;
+                }
+                _ => merged.push(next),
+            }
+        }
+
+        drop(existing);
+
+        // Upholds the safety contract
+        self.last_used = None;
+        self.list = merged.into_iter().collect();
+        Ok(())
+    }
+
+    /// Free all memory, restoring a single range spanning the entire capacity
+    pub fn reset(&mut self) {
+        *self = Self::with_fit(self.cap, self.fit);
+    }
+
+    /// Panic, if the free ranges are not sorted, overlapping, adjacent to each
+    /// other, empty, unaligned or exceed the capacity. For validating the list
+    /// in tests and debugging.
+    pub fn check_invariants(&mut self) {
+        let cap = self.cap;
+        let mut prev: Option<Range> = None;
+        for r in self.list.iter_mut() {
+            assert!(r.size != 0, "empty free range at {}", r.offset);
+            assert!(
+                r.offset.is_multiple_of(WORD) && r.size.is_multiple_of(WORD),
+                "unaligned free range {}+{}",
+                r.offset,
+                r.size
+            );
+            assert!(
+                r.offset + r.size <= cap,
+                "free range {}+{} exceeds capacity {}",
+                r.offset,
+                r.size,
+                cap
+            );
+            if let Some(p) = &prev {
+                assert!(
+                    p.offset + p.size < r.offset,
+                    "free range {}+{} not sorted, overlapping or adjacent to \
+                     {}+{}",
+                    r.offset,
+                    r.size,
+                    p.offset,
+                    p.size
+                );
+            }
+            prev = Some(r.clone());
+        }
+    }
+
+    /// Returns the total size of all free ranges in bytes
+    pub fn free_bytes(&mut self) -> usize {
+        self.list.iter_mut().map(|r| r.size).sum()
+    }
+
+    /// Returns the size of the largest free range in bytes
+    pub fn largest_free(&mut self) -> usize {
+        self.list.iter_mut().map(|r| r.size).max().unwrap_or(0)
+    }
+
+    /// Returns the number of free ranges
+    #[inline]
+    pub fn free_ranges(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns the share of free bytes outside of the largest free range
+    /// from 0 to 1.
+    ///
+    /// 0 means all free memory is contiguous or there is none. Values close to
+    /// 1 mean free memory is scattered in many small ranges and large
+    /// allocations are likely to fail, even with enough free bytes.
+    pub fn fragmentation(&mut self) -> f64 {
+        let (free, largest) =
+            self.list.iter_mut().fold((0, 0), |(free, largest), r| {
+                (free + r.size, largest.max(r.size))
+            });
+        if free == 0 {
+            0.0
+        } else {
+            1.0 - largest as f64 / free as f64
+        }
+    }
+
+    /// Mark a memory region as free in the list, merging it with the
+    /// preceding and following free ranges, if contiguous
+    pub fn free(
+        &mut self,
+        offset: usize,
+        mut size: usize,
+    ) -> Result<(), &'static str> {
+        Self::pad_size(&mut size);
+        let end = offset + size;
+
+        // Search from the last used range, as memory is often freed close to
+        // recent allocations
+        let mut c = match &self.last_used {
+            Some(r) if r.is_valid(&self.list) => {
+                r.cursor_mut(&mut self.list).unwrap()
+            }
+            _ => self.list.cursor_mut(),
+        };
+        c.insert_sorted(Range { offset, size }, |a, b| a.offset.cmp(&b.offset));
+        if c.peek_prev()
+            .is_some_and(|prev| prev.offset + prev.size > offset)
+            || c.peek_next().is_some_and(|next| next.offset < end)
+        {
+            c.remove();
+            return Err("new range overlaps with existing range");
+        }
+
+        if c.peek_next().is_some_and(|next| next.offset == end) {
+            c.next();
+            let next_size = c.value().unwrap().size;
+            Self::remove_range(&mut self.last_used, &mut c);
+            c.value().unwrap().size += next_size;
+        }
+        if c.peek_prev()
+            .is_some_and(|prev| prev.offset + prev.size == offset)
+        {
+            let size = c.value().unwrap().size;
+            Self::remove_range(&mut self.last_used, &mut c);
+            c.value().unwrap().size += size;
+        }
+        if self.last_used.is_none() {
+            self.last_used = c.checked_reference();
+        }
+        Ok(())
+    }
+
+    /// Remove the range at the cursor and move the cursor to the previous one,
+    /// if any, forgetting the range as the last used one
+    fn remove_range(
+        last_used: &mut Option<CheckedRef<Range>>,
+        c: &mut CursorMut<'_, Range, 8>,
+    ) {
+        if let Some((_, Some(removed))) = c.remove() {
+            if last_used.as_ref().is_some_and(|r| *r == removed) {
+                *last_used = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    impl FreeList {
+        /// Returns the free ranges as offset and size pairs
+        fn ranges(&mut self) -> Vec<(usize, usize)> {
+            self.list.iter_mut().map(|r| (r.offset, r.size)).collect()
+        }
+    }
+
+    fn allocate(fl: &mut FreeList, size: usize) -> usize {
+        match fl.allocate(size) {
+            AllocationResult::Allocated(offset) => offset,
+            AllocationResult::NotFound(_) => panic!("no space for {}", size),
+        }
+    }
+
+    #[test]
+    fn coalesce() {
+        // Sizes are padded by a word
+        let size = 3 * WORD;
+        let padded = 4 * WORD;
+
+        let mut fl = FreeList::new(1 << 10);
+        let offsets: Vec<_> = (0..4).map(|_| allocate(&mut fl, size)).collect();
+        assert_eq!(fl.ranges(), [(4 * padded, (1 << 10) - 4 * padded)]);
+
+        // Not contiguous with any range
+        fl.free(offsets[1], size).unwrap();
+        assert_eq!(fl.ranges()[0], (padded, padded));
+
+        // Merge with the following range
+        fl.free(offsets[0], size).unwrap();
+        assert_eq!(fl.ranges()[0], (0, 2 * padded));
+
+        // Merge with the preceding range
+        fl.free(offsets[3], size).unwrap();
+        assert_eq!(
+            fl.ranges(),
+            [(0, 2 * padded), (3 * padded, (1 << 10) - 3 * padded)]
+        );
+
+        // Merge with both
+        fl.free(offsets[2], size).unwrap();
+        assert_eq!(fl.ranges(), [(0, 1 << 10)]);
+    }
+
+    #[test]
+    fn fit() {
+        // Leave free ranges of 128, 64 and 512 bytes at offsets 64, 256 and
+        // 512. Requesting 63 bytes allocates 64 after padding.
+        let setup = |fit| {
+            let mut fl = FreeList::with_fit(1 << 10, fit);
+            for _ in 0..8 {
+                allocate(&mut fl, 63);
+            }
+            for offset in [64, 128, 256] {
+                fl.free(offset, 63).unwrap();
+            }
+            assert_eq!(fl.ranges(), [(64, 128), (256, 64), (512, 512)]);
+            fl
+        };
+
+        // The last used range is tried before scanning
+        assert_eq!(allocate(&mut setup(Fit::First), 63), 512);
+        assert_eq!(allocate(&mut setup(Fit::Best), 63), 256);
+
+        // Continues from the last used range and wraps around
+        let mut fl = setup(Fit::Next);
+        assert_eq!(allocate(&mut fl, 63), 512);
+        assert_eq!(allocate(&mut fl, 127), 576);
+        assert_eq!(allocate(&mut fl, 319), 704);
+        assert_eq!(allocate(&mut fl, 100), 64);
+        assert_eq!(fl.ranges(), [(168, 24), (256, 64)]);
+
+        // Strategy can be overridden per allocation
+        let mut fl = setup(Fit::First);
+        assert!(matches!(
+            fl.allocate_fit(63, Fit::Best),
+            AllocationResult::Allocated(256)
+        ));
+        assert!(matches!(
+            fl.allocate_fit(1 << 10, Fit::Best),
+            AllocationResult::NotFound(512)
+        ));
+    }
+
+    #[test]
+    fn aligned() {
+        let mut fl = FreeList::new(1 << 10);
+        assert_eq!(allocate(&mut fl, 1), 0);
+
+        let offset = match fl.allocate_aligned(100, 64) {
+            AllocationResult::Allocated(offset) => offset,
+            AllocationResult::NotFound(_) => panic!("no space"),
+        };
+        assert_eq!(offset, 64);
+        assert_eq!(fl.ranges(), [(WORD, 64 - WORD), (168, 856)]);
+
+        // The skipped space is still usable and merged back on free
+        assert_eq!(allocate(&mut fl, 1), WORD);
+        fl.free(offset, 100).unwrap();
+        assert_eq!(fl.ranges(), [(2 * WORD, (1 << 10) - 2 * WORD)]);
+
+        // Only an unaligned range is large enough
+        let mut fl = FreeList::new(1 << 10);
+        allocate(&mut fl, 1);
+        assert!(matches!(
+            fl.allocate_aligned(1000, 512),
+            AllocationResult::NotFound(_)
+        ));
+    }
+
+    #[test]
+    fn metrics() {
+        let mut fl = FreeList::new(1 << 10);
+        assert_eq!(fl.free_bytes(), 1 << 10);
+        assert_eq!(fl.largest_free(), 1 << 10);
+        assert_eq!(fl.free_ranges(), 1);
+        assert_eq!(fl.fragmentation(), 0.0);
+
+        let offsets: Vec<_> = (0..16).map(|_| allocate(&mut fl, 63)).collect();
+        assert_eq!(fl.free_bytes(), 0);
+        assert_eq!(fl.largest_free(), 0);
+        assert_eq!(fl.free_ranges(), 0);
+        assert_eq!(fl.fragmentation(), 0.0);
+
+        // Free every other block
+        for offset in offsets.iter().step_by(2) {
+            fl.free(*offset, 63).unwrap();
+        }
+        assert_eq!(fl.free_bytes(), 512);
+        assert_eq!(fl.largest_free(), 64);
+        assert_eq!(fl.free_ranges(), 8);
+        assert_eq!(fl.fragmentation(), 1.0 - 64.0 / 512.0);
+
+        for offset in offsets.iter().skip(1).step_by(2) {
+            fl.free(*offset, 63).unwrap();
+        }
+        assert_eq!(fl.free_ranges(), 1);
+        assert_eq!(fl.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut fl = FreeList::with_fit(1 << 10, Fit::Best);
+        let offsets: Vec<_> = (0..4).map(|_| allocate(&mut fl, 63)).collect();
+        fl.free(offsets[1], 63).unwrap();
+
+        let buf = fl.encode();
+        assert_eq!(buf.len(), 2 * ENCODED_RANGE_SIZE);
+        let mut decoded = FreeList::decode(1 << 10, Fit::Best, &buf).unwrap();
+        assert_eq!(decoded.ranges(), fl.ranges());
+        assert_eq!(allocate(&mut decoded, 63), offsets[1]);
+        decoded.free(offsets[0], 63).unwrap();
+        assert_eq!(decoded.ranges(), [(0, 64), (256, 768)]);
+
+        assert_eq!(
+            FreeList::decode(1 << 10, Fit::First, &[])
+                .unwrap()
+                .free_ranges(),
+            0
+        );
+        assert!(FreeList::decode(1 << 10, Fit::First, &buf[1..]).is_err());
+        assert!(FreeList::decode(1 << 9, Fit::First, &buf).is_err());
+
+        let mut reversed = buf[8..].to_vec();
+        reversed.extend_from_slice(&buf[..8]);
+        assert!(FreeList::decode(1 << 10, Fit::First, &reversed).is_err());
+    }
+
+    #[test]
+    fn free_many() {
+        let mut fl = FreeList::new(1 << 10);
+        let offsets: Vec<_> = (0..8).map(|_| allocate(&mut fl, 63)).collect();
+        fl.free(offsets[2], 63).unwrap();
+
+        // Overlaps with an existing range or each other
+        let before = fl.ranges();
+        assert!(fl.free_many(&[(offsets[0], 63), (offsets[2], 63)]).is_err());
+        assert!(fl.free_many(&[(offsets[0], 63), (offsets[0], 63)]).is_err());
+        assert_eq!(fl.ranges(), before);
+
+        fl.free_many(&[(offsets[6], 63), (offsets[0], 63), (offsets[1], 63)])
+            .unwrap();
+        assert_eq!(fl.ranges(), [(0, 192), (384, 64), (512, 512)]);
+        assert_eq!(allocate(&mut fl, 63), 0);
+
+        fl.reset();
+        assert_eq!(fl.ranges(), [(0, 1 << 10)]);
+        assert_eq!(allocate(&mut fl, 63), 0);
+    }
+
+    #[test]
+    fn overlapping_free() {
+        let mut fl = FreeList::new(1 << 10);
+        let a = allocate(&mut fl, WORD);
+        let b = allocate(&mut fl, WORD);
+        fl.free(a, WORD).unwrap();
+        assert!(fl.free(a, WORD).is_err());
+        assert!(fl.free(b + WORD, WORD).is_err());
+        assert!(fl.free(a + WORD, 3 * WORD).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "not sorted, overlapping or adjacent")]
+    fn check_invariants() {
+        let mut fl = FreeList::new(1 << 10);
+        fl.check_invariants();
+        fl.list.cursor_mut().insert_before(Range {
+            offset: 1 << 9,
+            size: 8,
+        });
+        fl.check_invariants();
+    }
+
+    /// Byte map of allocated memory to check `FreeList` results against
+    struct Reference(Vec<bool>);
+
+    impl Reference {
+        /// Mark the range as allocated or free, asserting it was in the other
+        /// state before
+        fn mark(&mut self, offset: usize, mut size: usize, allocated: bool) {
+            FreeList::pad_size(&mut size);
+            for b in &mut self.0[offset..offset + size] {
+                assert_ne!(*b, allocated, "double allocation or free");
+                *b = allocated;
+            }
+        }
+
+        /// Returns the size of the largest free run of bytes
+        fn largest_free(&self) -> usize {
+            self.0
+                .split(|allocated| *allocated)
+                .map(|run| run.len())
+                .max()
+                .unwrap_or(0)
+        }
+
+        /// Returns, if there is a free run of `size` bytes aligned to `align`
+        fn fits(&self, size: usize, align: usize) -> bool {
+            (0..self.0.len())
+                .step_by(align)
+                .take_while(|o| o + size <= self.0.len())
+                .any(|o| self.0[o..o + size].iter().all(|a| !*a))
+        }
+    }
+
+    #[test]
+    fn matches_reference() {
+        const CAP: usize = 4 << 10;
+
+        let mut rng = Rng::new(7);
+        let mut rand = move |n: usize| rng.below(n);
+        for fit in [Fit::First, Fit::Best, Fit::Next] {
+            let mut fl = FreeList::with_fit(CAP, fit);
+            let mut reference = Reference(vec![false; CAP]);
+            let mut live = Vec::new();
+            for _ in 0..5_000 {
+                match rand(8) {
+                    0..=3 => {
+                        let size = rand(200) + 1;
+                        let mut padded = size;
+                        FreeList::pad_size(&mut padded);
+                        let (res, align) = if rand(4) == 0 {
+                            (fl.allocate_aligned(size, 64), 64)
+                        } else {
+                            (fl.allocate(size), WORD)
+                        };
+                        match res {
+                            AllocationResult::Allocated(offset) => {
+                                assert_eq!(offset % align, 0);
+                                reference.mark(offset, size, true);
+                                live.push((offset, size));
+                            }
+                            AllocationResult::NotFound(max) => {
+                                assert!(!reference.fits(padded, align));
+                                if align == WORD {
+                                    assert_eq!(max, reference.largest_free());
+                                }
+                            }
+                        }
+                    }
+                    4..=6 if !live.is_empty() => {
+                        let (offset, size) = live.swap_remove(rand(live.len()));
+                        fl.free(offset, size).unwrap();
+                        reference.mark(offset, size, false);
+                    }
+                    _ => {
+                        let n = rand(live.len() + 1);
+                        let freed: Vec<_> = (0..n)
+                            .map(|_| live.swap_remove(rand(live.len())))
+                            .collect();
+                        fl.free_many(&freed).unwrap();
+                        for (offset, size) in freed {
+                            reference.mark(offset, size, false);
+                        }
+                    }
+                }
+
+                fl.check_invariants();
+                assert_eq!(
+                    fl.free_bytes(),
+                    reference.0.iter().filter(|a| !**a).count()
+                );
+                assert_eq!(fl.largest_free(), reference.largest_free());
+            }
+        }
+    }
+
+    #[test]
+    fn fragmentation_stays_bounded() {
+        const CAP: usize = 64 << 10;
+
+        let mut fl = FreeList::new(CAP);
+        let mut live = Vec::new();
+        let mut rng = Rng::new(1);
+        for round in 0..10_000 {
+            let state = rng.next_u32();
+
+            if live.len() < 64 && !state.is_multiple_of(3) {
+                let size = (state as usize % 256) + 1;
+                if let AllocationResult::Allocated(offset) = fl.allocate(size) {
+                    live.push((offset, size));
+                }
+            } else if !live.is_empty() {
+                let (offset, size) =
+                    live.swap_remove(state as usize % live.len());
+                fl.free(offset, size).unwrap();
+            }
+
+            // Free ranges are separated by at least one live allocation
+            assert!(
+                fl.list.len() <= live.len() + 1,
+                "round {}: {} free ranges for {} allocations",
+                round,
+                fl.list.len(),
+                live.len()
+            );
+        }
+
+        for (offset, size) in live {
+            fl.free(offset, size).unwrap();
+        }
+        assert_eq!(fl.ranges(), [(0, CAP)]);
+    }
+}
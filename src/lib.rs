@@ -0,0 +1,15 @@
+// The linked list and free list only use core and alloc, so they can be reused
+// in no_std builds. Everything else requires the std feature.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+// Renamed to not clash with the alloc module
+extern crate alloc as alloc_crate;
+
+#[cfg(feature = "std")]
+pub mod alloc;
+#[cfg(feature = "std")]
+pub mod engine;
+pub mod free_list;
+pub mod linked_list;
+#[cfg(test)]
+mod rng;
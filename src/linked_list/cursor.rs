@@ -0,0 +1,689 @@
+use super::{
+    node::{CheckedRef, Location, Node, NodeRef, NullNodeRef},
+    LinkedList,
+};
+use alloc_crate::vec::Vec;
+use core::cmp::Ordering;
+
+/// Enables safe linked list iteration and modification
+pub struct CursorMut<'a, T, const N: usize>
+where
+    T: Sized,
+{
+    /// Node the cursor is currently at. Guaranteed to never be null.
+    node: *mut Node<T, N>,
+
+    /// Current cursor position in the Node
+    position: usize,
+
+    /// Index of the current value in the list, if known. Computed on first
+    /// request for cursors created from a NodeRef and tracked afterwards.
+    index: Option<usize>,
+
+    /// The first value of the list was removed and the cursor was moved to
+    /// the new first value. The next call to `next()` stays at it, so the
+    /// value is not skipped.
+    before_start: bool,
+
+    /// Parent list
+    pub(super) list: &'a mut LinkedList<T, N>,
+}
+
+// The cursor only points into the list it mutably borrows, so it is as
+// thread-safe as &mut LinkedList
+unsafe impl<'a, T, const N: usize> Send for CursorMut<'a, T, N> where
+    T: Sized + Send
+{
+}
+
+// Shared references to the cursor only hand out &T through peek_next() and
+// peek_prev(), so sharing it is as thread-safe as sharing &T
+unsafe impl<'a, T, const N: usize> Sync for CursorMut<'a, T, N> where
+    T: Sized + Sync
+{
+}
+
+impl<'a, T, const N: usize> CursorMut<'a, T, N>
+where
+    T: Sized + 'static,
+{
+    /// Create a cursor over the passed list, setting the cursor position to the
+    /// passed node.
+    /// Node must not be null.
+    /// Position is ignored, if node is empty.
+    /// `index` must be the index of the value in the list, if known.
+    #[inline]
+    pub(super) unsafe fn new(
+        list: &'a mut LinkedList<T, N>,
+        node: *mut Node<T, N>,
+        position: usize,
+        index: Option<usize>,
+    ) -> Self {
+        Self {
+            list,
+            position,
+            node,
+            index,
+            before_start: false,
+        }
+    }
+
+    /// Shorthand for accessing the current Node as a reference
+    #[inline]
+    fn node(&mut self) -> &'a mut Node<T, N> {
+        unsafe { &mut *self.node }
+    }
+
+    /// Tries to advances cursor to the next position.
+    /// Returns false, if there is no next position and the cursor did not
+    /// advance.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> bool {
+        if self.before_start {
+            self.before_start = false;
+            return true;
+        }
+        if self.position + 1 < self.node().len() {
+            self.position += 1;
+        } else if !self.node().next().is_null() {
+            // Next node can not have zero length
+            self.node = self.node().next();
+            self.position = 0;
+        } else {
+            return false;
+        }
+        self.index = self.index.map(|i| i + 1);
+        true
+    }
+
+    /// Tries to move cursor to the previous position.
+    /// Returns false, if there is no previous position and the cursor did not
+    /// move.
+    #[inline]
+    pub fn previous(&mut self) -> bool {
+        self.before_start = false;
+        if self.position != 0 {
+            self.position -= 1;
+        } else {
+            let prev = self.node().previous();
+            if prev.is_null() {
+                return false;
+            }
+            self.node = prev;
+            self.position = self.node().len() - 1;
+        }
+        self.index = self.index.map(|i| i - 1);
+        true
+    }
+
+    /// Returns the index of the current value in the list or None, if the list
+    /// is empty.
+    ///
+    /// The index is tracked as the cursor moves. Only the first call on a
+    /// cursor created from a NodeRef counts the values before it.
+    pub fn position(&mut self) -> Option<usize> {
+        if self.list.length == 0 {
+            return None;
+        }
+        if self.index.is_none() {
+            let mut index = self.position;
+            let mut node = self.list.head;
+            while node != self.node {
+                unsafe {
+                    index += (*node).len();
+                    node = (*node).next();
+                }
+            }
+            self.index = Some(index);
+        }
+        self.index
+    }
+
+    /// Navigate to the value at index `n` in the list.
+    ///
+    /// Walks whole nodes from the closest end of the list or the current
+    /// position, if known. Returns false, if `n` is out of bounds and the
+    /// cursor did not move.
+    pub fn seek(&mut self, n: usize) -> bool {
+        let len = self.list.length;
+        if n >= len {
+            return false;
+        }
+        self.before_start = false;
+
+        let from_current = self.index.map(|i| i.abs_diff(n));
+        if from_current.is_some_and(|d| d < n.min(len - 1 - n)) {
+            let i = self.index.unwrap();
+            for _ in 0..i.abs_diff(n) {
+                if n > i {
+                    self.next();
+                } else {
+                    self.previous();
+                }
+            }
+            return true;
+        }
+
+        unsafe {
+            if n < len / 2 {
+                let mut node = self.list.head;
+                let mut position = n;
+                while position >= (*node).len() {
+                    position -= (*node).len();
+                    node = (*node).next();
+                }
+                self.node = node;
+                self.position = position;
+            } else {
+                // Count from the end of the list
+                let mut node = self.list.tail;
+                let mut from_end = len - 1 - n;
+                while from_end >= (*node).len() {
+                    from_end -= (*node).len();
+                    node = (*node).previous();
+                }
+                self.node = node;
+                self.position = (*node).len() - 1 - from_end;
+            }
+        }
+        self.index = Some(n);
+        true
+    }
+
+    /// Navigate to the start of the linked list
+    #[inline]
+    pub fn seek_start(&mut self) {
+        self.before_start = false;
+        self.node = self.list.head;
+        self.position = 0;
+        self.index = Some(0);
+    }
+
+    /// Navigate to the end of the linked list
+    #[inline]
+    pub fn seek_end(&mut self) {
+        self.before_start = false;
+        // `self.node().len() -1` can be negative only in case of an empty list
+        if self.list.length == 0 {
+            self.seek_start();
+        } else {
+            // In all other cases a node can not be empty
+            self.node = self.list.tail;
+            self.position = self.node().len() - 1;
+            self.index = Some(self.list.length - 1);
+        }
+    }
+
+    /// Returns a reference to the current position's value.
+    /// Only returns None, if the current linked list is empty.
+    #[inline]
+    pub fn value(&mut self) -> Option<&'a mut T> {
+        if self.node().len() == 0 {
+            None
+        } else {
+            Some(self.node().value(self.position))
+        }
+    }
+
+    /// Returns a reference to the value after the current position without
+    /// moving the cursor, if any
+    #[inline]
+    pub fn peek_next(&self) -> Option<&T> {
+        let node = unsafe { &*self.node };
+        if self.position + 1 < node.len() {
+            Some(node.value_ref(self.position + 1))
+        } else {
+            // Next node can not have zero length
+            unsafe { node.next().as_ref() }.map(|next| next.value_ref(0))
+        }
+    }
+
+    /// Returns a reference to the value before the current position without
+    /// moving the cursor, if any
+    #[inline]
+    pub fn peek_prev(&self) -> Option<&T> {
+        let node = unsafe { &*self.node };
+        if self.position != 0 {
+            Some(node.value_ref(self.position - 1))
+        } else {
+            unsafe { node.previous().as_ref() }
+                .map(|prev| prev.value_ref(prev.len() - 1))
+        }
+    }
+
+    /// Returns a reference to the current value, that can be stored and used to
+    /// construct cursors.
+    #[inline]
+    pub fn reference(&mut self) -> Option<NodeRef<T, N>> {
+        if self.node().len() == 0 {
+            None
+        } else {
+            Some(Node::reference(
+                self.node,
+                self.position,
+                &mut self.list.retired,
+            ))
+        }
+    }
+
+    /// Returns a reference to the current value, that can be stored and
+    /// safely used to construct cursors
+    #[inline]
+    pub fn checked_reference(&mut self) -> Option<CheckedRef<T, N>> {
+        let id = self.list.id;
+        self.reference().map(|r| CheckedRef::new(r, id))
+    }
+
+    /// Insert value before the current cursor position
+    pub fn insert_before(&mut self, val: T) {
+        self.before_start = false;
+        self.list.length += 1;
+
+        let len = self.node().len();
+        if len == 0 {
+            self.node().append(val);
+            self.position = 0;
+            self.index = Some(0);
+        } else if self.position == 0 && len == N {
+            // Append to previous node
+            let new = self.node().append_to_previous(val);
+            if self.list.head == self.node {
+                self.list.head = new;
+            }
+            self.index = self.index.map(|i| i + 1);
+        } else {
+            // Insert into current node and possibly split it
+            let new = self.node().insert(self.position, val);
+            if !new.is_null() && self.list.tail == self.node {
+                self.list.tail = new;
+            }
+
+            // Advance back to next value to maintain API consistency
+            self.next();
+        }
+    }
+
+    /// Insert value before the current cursor position
+    pub fn insert_after(&mut self, val: T) {
+        self.before_start = false;
+        self.list.length += 1;
+
+        let len = self.node().len();
+        if len == 0 {
+            // Append to start of node
+            self.node().append(val);
+            self.position = 0;
+            self.index = Some(0);
+        } else if len == N && self.position == N - 1 {
+            // Prepend to next node
+            let new = self.node().prepend_to_next(val);
+            if !new.is_null() && self.node == self.list.tail {
+                self.list.tail = new;
+            }
+        } else {
+            // Inserts into existing node, possibly splitting it
+            let new = self.node().insert(self.position + 1, val);
+            if !new.is_null() && self.list.tail == self.node {
+                self.list.tail = new;
+            }
+        }
+    }
+
+    /// Insert value into a sorted list in order according to `compare` and
+    /// set the cursor to it.
+    ///
+    /// The position is searched for starting from the current one, so passing
+    /// a cursor close to the target position saves walking the list. Values
+    /// equal to `val` are kept before it.
+    pub fn insert_sorted<F>(&mut self, val: T, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.before_start = false;
+
+        // Move back while the current value is greater
+        loop {
+            match self.value() {
+                None => {
+                    self.insert_after(val);
+                    return;
+                }
+                Some(cur) if compare(cur, &val) == Ordering::Greater => {
+                    if !self.previous() {
+                        self.insert_before(val);
+                        self.previous();
+                        return;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        // Move forward while the next value is not greater
+        while self
+            .peek_next()
+            .is_some_and(|next| compare(next, &val) != Ordering::Greater)
+        {
+            self.next();
+        }
+        self.insert_after(val);
+        self.next();
+    }
+
+    /// Move the current value to the front of the list and set the cursor to
+    /// it.
+    ///
+    /// The value's reference stays valid and resolves to its new position.
+    /// Does nothing, if the list is empty.
+    pub fn move_to_front(&mut self) {
+        self.before_start = false;
+        if self.list.head == self.node && self.position == 0 {
+            return;
+        }
+        let (val, loc) = match self.take() {
+            Some(v) => v,
+            None => return,
+        };
+        self.seek_start();
+        self.insert_before(val);
+        self.seek_start();
+        Node::attach(self.node, self.position, loc);
+    }
+
+    /// Move the current value to the back of the list and set the cursor to
+    /// it.
+    ///
+    /// The value's reference stays valid and resolves to its new position.
+    /// Does nothing, if the list is empty.
+    pub fn move_to_back(&mut self) {
+        self.before_start = false;
+        if self.list.tail == self.node && self.position + 1 >= self.node().len()
+        {
+            return;
+        }
+        let (val, loc) = match self.take() {
+            Some(v) => v,
+            None => return,
+        };
+        self.seek_end();
+        self.insert_after(val);
+        self.seek_end();
+        Node::attach(self.node, self.position, loc);
+    }
+
+    /// Swap the current value with the next one and advance the cursor to
+    /// keep it at the current value.
+    ///
+    /// References to both values follow them to their new positions.
+    /// Returns false, if there is no next value and nothing was swapped.
+    pub fn swap_with_next(&mut self) -> bool {
+        self.before_start = false;
+        let len = self.node().len();
+        let (next, j) = if self.position + 1 < len {
+            (self.node, self.position + 1)
+        } else if !self.node().next().is_null() {
+            (self.node().next(), 0)
+        } else {
+            // Also covers empty lists
+            return false;
+        };
+
+        unsafe { Node::swap(self.node, self.position, next, j) };
+        self.next()
+    }
+
+    /// Split the list after the current position, returning all following
+    /// values as a new list.
+    ///
+    /// Relinks the following nodes and splits the current one, so takes
+    /// O(N) time for moving values within nodes and O(n / N) for counting the
+    /// moved values.
+    ///
+    /// References to the moved values stay valid, but must now be used with
+    /// the returned list. All CheckedRef to this list are invalidated.
+    pub fn split_off(&mut self) -> LinkedList<T, N> {
+        self.before_start = false;
+        let mut split = LinkedList::new();
+        if self.node().len() == 0 {
+            return split;
+        }
+
+        let head = if self.position + 1 < self.node().len() {
+            let new = self.node().split_off(self.position + 1);
+            unsafe { (*new).set_next(self.node().next()) };
+            if self.list.tail == self.node {
+                self.list.tail = new;
+            }
+            new
+        } else {
+            self.node().next()
+        };
+        if head.is_null() {
+            return split;
+        }
+        self.node().set_next(core::ptr::null_mut());
+        unsafe { (*head).store_previous(core::ptr::null_mut()) };
+
+        let mut length = 0;
+        let mut node = head;
+        while !node.is_null() {
+            unsafe {
+                length += (*node).len();
+                node = (*node).next();
+            }
+        }
+
+        // Replace the empty node of the new list
+        unsafe { Node::free(split.head) };
+        split.head = head;
+        split.tail = self.list.tail;
+        split.length = length;
+        self.list.tail = self.node;
+        self.list.length -= length;
+
+        // Locations of the moved values are now owned by `split`, so
+        // CheckedRef must not access them through this list
+        self.list.id = super::next_id();
+        split
+    }
+
+    /// Link all values of `other` after the current position.
+    ///
+    /// Relinks the nodes of `other` in O(1), but the current node is split, if
+    /// the cursor is not at its last value, which takes O(N).
+    ///
+    /// References to values of `other` stay valid, but must now be used with
+    /// this list. The cursor position does not change.
+    pub fn splice_after(&mut self, mut other: LinkedList<T, N>) {
+        self.before_start = false;
+        // Keep the retired locations of `other`, so its references to removed
+        // values can still be checked
+        self.list.retired.append(&mut other.retired);
+
+        let (head, tail, length) = match other.into_nodes() {
+            Some(nodes) => nodes,
+            None => return,
+        };
+
+        if self.node().len() == 0 {
+            // Replace the only empty node
+            unsafe { Node::free(self.node) };
+            self.node = head;
+            self.position = 0;
+            self.index = Some(0);
+            self.list.head = head;
+            self.list.tail = tail;
+            self.list.length = length;
+            return;
+        }
+
+        let next = if self.position + 1 < self.node().len() {
+            let new = self.node().split_off(self.position + 1);
+            unsafe { (*new).set_next(self.node().next()) };
+            if self.list.tail == self.node {
+                self.list.tail = new;
+            }
+            new
+        } else {
+            self.node().next()
+        };
+
+        self.node().set_next(head);
+        unsafe { (*tail).set_next(next) };
+        if next.is_null() {
+            self.list.tail = tail;
+        }
+        self.list.length += length;
+    }
+
+    /// Remove current value, if any.
+    ///
+    /// Returns the removed value and a reference to the removed value, if one
+    /// was ever taken for it.
+    ///
+    /// Sets the cursor to the previous value. If none, sets it to the new first
+    /// value, but the following call to `next()` will not advance the cursor.
+    /// Either way calling `next()` after `remove()` moves the cursor to the
+    /// value following the removed one, so loops removing values while
+    /// advancing the cursor visit each value exactly once.
+    ///
+    /// References to the removed value are invalidated and can be checked with
+    /// `NodeRef::is_valid()` or `CheckedRef::is_valid()`.
+    pub fn remove(&mut self) -> Option<(T, Option<NullNodeRef<T, N>>)> {
+        let (val, loc) = self.take()?;
+        let loc = if loc.is_null() {
+            None
+        } else {
+            // Keep the location for reuse, so references to the removed value
+            // never point to freed memory
+            let r = unsafe {
+                let r = NodeRef::new(loc);
+                Location::retire(loc);
+                r
+            };
+            self.list.retired.push(loc);
+            Some(r.into())
+        };
+        Some((val, loc))
+    }
+
+    /// Remove up to `n` values starting with the current one and return them.
+    ///
+    /// The run of values is unlinked at once. Sets the cursor as `remove()`
+    /// does.
+    pub fn remove_n(&mut self, n: usize) -> Vec<T> {
+        self.remove_run(|_, i| i < n)
+    }
+
+    /// Remove values starting with the current one until `pred` returns true
+    /// for a value or the end of the list is reached and return them. The value
+    /// `pred` returned true for is not removed.
+    ///
+    /// The run of values is unlinked at once. Sets the cursor as `remove()`
+    /// does.
+    pub fn remove_until<F>(&mut self, mut pred: F) -> Vec<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.remove_run(|v, _| !pred(v))
+    }
+
+    /// Remove the values starting with the current one, while `take` returns
+    /// true for the value and its index in the run
+    fn remove_run<F>(&mut self, mut take: F) -> Vec<T>
+    where
+        F: FnMut(&T, usize) -> bool,
+    {
+        // Count the values to remove without moving the cursor
+        let mut count = 0;
+        let (mut node, mut position) = (self.node, self.position);
+        unsafe {
+            while position < (*node).len()
+                && take((*node).value_ref(position), count)
+            {
+                count += 1;
+                position += 1;
+                if position == (*node).len() && !(*node).next().is_null() {
+                    node = (*node).next();
+                    position = 0;
+                }
+            }
+        }
+        if count == 0 {
+            return Vec::new();
+        }
+
+        // Split the run and all values after it off the list
+        let id = self.list.id;
+        let from_start = !self.previous();
+        let mut run = if from_start {
+            let run = core::mem::take(self.list);
+            self.seek_start();
+            run
+        } else {
+            self.split_off()
+        };
+        let mut c = run.cursor_mut();
+        c.seek(count - 1);
+        let after = c.split_off();
+        self.splice_after(after);
+        self.before_start = from_start && self.list.length != 0;
+
+        // Keep the locations of the removed values, so references to them can
+        // still be checked
+        let mut vals = Vec::with_capacity(count);
+        self.list.retired.append(&mut run.retired);
+        if let Some((head, _, _)) = run.into_nodes() {
+            unsafe {
+                Node::drain_list(head, &mut vals, &mut self.list.retired)
+            };
+        }
+
+        // Only removed values were moved out of the list
+        self.list.id = id;
+        vals
+    }
+
+    /// Remove current value together with the location of its reference, if
+    /// any, and move the cursor as `remove()` does.
+    ///
+    /// The location must be attached to a new position with `Node::attach()`.
+    fn take(&mut self) -> Option<(T, *mut Location<T, N>)> {
+        if self.list.is_empty() {
+            return None;
+        }
+
+        // Navigate to the previous or next sibling ahead of time.
+        // Save node pointer, in case node is to be removed.
+        let to_remove = (self.node, self.position);
+        let removing_node = self.list.len() != 1 && self.node().len() == 1;
+        let index = self.index;
+        let moved_back = self.previous();
+        if !moved_back && self.next() && self.node == to_remove.0 {
+            // Following values in the same node will be shifted left
+            self.position -= 1;
+        }
+        if !moved_back {
+            // The next value takes over the index of the removed one
+            self.index = index;
+            self.before_start = self.list.length != 1;
+        }
+        self.list.length -= 1;
+
+        let re = unsafe { Node::take(to_remove.0, to_remove.1) };
+
+        if removing_node {
+            if self.list.head == to_remove.0 {
+                // Was head, so we moved to the next node
+                self.list.head = self.node;
+            }
+            if self.list.tail == to_remove.0 {
+                // Was tail, so we moved to the previous node
+                self.list.tail = self.node;
+            }
+        }
+
+        Some(re)
+    }
+}
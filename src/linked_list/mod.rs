@@ -0,0 +1,628 @@
+mod cursor;
+mod node;
+#[cfg(feature = "node-pool")]
+mod pool;
+
+mod tests;
+
+use alloc_crate::{boxed::Box, vec::Vec};
+use core::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    iter::{FromIterator, FusedIterator},
+    marker::PhantomData,
+    sync::atomic::{self, AtomicU64},
+};
+use node::{Location, Node};
+
+pub use node::{CheckedRef, NodeRef};
+
+pub use self::cursor::CursorMut;
+
+/// Default number of values stored in a node.
+///
+/// Bigger nodes have more cache-local values but also require more NodeRef
+/// updates on shifting, which produce cache misses. In
+/// `benches/node_capacity.rs` scanning barely improves past 8, while shifting
+/// is fastest at 4 and slows down with bigger nodes.
+pub const DEFAULT_CAPACITY: usize = 8;
+
+/// Doubly-linked unrolled list with cursor iteration and reference storage
+/// support.
+/// Stores type T in N-sized nodes.
+pub struct LinkedList<T, const N: usize = DEFAULT_CAPACITY>
+where
+    T: Sized,
+{
+    /// First node of list. Guaranteed to never be null.
+    head: *mut Node<T, N>,
+
+    /// Last node of the list. Guaranteed to never be null.
+    tail: *mut Node<T, N>,
+
+    /// Cached for cheap lookup
+    length: usize,
+
+    /// Locations of removed values, that still may be pointed to by stale
+    /// references. Reused for new references and freed with the list.
+    retired: Vec<*mut Location<T, N>>,
+
+    /// Unique identifier of the list for validating CheckedRef. Replaced, when
+    /// values are moved out of the list.
+    id: u64,
+}
+
+/// Returns a new unique list identifier
+fn next_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, atomic::Ordering::Relaxed)
+}
+
+// The list exclusively owns its nodes and the locations of their references,
+// so moving it to another thread moves all of them
+unsafe impl<T, const N: usize> Send for LinkedList<T, N> where T: Sized + Send {}
+
+// No method taking &self modifies nodes or locations, so shared references only
+// ever read values
+unsafe impl<T, const N: usize> Sync for LinkedList<T, N> where T: Sized + Sync {}
+
+impl<T, const N: usize> Drop for LinkedList<T, N>
+where
+    T: Sized,
+{
+    fn drop(&mut self) {
+        if !self.head.is_null() {
+            unsafe { Node::free_list(self.head) };
+        }
+        for loc in self.retired.drain(..) {
+            drop(unsafe { Box::from_raw(loc) });
+        }
+    }
+}
+
+impl<T, const N: usize> LinkedList<T, N>
+where
+    T: Sized + 'static,
+{
+    /// Create new empty list
+    #[inline]
+    pub fn new() -> Self {
+        // List must never have zero nodes
+        let n = Node::empty();
+        Self {
+            head: n,
+            tail: n,
+            length: 0,
+            retired: Vec::new(),
+            id: next_id(),
+        }
+    }
+
+    /// Take ownership of the nodes of the list and its length.
+    ///
+    /// Returns None and drops the list, if it is empty. Retired locations are
+    /// freed.
+    fn into_nodes(
+        mut self,
+    ) -> Option<(*mut Node<T, N>, *mut Node<T, N>, usize)> {
+        if self.length == 0 {
+            return None;
+        }
+        for loc in core::mem::take(&mut self.retired) {
+            drop(unsafe { Box::from_raw(loc) });
+        }
+        let this = core::mem::ManuallyDrop::new(self);
+        Some((this.head, this.tail, this.length))
+    }
+
+    /// Creates a cursor for iterating and manipulating the list
+    #[inline]
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T, N> {
+        unsafe { CursorMut::new(self, self.head, 0, Some(0)) }
+    }
+
+    /// Returns the length of the list
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns, if the list contains no values
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Clone and append all values of `vals` to the end of the list
+    #[inline]
+    pub fn extend_from_slice(&mut self, vals: &[T])
+    where
+        T: Clone,
+    {
+        self.extend(vals.iter().cloned())
+    }
+
+    /// Move all values of `other` to the end of the list in O(1), leaving
+    /// `other` empty.
+    ///
+    /// References to the moved values stay valid, but must now be used with
+    /// this list.
+    pub fn append(&mut self, other: &mut Self) {
+        let mut c = self.cursor_mut();
+        c.seek_end();
+        c.splice_after(core::mem::take(other));
+    }
+
+    /// Insert value into a sorted list in order according to `compare`.
+    /// Values equal to `val` are kept before it.
+    ///
+    /// Searches from the start of the list. Use `CursorMut::insert_sorted()`
+    /// to search from a known close position instead.
+    #[inline]
+    pub fn insert_sorted<F>(&mut self, val: T, compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.cursor_mut().insert_sorted(val, compare)
+    }
+
+    /// Swap the positions of the values referenced by `a` and `b`.
+    ///
+    /// Both references follow their values to their new positions.
+    ///
+    /// # Safety
+    ///
+    /// Both references must be valid references to values of this list, as
+    /// required by `NodeRef::cursor_mut()`.
+    pub unsafe fn swap_values(&mut self, a: &NodeRef<T, N>, b: &NodeRef<T, N>) {
+        let (a, i) = a.resolve(self);
+        let (b, j) = b.resolve(self);
+        Node::swap(a, i, b, j)
+    }
+
+    /// Stable sort the list by `compare`.
+    ///
+    /// References to values stay valid and keep pointing to the same values
+    /// at their new positions.
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        unsafe { Node::sort_list(self.head, compare) }
+    }
+
+    /// Stable sort the list by the key extracted with `f`.
+    ///
+    /// References to values stay valid and keep pointing to the same values
+    /// at their new positions.
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Stable sort the list.
+    ///
+    /// References to values stay valid and keep pointing to the same values
+    /// at their new positions.
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(T::cmp)
+    }
+
+    /// Return an iterator over the list, that can be iterated from both ends
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            ends: Ends::new(self),
+            pd: PhantomData,
+        }
+    }
+
+    /// Remove the value referenced by `r` and return it.
+    ///
+    /// Returns None, if the value is not in the list.
+    pub fn remove(&mut self, r: CheckedRef<T, N>) -> Option<T> {
+        r.cursor_mut(self)?.remove().map(|(val, _)| val)
+    }
+
+    /// Clone the values of the list into a Vec
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut v = Vec::with_capacity(self.length);
+        v.extend(self.iter().cloned());
+        v
+    }
+
+    /// Copy values from the start of the list into `dst` and return the
+    /// number of values copied, which is the lesser of both lengths
+    pub fn copy_into(&self, dst: &mut [T]) -> usize
+    where
+        T: Copy,
+    {
+        let mut n = 0;
+        for (dst, src) in dst.iter_mut().zip(self.iter()) {
+            *dst = *src;
+            n += 1;
+        }
+        n
+    }
+
+    /// Return a mutable iterator over the list, that can be iterated from
+    /// both ends
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N> {
+        IterMut {
+            ends: Ends::new(self),
+            pd: PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for LinkedList<T, N>
+where
+    T: Sized + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut ll = LinkedList::new();
+        ll.extend(iter);
+        ll
+    }
+}
+
+impl<T, const N: usize> Extend<T> for LinkedList<T, N>
+where
+    T: Sized + 'static,
+{
+    /// Append values to the end of the list, keeping a cursor at the tail
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut c = self.cursor_mut();
+        c.seek_end();
+        for val in iter.into_iter() {
+            c.insert_after(val);
+            c.next();
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Extend<&'a T> for LinkedList<T, N>
+where
+    T: Sized + Copy + 'static,
+{
+    #[inline]
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+
+/// Positions of the next values at both ends of an iteration over a list
+struct Ends<T, const N: usize>
+where
+    T: Sized + 'static,
+{
+    /// Node and position of the next value from the front
+    front: (*mut Node<T, N>, usize),
+
+    /// Node and position of the next value from the back
+    back: (*mut Node<T, N>, usize),
+
+    /// Number of values left to iterate
+    remaining: usize,
+}
+
+impl<T, const N: usize> Ends<T, N>
+where
+    T: Sized + 'static,
+{
+    fn new(list: &LinkedList<T, N>) -> Self {
+        Self {
+            front: (list.head, 0),
+            back: (list.tail, unsafe { (*list.tail).len() }.saturating_sub(1)),
+            remaining: list.length,
+        }
+    }
+
+    /// Advance the front end and return the node and position of the value it
+    /// was at
+    #[inline]
+    fn next_front(&mut self) -> Option<(*mut Node<T, N>, usize)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        // Nodes can not be modified, while the list is borrowed by the
+        // iterator. The ends never pass each other, so each value is returned
+        // once.
+        let (node, position) = self.front;
+        self.front = unsafe {
+            if position + 1 == (*node).len() {
+                ((*node).next(), 0)
+            } else {
+                (node, position + 1)
+            }
+        };
+        Some((node, position))
+    }
+
+    /// Advance the back end and return the node and position of the value it
+    /// was at
+    #[inline]
+    fn next_back(&mut self) -> Option<(*mut Node<T, N>, usize)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let (node, position) = self.back;
+        self.back = unsafe {
+            if position == 0 {
+                let prev = (*node).previous();
+                if prev.is_null() {
+                    (prev, 0)
+                } else {
+                    (prev, (*prev).len() - 1)
+                }
+            } else {
+                (node, position - 1)
+            }
+        };
+        Some((node, position))
+    }
+}
+
+/// Iterator over shared references to the values of a list
+pub struct Iter<'a, T, const N: usize = DEFAULT_CAPACITY>
+where
+    T: Sized + 'static,
+{
+    ends: Ends<T, N>,
+    pd: PhantomData<&'a T>,
+}
+
+// Only yields shared references to values, like &LinkedList
+unsafe impl<'a, T, const N: usize> Send for Iter<'a, T, N> where
+    T: Sized + Sync + 'static
+{
+}
+
+unsafe impl<'a, T, const N: usize> Sync for Iter<'a, T, N> where
+    T: Sized + Sync + 'static
+{
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N>
+where
+    T: Sized + 'static,
+{
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ends
+            .next_front()
+            .map(|(node, i)| unsafe { (*node).value_ref(i) })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.ends.remaining, Some(self.ends.remaining))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for Iter<'a, T, N>
+where
+    T: Sized + 'static,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.ends
+            .next_back()
+            .map(|(node, i)| unsafe { (*node).value_ref(i) })
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for Iter<'a, T, N> where
+    T: Sized + 'static
+{
+}
+
+impl<'a, T, const N: usize> FusedIterator for Iter<'a, T, N> where
+    T: Sized + 'static
+{
+}
+
+/// Iterator over mutable references to the values of a list
+pub struct IterMut<'a, T, const N: usize = DEFAULT_CAPACITY>
+where
+    T: Sized + 'static,
+{
+    ends: Ends<T, N>,
+    pd: PhantomData<&'a mut T>,
+}
+
+// Yields mutable references to values, like &mut LinkedList
+unsafe impl<'a, T, const N: usize> Send for IterMut<'a, T, N> where
+    T: Sized + Send + 'static
+{
+}
+
+unsafe impl<'a, T, const N: usize> Sync for IterMut<'a, T, N> where
+    T: Sized + Sync + 'static
+{
+}
+
+impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N>
+where
+    T: Sized + 'static,
+{
+    type Item = &'a mut T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ends
+            .next_front()
+            .map(|(node, i)| unsafe { (*node).value(i) })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.ends.remaining, Some(self.ends.remaining))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for IterMut<'a, T, N>
+where
+    T: Sized + 'static,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.ends
+            .next_back()
+            .map(|(node, i)| unsafe { (*node).value(i) })
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for IterMut<'a, T, N> where
+    T: Sized + 'static
+{
+}
+
+impl<'a, T, const N: usize> FusedIterator for IterMut<'a, T, N> where
+    T: Sized + 'static
+{
+}
+
+/// Owning iterator over the values of a list, that frees nodes as it
+/// advances
+pub struct IntoIter<T, const N: usize = DEFAULT_CAPACITY>
+where
+    T: Sized + 'static,
+{
+    list: LinkedList<T, N>,
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N>
+where
+    T: Sized + 'static,
+{
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.cursor_mut().remove().map(|(val, _)| val)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len(), Some(self.list.len()))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N>
+where
+    T: Sized + 'static,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let mut c = self.list.cursor_mut();
+        c.seek_end();
+        c.remove().map(|(val, _)| val)
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> where
+    T: Sized + 'static
+{
+}
+
+impl<T, const N: usize> FusedIterator for IntoIter<T, N> where T: Sized + 'static
+{}
+
+impl<T, const N: usize> IntoIterator for LinkedList<T, N>
+where
+    T: Sized + 'static,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+impl<T, const N: usize> Default for LinkedList<T, N>
+where
+    T: Sized + 'static,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Debug for LinkedList<T, N>
+where
+    T: Sized + Debug + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> Clone for LinkedList<T, N>
+where
+    T: Sized + Clone + 'static,
+{
+    /// Deep copy the values into fresh nodes. References to the values of the
+    /// original list do not resolve to the copy.
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T, const N: usize, const M: usize> PartialEq<LinkedList<T, M>>
+    for LinkedList<T, N>
+where
+    T: Sized + PartialEq + 'static,
+{
+    fn eq(&self, other: &LinkedList<T, M>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T, const N: usize> Eq for LinkedList<T, N> where T: Sized + Eq + 'static {}
+
+impl<T, const N: usize> PartialEq<[T]> for LinkedList<T, N>
+where
+    T: Sized + PartialEq + 'static,
+{
+    fn eq(&self, other: &[T]) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T, const N: usize> PartialEq<&[T]> for LinkedList<T, N>
+where
+    T: Sized + PartialEq + 'static,
+{
+    #[inline]
+    fn eq(&self, other: &&[T]) -> bool {
+        *self == **other
+    }
+}
+
+impl<T, const N: usize, const M: usize> PartialEq<[T; M]> for LinkedList<T, N>
+where
+    T: Sized + PartialEq + 'static,
+{
+    #[inline]
+    fn eq(&self, other: &[T; M]) -> bool {
+        *self == other[..]
+    }
+}
@@ -0,0 +1,927 @@
+use super::{cursor::CursorMut, LinkedList, DEFAULT_CAPACITY};
+use alloc_crate::{boxed::Box, vec::Vec};
+use core::{
+    cmp::Ordering,
+    mem::MaybeUninit,
+    ptr::{copy_nonoverlapping, null_mut},
+};
+
+/// Unrolled linked list node containing up to N values of type T.
+/// N must be non-zero and fit into the pointer bits unused for addressing.
+pub(super) struct Node<T, const N: usize>
+where
+    T: Sized,
+{
+    /// Previous node in the list
+    ///
+    /// Also stores the used length of vals in the highest `LENGTH_BITS` bits
+    /// of the pointer, that are not and will not be used for addressing for
+    /// many years.
+    /// This saves us 8 bytes because of struct padding.
+    /// Most of the time you'd traverse the list from the front, so it's better
+    /// to store it in the `previous` pointer, rather than the `next` one, to
+    /// make getting the address of the next node slightly cheaper.
+    previous: *mut Node<T, N>,
+
+    /// Next node in the list
+    next: *mut Node<T, N>,
+
+    /// Array of values and optional references to these values
+    vals: [MaybeUninit<(T, *mut Location<T, N>)>; N],
+}
+
+impl<T, const N: usize> Node<T, N>
+where
+    T: Sized,
+{
+    /// Number of highest bits in previous pointer used for length storage.
+    ///
+    /// Can use no more than 7 bits as of 2021.
+    const LENGTH_BITS: usize = {
+        let mut bits = 1;
+        while 1 << (bits - 1) < N {
+            bits += 1;
+        }
+
+        bits
+    };
+
+    /// Fails compilation for capacities, that can not be stored. Must be
+    /// evaluated by all node constructors.
+    const VALID_CAPACITY: () = assert!(
+        N != 0 && Self::LENGTH_BITS <= 16,
+        "node capacity must be non-zero and fit into the 16 pointer bits \
+         unused by 48 bit virtual addresses"
+    );
+
+    /// Bits to shift a length value for
+    const LENGTH_SHIFT: usize = {
+        #[cfg(not(target_pointer_width = "64"))]
+        compile_error!("only 64 bit systems are supported");
+
+        64 - Self::LENGTH_BITS
+    };
+
+    /// Mask for resetting stored length
+    const LENGTH_MASK: usize = {
+        let mut i = 0;
+        let mut mask = 0;
+        while i < Self::LENGTH_BITS {
+            mask |= 1 << (63 - i);
+            i += 1;
+        }
+        !mask
+    };
+
+    /// Create new node pointer from value
+    pub fn new(val: T) -> *mut Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_CAPACITY;
+        Self {
+            vals: {
+                let mut arr: [MaybeUninit<(T, *mut Location<T, N>)>; N] =
+                    unsafe { MaybeUninit::uninit().assume_init() };
+                arr[0] = Self::wrap_value(val);
+                arr
+            },
+            next: null_mut(),
+            previous: (1usize << Self::LENGTH_SHIFT) as *mut _,
+        }
+        .into_raw()
+    }
+
+    /// Create a new empty Node
+    pub fn empty() -> *mut Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_CAPACITY;
+        Self {
+            vals: unsafe { MaybeUninit::uninit().assume_init() },
+            next: null_mut(),
+            previous: null_mut(),
+        }
+        .into_raw()
+    }
+
+    /// Convert self to raw pointer
+    #[cfg(not(feature = "node-pool"))]
+    #[inline]
+    fn into_raw(self) -> *mut Self {
+        Box::into_raw(Box::new(self))
+    }
+
+    /// Convert self to raw pointer
+    #[cfg(feature = "node-pool")]
+    #[inline]
+    fn into_raw(self) -> *mut Self {
+        let ptr = super::pool::allocate(core::alloc::Layout::new::<Self>())
+            as *mut Self;
+        unsafe { ptr.write(self) };
+        ptr
+    }
+
+    /// Drop a node allocated with `into_raw()` and free its memory
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node pointer, that is not used afterwards.
+    #[cfg(not(feature = "node-pool"))]
+    #[inline]
+    pub unsafe fn free(node: *mut Self) {
+        drop(Box::from_raw(node));
+    }
+
+    /// Drop a node allocated with `into_raw()` and free its memory
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node pointer, that is not used afterwards.
+    #[cfg(feature = "node-pool")]
+    #[inline]
+    pub unsafe fn free(node: *mut Self) {
+        core::ptr::drop_in_place(node);
+        super::pool::free(node as *mut u8, core::alloc::Layout::new::<Self>());
+    }
+
+    /// Wrap value for inserting into the array
+    #[inline]
+    fn wrap_value(val: T) -> MaybeUninit<(T, *mut Location<T, N>)> {
+        MaybeUninit::new((val, null_mut()))
+    }
+
+    /// Store the length of the array
+    #[inline]
+    fn set_length(&mut self, length: usize) {
+        self.previous = (((self.previous as usize) & Self::LENGTH_MASK)
+            | (length << Self::LENGTH_SHIFT)) as *mut _;
+    }
+
+    /// Return pointer to the previous node, if any
+    #[inline]
+    pub fn previous(&self) -> *mut Self {
+        // Sign extend first to make the pointer canonical.
+        // Note: Technically this is implementation defined. We may want a more
+        // standard-compliant way to sign-extend the value.
+        (((self.previous as usize) << Self::LENGTH_BITS) >> Self::LENGTH_BITS)
+            as *mut _
+    }
+
+    /// Set the previous node pointer and set the next node pointer of the
+    /// previous node, if any
+    #[inline]
+    fn set_previous(&mut self, previous: *mut Self) {
+        debug_assert!(
+            (previous as usize & Self::LENGTH_MASK) == previous as usize
+        );
+
+        self.store_previous(previous);
+        if !previous.is_null() {
+            unsafe {
+                (*previous).next = self as *mut _;
+            }
+        }
+    }
+
+    /// Encodes the previous node pointer, without setting the next pointer on
+    /// the previous node
+    #[inline]
+    pub fn store_previous(&mut self, previous: *mut Self) {
+        let len = self.len();
+        self.previous =
+            (previous as usize | (len << Self::LENGTH_SHIFT)) as *mut _;
+    }
+
+    /// Return pointer to the next node, if any
+    #[inline]
+    pub fn next(&self) -> *mut Self {
+        self.next
+    }
+
+    /// Set the next node pointer and set the previous node pointer of the next
+    /// node, if any
+    #[inline]
+    pub fn set_next(&mut self, next: *mut Self) {
+        self.next = next;
+        if !next.is_null() {
+            unsafe {
+                (*next).store_previous(self as *mut _);
+            }
+        }
+    }
+
+    /// Return the occupied position count in the node
+    #[inline]
+    pub fn len(&self) -> usize {
+        (self.previous as usize) >> Self::LENGTH_SHIFT
+    }
+
+    /// Move the values of the node and all the nodes after it into `vals`,
+    /// retire the locations of their references into `retired` and free the
+    /// nodes
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node pointer and neither it nor any following
+    /// node may be used afterwards.
+    pub unsafe fn drain_list(
+        mut node: *mut Self,
+        vals: &mut Vec<T>,
+        retired: &mut Vec<*mut Location<T, N>>,
+    ) {
+        while !node.is_null() {
+            let len = (*node).len();
+            for i in 0..len {
+                let (val, loc) = (*node).vals[i].as_ptr().read();
+                vals.push(val);
+                if !loc.is_null() {
+                    Location::retire(loc);
+                    retired.push(loc);
+                }
+            }
+
+            // Values already moved out
+            (*node).set_length(0);
+            let next = (*node).next;
+            Self::free(node);
+            node = next;
+        }
+    }
+
+    /// Free the node and all the nodes after it in the list
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node pointer and neither it nor any following
+    /// node may be used afterwards.
+    pub unsafe fn free_list(mut node: *mut Self) {
+        while !node.is_null() {
+            let next = (*node).next;
+            Self::free(node);
+            node = next;
+        }
+    }
+
+    /// Returns a reference to the value-reference pair
+    ///
+    /// # Panics
+    ///
+    /// Panics, if index is out of bounds.
+    #[inline]
+    fn get(&mut self, i: usize) -> &'_ mut (T, *mut Location<T, N>) {
+        let len = self.len();
+        assert!(i < len, "index out of bounds");
+
+        unsafe { &mut (*self.vals[i].as_mut_ptr()) }
+    }
+
+    /// Returns a shared reference to the value at position `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if index is out of bounds.
+    #[inline]
+    pub fn value_ref(&self, i: usize) -> &T {
+        assert!(i < self.len(), "index out of bounds");
+
+        unsafe { &(*self.vals[i].as_ptr()).0 }
+    }
+
+    /// Returns a reference to the value at position `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if index is out of bounds.
+    #[inline]
+    pub fn value<'a>(&mut self, i: usize) -> &'a mut T {
+        unsafe { core::mem::transmute(&mut self.get(i).0) }
+    }
+
+    /// Returns a reference to the node's value at position `i`.
+    /// `node must not be `null`.
+    ///
+    /// A location is reused from `retired`, if any, when the value has none
+    /// yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if index is out of bounds or `node` is `null`.
+    #[inline]
+    pub fn reference(
+        node: *mut Self,
+        i: usize,
+        retired: &mut Vec<*mut Location<T, N>>,
+    ) -> NodeRef<T, N> {
+        assert!(!node.is_null());
+        let t = unsafe { (*node).get(i) };
+
+        if t.1.is_null() {
+            t.1 = match retired.pop() {
+                Some(loc) => {
+                    unsafe {
+                        (*loc).node = node;
+                        (*loc).position = i;
+                    }
+                    loc
+                }
+                None => Box::into_raw(
+                    Location {
+                        node,
+                        position: i,
+                        generation: 0,
+                    }
+                    .into(),
+                ),
+            };
+        }
+        unsafe { NodeRef::new(t.1) }
+    }
+
+    /// Swap the values at position `i` of node `a` and position `j` of node
+    /// `b`. The locations of their references are updated to follow the
+    /// values.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if either position is out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// `a` and `b` must be valid node pointers.
+    pub unsafe fn swap(a: *mut Self, i: usize, b: *mut Self, j: usize) {
+        assert!(i < (*a).len() && j < (*b).len(), "index out of bounds");
+        if a == b && i == j {
+            return;
+        }
+
+        core::ptr::swap((*a).vals[i].as_mut_ptr(), (*b).vals[j].as_mut_ptr());
+        for (node, i) in [(a, i), (b, j)] {
+            let loc = (*(*node).vals[i].as_ptr()).1;
+            if !loc.is_null() {
+                (*loc).node = node;
+                (*loc).position = i;
+            }
+        }
+    }
+
+    /// Attach a location taken with `take()` to the value at position `i` of
+    /// `node`, so references to it resolve to this value. Does nothing for
+    /// null locations.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if index is out of bounds or the value already has a location.
+    pub fn attach(node: *mut Self, i: usize, loc: *mut Location<T, N>) {
+        if loc.is_null() {
+            return;
+        }
+        let t = unsafe { (*node).get(i) };
+        assert!(t.1.is_null(), "value already has a location");
+        t.1 = loc;
+        unsafe {
+            (*loc).node = node;
+            (*loc).position = i;
+        }
+    }
+
+    /// Appends a value to the node.
+    ///
+    /// # Panics
+    ////
+    /// Panics, if node capacity is exceeded.
+    #[inline]
+    pub fn append(&mut self, val: T) {
+        let l = self.len();
+        self.vals[l] = Self::wrap_value(val);
+        self.set_length(l + 1);
+    }
+
+    /// Appends a value to the previous node.
+    ///
+    /// If the previous node is is full or not set, a new node is created and
+    /// returned.
+    pub fn append_to_previous(&mut self, val: T) -> *mut Self {
+        match unsafe { self.previous().as_mut() } {
+            None => {
+                let new = Node::new(val);
+                self.set_previous(new);
+                new
+            }
+            Some(prev) if prev.len() == N => {
+                let new = Node::new(val);
+                prev.set_next(new);
+                self.set_previous(new);
+                new
+            }
+            Some(prev) => {
+                prev.append(val);
+                null_mut()
+            }
+        }
+    }
+
+    /// Push value to the start of the next node.
+    //
+    /// If the next node is is full or not set, a new node is created and
+    /// returned.
+    pub fn prepend_to_next(&mut self, val: T) -> *mut Self {
+        match unsafe { self.next().as_mut() } {
+            None => {
+                let new = Node::new(val);
+                self.set_next(new);
+                new
+            }
+            Some(next) if next.len() == N => {
+                let new = Node::new(val);
+                next.set_previous(new);
+                self.set_next(new);
+                new
+            }
+            Some(next) => {
+                next.insert_non_full(0, val);
+                null_mut()
+            }
+        }
+    }
+
+    /// Insert value into the passed position in the node, shifting all
+    /// following nodes to the right.
+    /// If a new next node is created containing overflown shifted values, it is
+    /// returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if insertion would result in a sparse array.
+    pub fn insert(&mut self, i: usize, val: T) -> *mut Self {
+        let len = self.len();
+
+        if len < N {
+            self.insert_non_full(i, val);
+            return null_mut();
+        }
+
+        // Split the current array
+        let new = self.split_off(i);
+        self.append(val);
+
+        if !self.next.is_null() {
+            unsafe {
+                (*self.next).set_previous(new);
+            }
+        }
+        self.set_next(new);
+
+        new
+    }
+
+    /// Move the values from position `i` on into a new node, that is not
+    /// linked to any other node, and return it
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `i` is out of bounds.
+    pub fn split_off(&mut self, i: usize) -> *mut Self {
+        let len = self.len();
+        assert!(i < len, "node split out of bounds");
+
+        let new = Node::empty();
+        unsafe {
+            let new_len = len - i;
+            copy_nonoverlapping(
+                self.vals[i..].as_ptr(),
+                (*new).vals.as_mut_ptr(),
+                new_len,
+            );
+            (*new).set_length(new_len);
+
+            for (i, (_, loc)) in (*new).iter_mut().enumerate() {
+                if !(*loc).is_null() {
+                    (**loc).node = new;
+                    (**loc).position = i;
+                }
+            }
+        }
+        self.set_length(i);
+
+        new
+    }
+
+    /// Insert value into non-full node at position `i`
+    ///
+    /// # Panics
+    ///
+    /// Panics, if insertion would result in a sparse array.
+    fn insert_non_full(&mut self, i: usize, val: T) {
+        let len = self.len();
+        assert!(i <= len, "value insertion would result in sparse array");
+
+        let mut next = Self::wrap_value(val);
+
+        // Insert as last value
+        if i == len {
+            self.vals[i] = next;
+            self.set_length(len + 1);
+            return;
+        }
+
+        // Shift all following values
+        let mut i = i;
+        loop {
+            core::mem::swap(&mut next, &mut self.vals[i]);
+            unsafe {
+                let loc = (*next.as_mut_ptr()).1;
+                if !loc.is_null() {
+                    (*loc).position += 1
+                }
+            }
+            i += 1;
+            if i == len {
+                self.vals[i] = next;
+                self.set_length(len + 1);
+                return;
+            }
+        }
+    }
+
+    /// Remove value at position `i` together with the location of its
+    /// reference, if any. The location is not updated and must be attached to
+    /// a new position with `attach()` or retired.
+    ///
+    /// Empty nodes with either a previous or next node are removed.
+    /// A node that has neither a previous nor next node will never be removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `i` is out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node pointer and the returned location must not
+    /// be dangling, while references to it exist.
+    pub unsafe fn take(
+        node: *mut Self,
+        mut i: usize,
+    ) -> (T, *mut Location<T, N>) {
+        let this = &mut *node;
+
+        let len = this.len();
+        assert!(i < len, "value removal out of bounds");
+
+        let mut tuple = MaybeUninit::uninit();
+        copy_nonoverlapping(this.vals[i].as_ptr(), tuple.as_mut_ptr(), 1);
+        let (val, loc) = tuple.assume_init();
+
+        if len == 1 {
+            // Ensure only the first node in an empty list can have zero
+            // length
+            let prev = this.previous();
+            if prev.is_null() && this.next().is_null() {
+                this.set_length(0);
+            } else {
+                if !prev.is_null() {
+                    (*prev).set_next(this.next);
+                } else {
+                    // This node was the head
+                    (*this.next()).set_previous(null_mut());
+                }
+
+                // Value already moved out, so prevent it from being dropped
+                // again
+                this.set_length(0);
+                Self::free(node);
+            }
+
+            (val, loc)
+        } else {
+            this.set_length(len - 1);
+
+            // Shift all nodes to the left
+            i += 1;
+            while i < len {
+                let loc = (*this.vals[i].as_mut_ptr()).1;
+                if !loc.is_null() {
+                    (*loc).position = i - 1;
+                }
+
+                copy_nonoverlapping(
+                    this.vals[i].as_ptr(),
+                    this.vals[i - 1].as_mut_ptr(),
+                    1,
+                );
+                i += 1;
+            }
+
+            (val, loc)
+        }
+    }
+
+    /// Stable sort the values of the list starting at `head` by `compare`.
+    ///
+    /// Values are moved between positions, but node lengths do not change and
+    /// the locations of all references are updated to follow their values.
+    /// Only pointers to the values are sorted, so a panicking `compare` leaves
+    /// the list unchanged.
+    ///
+    /// # Safety
+    ///
+    /// `head` must be the first node of a valid list.
+    pub unsafe fn sort_list<F>(head: *mut Self, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        // Positions of all values in list order
+        let mut positions = Vec::new();
+        let mut node = head;
+        while !node.is_null() {
+            let len = (*node).len();
+            for v in (&mut (*node).vals)[..len].iter_mut() {
+                positions.push(v.as_mut_ptr());
+            }
+            node = (*node).next;
+        }
+
+        let mut sorted = positions.clone();
+        sorted.sort_by(|a, b| compare(&(**a).0, &(**b).0));
+        let moved: Vec<_> = sorted.into_iter().map(|p| p.read()).collect();
+        for (dst, pair) in positions.into_iter().zip(moved) {
+            dst.write(pair);
+        }
+
+        let mut node = head;
+        while !node.is_null() {
+            for (i, (_, loc)) in (*node).iter_mut().enumerate() {
+                if !loc.is_null() {
+                    (**loc).node = node;
+                    (**loc).position = i;
+                }
+            }
+            node = (*node).next;
+        }
+    }
+
+    /// Create iterator over the node's value-reference pairs
+    #[inline]
+    fn iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = &'_ mut (T, *mut Location<T, N>)> {
+        let len = self.len();
+        self.vals[..len]
+            .iter_mut()
+            .map(|p| unsafe { &mut *p.as_mut_ptr() })
+    }
+}
+
+impl<T, const N: usize> Drop for Node<T, N>
+where
+    T: Sized,
+{
+    fn drop(&mut self) {
+        let len = self.len();
+        let mut i = 0;
+        unsafe {
+            while i < len {
+                let mut tmp = MaybeUninit::uninit();
+                copy_nonoverlapping(self.vals[i].as_ptr(), tmp.as_mut_ptr(), 1);
+
+                let (_, loc) = tmp.assume_init(); // Drop value
+
+                // Drop location
+                if !loc.is_null() {
+                    drop(Box::from_raw(loc));
+                }
+
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Describes the location of a value in a linked list
+#[derive(Eq, PartialEq, Clone)]
+pub(super) struct Location<T, const N: usize>
+where
+    T: Sized,
+{
+    /// Parent Node
+    node: *mut Node<T, N>,
+
+    /// Position in the node
+    position: usize,
+
+    /// Incremented each time the location is retired with its value removed
+    /// from the list, so references to the removed value can be told apart
+    /// from references to values the location is reused for
+    generation: u64,
+}
+
+impl<T, const N: usize> Location<T, N>
+where
+    T: Sized,
+{
+    /// Invalidate all references to the location, so it can be reused for
+    /// another value
+    ///
+    /// # Safety
+    ///
+    /// `loc` must be a valid location pointer.
+    #[inline]
+    pub unsafe fn retire(loc: *mut Self) {
+        (*loc).generation += 1;
+    }
+}
+
+/// Storable reference to a Node
+#[derive(Eq, Clone)]
+pub struct NodeRef<T, const N: usize = DEFAULT_CAPACITY>
+where
+    T: Sized,
+{
+    /// Pointer to location, that is updates as the value is moved around
+    location: *mut Location<T, N>,
+
+    /// Generation of the location, when the reference was taken
+    generation: u64,
+}
+
+// The location is only dereferenced by `cursor_mut()`, that requires a mutable
+// reference to the list owning it, so it is never accessed concurrently with
+// its updates
+unsafe impl<T, const N: usize> Send for NodeRef<T, N> where T: Sized + Send {}
+
+unsafe impl<T, const N: usize> Sync for NodeRef<T, N> where T: Sized + Sync {}
+
+impl<T, const N: usize> NodeRef<T, N>
+where
+    T: Sized,
+{
+    /// Create a reference to the current generation of a location
+    ///
+    /// # Safety
+    ///
+    /// `location` must be a valid location pointer.
+    #[inline]
+    pub(super) unsafe fn new(location: *mut Location<T, N>) -> Self {
+        Self {
+            location,
+            generation: (*location).generation,
+        }
+    }
+}
+
+impl<T, const N: usize> NodeRef<T, N>
+where
+    T: Sized + 'static,
+{
+    /// Returns, if the referenced value is still in the list and was not
+    /// removed.
+    ///
+    /// # Safety
+    ///
+    /// The list the reference was obtained from or, if the value was moved to
+    /// another list, that list must be passed. Locations of removed values are
+    /// kept by their list until it is dropped, so this is safe to call for
+    /// references to removed values as well.
+    #[inline]
+    pub unsafe fn is_valid(&self, _list: &LinkedList<T, N>) -> bool {
+        (*self.location).generation == self.generation
+    }
+
+    /// Obtain a mutable cursor to the referenced Node.
+    ///
+    /// # Safety
+    /// This method is only safe to call with the same list that the NodeRef was
+    /// obtained from, and only if the Node has not been removed from the list.
+    /// It is the caller's responsibility to remove any NodeRef to a removed
+    /// Node.
+    #[inline]
+    pub unsafe fn cursor_mut<'a>(
+        &self,
+        list: &'a mut LinkedList<T, N>,
+    ) -> CursorMut<'a, T, N> {
+        let (node, position) = self.resolve(list);
+        CursorMut::new(list, node, position, None)
+    }
+
+    /// Returns the node and position of the referenced value.
+    ///
+    /// # Safety
+    ///
+    /// Same as for `cursor_mut()`.
+    #[inline]
+    pub(super) unsafe fn resolve(
+        &self,
+        list: &LinkedList<T, N>,
+    ) -> (*mut Node<T, N>, usize) {
+        debug_assert!(self.is_valid(list), "stale node reference");
+        ((*self.location).node, (*self.location).position)
+    }
+}
+
+impl<T, const N: usize> PartialEq for NodeRef<T, N>
+where
+    T: Sized,
+{
+    #[inline]
+    fn eq(&self, other: &NodeRef<T, N>) -> bool {
+        self.location == other.location && self.generation == other.generation
+    }
+}
+
+impl<T, const N: usize> PartialEq<NullNodeRef<T, N>> for NodeRef<T, N>
+where
+    T: Sized,
+{
+    #[inline]
+    fn eq(&self, other: &NullNodeRef<T, N>) -> bool {
+        self == &other.0
+    }
+}
+
+/// Reference to a value, that checks it is used with the list it was obtained
+/// from and the value has not been removed, before accessing it.
+///
+/// References to values moved to another list with `CursorMut::split_off()`,
+/// `CursorMut::splice_after()` or `LinkedList::append()` are invalidated.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CheckedRef<T, const N: usize = DEFAULT_CAPACITY>
+where
+    T: Sized,
+{
+    /// Underlying unchecked reference
+    r: NodeRef<T, N>,
+
+    /// ID of the list the reference was obtained from
+    list: u64,
+}
+
+impl<T, const N: usize> CheckedRef<T, N>
+where
+    T: Sized + 'static,
+{
+    /// Wrap a reference obtained from the list with the passed ID
+    #[inline]
+    pub(super) fn new(r: NodeRef<T, N>, list: u64) -> Self {
+        Self { r, list }
+    }
+
+    /// Returns, if the referenced value is still in `list`
+    #[inline]
+    pub fn is_valid(&self, list: &LinkedList<T, N>) -> bool {
+        // The list keeps the locations of all its values and removed values
+        // alive, so the location can be accessed, once the list is matched
+        self.list == list.id && unsafe { self.r.is_valid(list) }
+    }
+
+    /// Obtain a mutable cursor to the referenced value, if it is still in
+    /// `list`
+    #[inline]
+    pub fn cursor_mut<'a>(
+        &self,
+        list: &'a mut LinkedList<T, N>,
+    ) -> Option<CursorMut<'a, T, N>> {
+        if self.is_valid(list) {
+            Some(unsafe { self.r.cursor_mut(list) })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, const N: usize> PartialEq<NullNodeRef<T, N>> for CheckedRef<T, N>
+where
+    T: Sized,
+{
+    #[inline]
+    fn eq(&self, other: &NullNodeRef<T, N>) -> bool {
+        self.r == *other
+    }
+}
+
+/// Reference to a removed Node. Can be used for equality comparison with
+/// NodeRef.
+///
+/// Locations are reused for new values only with a new generation, so a
+/// NullNodeRef is never equal to references to values inserted after the
+/// removal.
+#[derive(Clone)]
+pub struct NullNodeRef<T, const N: usize>(NodeRef<T, N>)
+where
+    T: Sized;
+
+impl<T, const N: usize> From<NodeRef<T, N>> for NullNodeRef<T, N>
+where
+    T: Sized,
+{
+    #[inline]
+    fn from(r: NodeRef<T, N>) -> Self {
+        Self(r)
+    }
+}
+
+impl<T, const N: usize> PartialEq<NodeRef<T, N>> for NullNodeRef<T, N>
+where
+    T: Sized,
+{
+    #[inline]
+    fn eq(&self, other: &NodeRef<T, N>) -> bool {
+        &self.0 == other
+    }
+}
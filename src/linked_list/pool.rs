@@ -0,0 +1,310 @@
+//! Pool of node allocations carved from pinned pages of a private page
+//! allocator.
+//!
+//! Lists allocating and freeing nodes at a high rate, like the free lists of
+//! pages being filled and emptied, pack their nodes densely into a few pages
+//! instead of going through the global heap allocator each time.
+//!
+//! Each thread carves nodes from its own current page without locking. Nodes
+//! can be freed on any thread. Every page counts its live nodes and is unpinned
+//! and returned to the private allocator, once all of them are freed and the
+//! page is no longer carved from. Memory of freed nodes is reused, once all
+//! nodes of the thread's current page are freed.
+//!
+//! Pool pages are pinned in resident memory, because page buffers are
+//! otherwise moved on swapping, which would leave the node pointers of a list
+//! dangling.
+//!
+//! Nodes are allocated from the heap instead, if they do not fit a page or
+//! while the thread runs the private allocator, so the lists of the allocator
+//! itself never hold on to pool pages.
+//!
+//! Requires std for the thread-local storage.
+
+use crate::alloc::{Allocator, AllocatorConfig, Page, PinnedPage};
+use std::{
+    alloc::{alloc, dealloc, handle_alloc_error, Layout},
+    cell::{Cell, RefCell},
+    mem::{align_of, size_of, ManuallyDrop},
+    ops::Range,
+    ptr::null_mut,
+    sync::atomic::{fence, AtomicUsize, Ordering},
+};
+
+/// Placed at the start of every pool page
+struct Header {
+    /// Number of live nodes carved from the page plus one, while the page is
+    /// the current page of its thread
+    live: AtomicUsize,
+
+    /// Page containing the header
+    page: ManuallyDrop<PinnedPage>,
+}
+
+/// Stored right before every allocation. Points to the header of the page
+/// the allocation was carved from or is null for heap allocations.
+type Prefix = *mut Header;
+
+/// Page the thread currently carves nodes from
+struct Pool {
+    /// Header of the current page. Null, if none.
+    header: *mut Header,
+
+    /// Uncarved addresses of the current page
+    unused: Range<usize>,
+
+    /// Size of the pages. Zero, until the first page is acquired.
+    page_size: usize,
+}
+
+impl Default for Pool {
+    fn default() -> Self {
+        Self {
+            header: null_mut(),
+            unused: 0..0,
+            page_size: 0,
+        }
+    }
+}
+
+impl Pool {
+    /// Carve an allocation of the prefixed `layout` from the current page or,
+    /// if it is used up, a newly acquired one
+    fn allocate(&mut self, layout: Layout) -> Option<*mut u8> {
+        if let Some(ptr) = self.carve(layout) {
+            return Some(ptr);
+        }
+        if self.page_size != 0
+            && size_of::<Header>() + layout.size() + layout.align()
+                > self.page_size
+        {
+            return None;
+        }
+
+        // No live nodes of the current page are left, so it can be carved
+        // from again from its start
+        if !self.header.is_null()
+            && unsafe { (*self.header).live.load(Ordering::Acquire) } == 1
+        {
+            self.unused.start = self.header as usize + size_of::<Header>();
+            if let Some(ptr) = self.carve(layout) {
+                return Some(ptr);
+            }
+        }
+
+        self.replace(acquire_page()?);
+        self.carve(layout)
+    }
+
+    /// Carve an allocation of the prefixed `layout` from the current page
+    fn carve(&mut self, layout: Layout) -> Option<*mut u8> {
+        if self.header.is_null() {
+            return None;
+        }
+        let start = (self.unused.start + size_of::<Prefix>() + layout.align()
+            - 1)
+            & !(layout.align() - 1);
+        if start + layout.size() > self.unused.end {
+            return None;
+        }
+        self.unused.start = start + layout.size();
+
+        unsafe {
+            (*self.header).live.fetch_add(1, Ordering::Relaxed);
+            (start as *mut Prefix).sub(1).write(self.header);
+        }
+        Some(start as *mut u8)
+    }
+
+    /// Start carving from a newly acquired page instead of the current one
+    fn replace(&mut self, page: PinnedPage) {
+        self.retire();
+
+        let start = page.as_ptr() as usize;
+        self.unused = start + size_of::<Header>()..start + page.len();
+        self.page_size = page.len();
+        self.header = page.as_ptr() as *mut Header;
+        unsafe {
+            self.header.write(Header {
+                live: AtomicUsize::new(1),
+                page: ManuallyDrop::new(page),
+            })
+        };
+    }
+
+    /// Stop carving from the current page
+    fn retire(&mut self) {
+        let header = std::mem::replace(&mut self.header, null_mut());
+        if !header.is_null() {
+            unsafe { unref(header) };
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.retire();
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Private allocator pool pages are acquired from, so the pages do not
+    /// count against the budget of the global allocator or keep it from being
+    /// configured. Only accessed from code running `in_allocator()`.
+    static ref POOL_ALLOCATOR: Allocator = Allocator::new(AllocatorConfig {
+        // Pinned pages are never zswapped, so there is nothing to defragment
+        defrag_pages: 0,
+        ..Default::default()
+    })
+    .unwrap();
+}
+
+thread_local! {
+    static POOL: RefCell<Pool> = RefCell::new(Pool::default());
+
+    /// Set, while the thread runs code of the private allocator
+    static IN_ALLOCATOR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` on the private allocator. Nodes allocated meanwhile, like for the
+/// lists of the allocator, are allocated from the heap.
+fn in_allocator<R>(f: impl FnOnce(&Allocator) -> R) -> R {
+    let prev = IN_ALLOCATOR.with(|a| a.replace(true));
+    let res = f(&POOL_ALLOCATOR);
+    IN_ALLOCATOR.with(|a| a.set(prev));
+    res
+}
+
+/// Acquire a new pinned page for the pool
+fn acquire_page() -> Option<PinnedPage> {
+    in_allocator(|a| a.try_get_page().and_then(Page::into_pinned).ok())
+}
+
+/// Drop a reference to a pool page and return the page to the private
+/// allocator, if it was the last one
+///
+/// # Safety
+///
+/// `header` must point to the header of a page with a reference held by the
+/// caller, that is not used afterwards.
+unsafe fn unref(header: *mut Header) {
+    if (*header).live.fetch_sub(1, Ordering::Release) == 1 {
+        // Synchronize with the frees of all other nodes of the page
+        fence(Ordering::Acquire);
+        let page = ManuallyDrop::take(&mut (*header).page);
+        in_allocator(|_| drop(page));
+    }
+}
+
+/// Layout of an allocation aligned for storing its prefix right before it
+#[inline]
+fn prefixed(layout: Layout) -> Layout {
+    layout.align_to(align_of::<Prefix>()).unwrap()
+}
+
+/// Layout of a heap allocation holding the prefixed `layout` after a prefix
+/// padded to its alignment
+#[inline]
+fn heap_layout(layout: Layout) -> Layout {
+    Layout::from_size_align(layout.size() + layout.align(), layout.align())
+        .unwrap()
+}
+
+/// Allocate memory for `layout` from a pool page or the heap
+pub fn allocate(layout: Layout) -> *mut u8 {
+    let layout = prefixed(layout);
+    if !IN_ALLOCATOR.with(Cell::get) {
+        let ptr = POOL
+            .try_with(|p| p.try_borrow_mut().ok()?.allocate(layout))
+            .ok()
+            .flatten();
+        if let Some(ptr) = ptr {
+            return ptr;
+        }
+    }
+
+    let heap = heap_layout(layout);
+    unsafe {
+        let ptr = alloc(heap);
+        if ptr.is_null() {
+            handle_alloc_error(heap);
+        }
+        let ptr = ptr.add(layout.align());
+        (ptr as *mut Prefix).sub(1).write(null_mut());
+        ptr
+    }
+}
+
+/// Return memory allocated with `allocate()` to its pool page or the heap
+///
+/// # Safety
+///
+/// `ptr` must have been allocated with `allocate()` for the same layout and
+/// must not be used afterwards.
+pub unsafe fn free(ptr: *mut u8, layout: Layout) {
+    let header = (ptr as *mut Prefix).sub(1).read();
+    if header.is_null() {
+        let layout = prefixed(layout);
+        dealloc(ptr.sub(layout.align()), heap_layout(layout));
+    } else {
+        unref(header);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{alloc::configure, linked_list::LinkedList};
+
+    /// Returns the ID of the pool page `ptr` was carved from
+    fn page_id(ptr: usize) -> u64 {
+        let header = unsafe { (ptr as *mut Prefix).sub(1).read() };
+        assert!(!header.is_null());
+        unsafe { (*header).page.id() }
+    }
+
+    #[test]
+    fn release_freed_pages() {
+        let layout = Layout::new::<[u64; 32]>();
+
+        // Carved on a separate thread, so the current page is retired on exit
+        let ptrs = std::thread::spawn(move || {
+            (0..64)
+                .map(|_| allocate(layout) as usize)
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .unwrap();
+        let mut ids: Vec<_> = ptrs.iter().map(|p| page_id(*p)).collect();
+        ids.dedup();
+        assert!(ids.len() > 1);
+
+        let acquired = || {
+            ids.iter()
+                .filter(|id| POOL_ALLOCATOR.lookup(**id).is_some())
+        };
+        assert_eq!(acquired().count(), ids.len());
+        for ptr in ptrs {
+            unsafe { free(ptr as *mut u8, layout) };
+        }
+        assert_eq!(acquired().count(), 0);
+    }
+
+    #[test]
+    fn heap_fallback() {
+        // Larger than a page
+        let layout = Layout::from_size_align(1 << 20, 64).unwrap();
+        let ptr = allocate(layout);
+        assert_eq!(ptr as usize % 64, 0);
+        assert!(unsafe { (ptr as *mut Prefix).sub(1).read() }.is_null());
+        unsafe { free(ptr, layout) };
+    }
+
+    #[test]
+    fn configure_global_allocator() {
+        // Pool pages are not acquired from the global allocator
+        let ll: LinkedList<usize> = (0..1 << 12).collect();
+        configure(Default::default()).unwrap();
+        drop(ll);
+    }
+}
@@ -0,0 +1,1286 @@
+#![cfg(test)]
+
+use super::{node::Node, LinkedList};
+use proptest::{
+    collection::vec,
+    prelude::{any, prop_oneof, Just, Strategy},
+    test_runner::{Config, TestRunner},
+};
+use std::{collections::VecDeque, fmt::Debug, ptr::null_mut};
+
+// Generate tests with various node sizes
+macro_rules! gen_tests {
+    ($name:ident) => {
+        mod $name {
+            // Start with 4, so the first `Run Test` in the IDE is
+            // has multiple values per node
+            gen_tests! {@for_sizes $name {4 1 2 8 15 16 17 32 64 128}}
+        }
+    };
+    (@for_sizes $name:ident { $( $size:literal )* }) => {
+        $(
+            paste::paste! {
+                #[test]
+                fn [<size_ $size>]() {
+                    super::$name::<$size>();
+                }
+            }
+        )*
+    };
+}
+
+gen_tests! {test_linear_inserts}
+fn test_linear_inserts<const N: usize>() {
+    let mut std = VecDeque::new();
+    let mut ll = LinkedList::<usize, N>::new();
+    let mut c = ll.cursor_mut();
+    for i in 0..256 {
+        c.next();
+        c.insert_after(i);
+        validate(c.list);
+
+        std.push_back(i);
+        compare(&std, c.list);
+    }
+}
+
+gen_tests! {test_collect}
+fn test_collect<const N: usize>() {
+    let src = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
+    macro_rules! copy {
+        () => {
+            src.iter().cloned().collect()
+        };
+    }
+    let std: VecDeque<usize> = copy!();
+    let mut ll: LinkedList<usize, N> = copy!();
+    validate(&mut ll);
+    compare(&std, &mut ll);
+}
+
+gen_tests! {test_insert_before}
+fn test_insert_before<const N: usize>() {
+    let mut std = VecDeque::<usize>::new();
+    let mut ll = LinkedList::<usize, N>::new();
+    let mut c = ll.cursor_mut();
+    let mut pos = 0;
+
+    // Keep inserting before the middle node
+    for i in 0..256 {
+        let mid = i / 2;
+
+        // Keep the cursor right after the middle node
+        if i != 0 {
+            while pos != mid + 1 {
+                pos -= 1;
+                c.previous();
+            }
+        }
+
+        c.insert_before(i);
+        pos += 1;
+
+        validate(c.list);
+
+        std.insert(mid, i);
+        compare(&std, c.list);
+    }
+}
+
+gen_tests! {test_insert_after}
+fn test_insert_after<const N: usize>() {
+    let mut std = VecDeque::<usize>::new();
+    let mut ll = LinkedList::<usize, N>::new();
+    let mut c = ll.cursor_mut();
+    let mut pos = 0;
+
+    // Keep inserting after the middle node
+    for i in 0..256 {
+        let mid = i / 2;
+
+        // Keep the cursor on the middle node
+        while pos != mid {
+            pos += 1;
+            c.next();
+        }
+
+        c.insert_after(i);
+
+        validate(c.list);
+
+        std.insert(if i == 0 { 0 } else { mid + 1 }, i);
+        compare(&std, c.list);
+    }
+}
+
+gen_tests! {test_remove_middle}
+fn test_remove_middle<const N: usize>() {
+    let mut std: VecDeque<usize> = (0..256).collect();
+    let mut ll: LinkedList<usize, N> = (0..256).collect();
+
+    // Keep removing the middle value
+    while !std.is_empty() {
+        let mid = std.len() / 2;
+        let mut c = ll.cursor_mut();
+        for _ in 0..mid {
+            c.next();
+        }
+        let (val, _) = c.remove().unwrap();
+        assert_eq!(val, std.remove(mid).unwrap());
+
+        validate(&mut ll);
+        compare(&std, &mut ll);
+    }
+
+    let mut c = ll.cursor_mut();
+    assert!(c.remove().is_none());
+}
+
+gen_tests! {test_remove_ends}
+fn test_remove_ends<const N: usize>() {
+    let mut std: VecDeque<usize> = (0..256).collect();
+    let mut ll: LinkedList<usize, N> = (0..256).collect();
+
+    // Alternate between removing from the front and back
+    let mut front = true;
+    while !std.is_empty() {
+        let mut c = ll.cursor_mut();
+        let expected = if front {
+            std.pop_front()
+        } else {
+            c.seek_end();
+            std.pop_back()
+        };
+        let (val, _) = c.remove().unwrap();
+        assert_eq!(Some(val), expected);
+        front = !front;
+
+        validate(&mut ll);
+        compare(&std, &mut ll);
+    }
+}
+
+gen_tests! {test_remove_while_iterating}
+fn test_remove_while_iterating<const N: usize>() {
+    // Remove runs at the start, middle and end of the list
+    let remove =
+        |v: usize| v < 10 || (100..150).contains(&v) || v.is_multiple_of(7);
+    for len in [0, 1, 2, 10, 256] {
+        let mut ll: LinkedList<usize, N> = (0..len).collect();
+        let mut visited = Vec::new();
+        let mut c = ll.cursor_mut();
+        while let Some(&mut v) = c.value() {
+            visited.push(v);
+            if remove(v) {
+                assert_eq!(c.remove().unwrap().0, v);
+            }
+            if !c.next() {
+                break;
+            }
+        }
+
+        // Every value visited exactly once
+        assert_eq!(visited, (0..len).collect::<Vec<_>>());
+        validate(&mut ll);
+        compare(&(0..len).filter(|v| !remove(*v)).collect(), &mut ll);
+    }
+
+    // Same for runs
+    let mut ll: LinkedList<usize, N> = (0..100).collect();
+    let mut visited = Vec::new();
+    let mut c = ll.cursor_mut();
+    while let Some(&mut v) = c.value() {
+        visited.push(v);
+        if v.is_multiple_of(10) {
+            let removed = c.remove_until(|v| v % 10 == 5);
+            visited.extend_from_slice(&removed[1..]);
+        }
+        if !c.next() {
+            break;
+        }
+    }
+    assert_eq!(visited, (0..100).collect::<Vec<_>>());
+    validate(&mut ll);
+    compare(
+        &(0..100).filter(|v| v % 10 >= 5).collect::<VecDeque<_>>(),
+        &mut ll,
+    );
+}
+
+gen_tests! {test_references}
+fn test_references<const N: usize>() {
+    let mut ll = LinkedList::<usize, N>::new();
+    let mut refs = Vec::new();
+    let mut c = ll.cursor_mut();
+    for i in 0..256 {
+        c.insert_after(i);
+        c.next();
+        refs.push(c.reference().unwrap());
+    }
+
+    // Remove every third value and ensure all other references still resolve
+    // to the correct values
+    let mut removed = Vec::new();
+    for (i, r) in refs.iter().enumerate().step_by(3) {
+        let mut c = unsafe { r.cursor_mut(&mut ll) };
+        let (val, null_ref) = c.remove().unwrap();
+        assert_eq!(val, i);
+        assert!(null_ref.unwrap() == *r);
+        removed.push(i);
+    }
+    validate(&mut ll);
+
+    for (i, r) in refs.iter().enumerate() {
+        if i % 3 != 0 {
+            let mut c = unsafe { r.cursor_mut(&mut ll) };
+            assert_eq!(c.value().copied(), Some(i));
+        }
+    }
+}
+
+gen_tests! {test_sort}
+fn test_sort<const N: usize>() {
+    // Pseudo-random values with many duplicates
+    let src: Vec<usize> = (0..256).map(|i| (i * 7919) % 61).collect();
+    let mut ll: LinkedList<(usize, usize), N> = src
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(i, v)| (v, i))
+        .collect();
+    let refs: Vec<_> = {
+        let mut c = ll.cursor_mut();
+        let mut refs = vec![c.reference().unwrap()];
+        while c.next() {
+            refs.push(c.reference().unwrap());
+        }
+        refs
+    };
+
+    // Stable, so values with equal keys keep their insertion order
+    ll.sort_by_key(|(v, _)| *v);
+    let mut std: VecDeque<_> = src
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(i, v)| (v, i))
+        .collect();
+    std.make_contiguous().sort_by_key(|(v, _)| *v);
+    validate(&mut ll);
+    compare(&std, &mut ll);
+
+    for (i, r) in refs.iter().enumerate() {
+        let mut c = unsafe { r.cursor_mut(&mut ll) };
+        assert_eq!(c.value().copied(), Some((src[i], i)));
+    }
+
+    ll.sort_by(|a, b| b.cmp(a));
+    std.make_contiguous().sort_by(|a, b| b.cmp(a));
+    compare(&std, &mut ll);
+    ll.sort();
+    std.make_contiguous().sort();
+    compare(&std, &mut ll);
+}
+
+gen_tests! {test_split_off_splice}
+fn test_split_off_splice<const N: usize>() {
+    const LEN: usize = 64;
+
+    for at in (0..LEN).step_by(5) {
+        let mut ll: LinkedList<usize, N> = (0..LEN).collect();
+        let refs: Vec<_> = {
+            let mut c = ll.cursor_mut();
+            let mut refs = vec![c.reference().unwrap()];
+            while c.next() {
+                refs.push(c.reference().unwrap());
+            }
+            refs
+        };
+
+        let mut c = ll.cursor_mut();
+        for _ in 0..at {
+            c.next();
+        }
+        let mut split = c.split_off();
+        assert_eq!(c.value().copied(), Some(at));
+        validate(&mut ll);
+        validate(&mut split);
+        compare(&(0..=at).collect(), &mut ll);
+        compare(&(at + 1..LEN).collect(), &mut split);
+        for (i, r) in refs.iter().enumerate().skip(at + 1) {
+            let mut c = unsafe { r.cursor_mut(&mut split) };
+            assert_eq!(c.value().copied(), Some(i));
+        }
+
+        // Splice after the first value and move the values back to their
+        // original positions
+        let mut c = ll.cursor_mut();
+        c.splice_after(split);
+        assert_eq!(c.value().copied(), Some(0));
+        validate(&mut ll);
+        compare(
+            &std::iter::once(0)
+                .chain(at + 1..LEN)
+                .chain(1..=at)
+                .collect(),
+            &mut ll,
+        );
+
+        let mut c = unsafe { refs[LEN - 1].cursor_mut(&mut ll) };
+        let split = c.split_off();
+        c.seek_start();
+        c.splice_after(split);
+        c.seek_end();
+        c.splice_after(LinkedList::new());
+        validate(&mut ll);
+        compare(&(0..LEN).collect(), &mut ll);
+        for (i, r) in refs.iter().enumerate() {
+            let mut c = unsafe { r.cursor_mut(&mut ll) };
+            assert_eq!(c.value().copied(), Some(i));
+        }
+    }
+
+    // Splicing into an empty list
+    let mut ll = LinkedList::<usize, N>::new();
+    assert_eq!(ll.cursor_mut().split_off().len(), 0);
+    ll.cursor_mut().splice_after((0..10).collect());
+    validate(&mut ll);
+    compare(&(0..10).collect(), &mut ll);
+}
+
+gen_tests! {test_append}
+fn test_append<const N: usize>() {
+    let mut ll = LinkedList::<usize, N>::new();
+    let mut other: LinkedList<usize, N> = (0..10).collect();
+    let r = other.cursor_mut().reference().unwrap();
+    ll.append(&mut other);
+    validate(&mut ll);
+    validate(&mut other);
+    compare(&(0..10).collect(), &mut ll);
+    assert_eq!(other.len(), 0);
+
+    ll.append(&mut other);
+    let mut other: LinkedList<usize, N> = (10..100).collect();
+    ll.append(&mut other);
+    validate(&mut ll);
+    compare(&(0..100).collect(), &mut ll);
+    assert_eq!(unsafe { r.cursor_mut(&mut ll) }.value().copied(), Some(0));
+
+    // Other list remains usable
+    other.cursor_mut().insert_after(1);
+    validate(&mut other);
+    compare(&std::iter::once(1).collect(), &mut other);
+}
+
+gen_tests! {test_move_to_front_back}
+fn test_move_to_front_back<const N: usize>() {
+    const LEN: usize = 64;
+    let mut ll: LinkedList<usize, N> = (0..LEN).collect();
+    let mut std: VecDeque<usize> = (0..LEN).collect();
+    let refs: Vec<_> = {
+        let mut c = ll.cursor_mut();
+        let mut refs = vec![c.reference().unwrap()];
+        while c.next() {
+            refs.push(c.reference().unwrap());
+        }
+        refs
+    };
+
+    for i in (0..LEN).step_by(3).chain([LEN - 1, 0]) {
+        let mut c = unsafe { refs[i].cursor_mut(&mut ll) };
+        if i % 2 == 0 {
+            c.move_to_front();
+        } else {
+            c.move_to_back();
+        }
+        assert_eq!(c.value().copied(), Some(i));
+
+        let pos = std.iter().position(|v| *v == i).unwrap();
+        std.remove(pos);
+        if i % 2 == 0 {
+            std.push_front(i);
+        } else {
+            std.push_back(i);
+        }
+        validate(&mut ll);
+        compare(&std, &mut ll);
+    }
+
+    for (i, r) in refs.iter().enumerate() {
+        let mut c = unsafe { r.cursor_mut(&mut ll) };
+        assert_eq!(c.value().copied(), Some(i));
+    }
+
+    // Single value lists are not modified
+    let mut ll: LinkedList<usize, N> = std::iter::once(1).collect();
+    ll.cursor_mut().move_to_front();
+    ll.cursor_mut().move_to_back();
+    validate(&mut ll);
+    compare(&std::iter::once(1).collect(), &mut ll);
+}
+
+gen_tests! {test_into_iter}
+fn test_into_iter<const N: usize>() {
+    let ll: LinkedList<String, N> = (0..100).map(|i| i.to_string()).collect();
+    let mut it = ll.into_iter();
+    assert_eq!(it.len(), 100);
+    assert_eq!(it.next().as_deref(), Some("0"));
+    assert_eq!(it.len(), 99);
+    assert_eq!(
+        it.collect::<Vec<_>>(),
+        (1..100).map(|i| i.to_string()).collect::<Vec<_>>()
+    );
+
+    // Partially consumed iterators drop the remaining values
+    let ll: LinkedList<String, N> = (0..100).map(|i| i.to_string()).collect();
+    let mut it = ll.into_iter();
+    it.nth(50);
+    drop(it);
+
+    let mut it = LinkedList::<String, N>::new().into_iter();
+    assert_eq!(it.next(), None);
+    assert_eq!(it.next(), None);
+}
+
+gen_tests! {test_double_ended}
+fn test_double_ended<const N: usize>() {
+    let mut ll: LinkedList<usize, N> = (0..100).collect();
+    assert!(ll.iter().rev().copied().eq((0..100).rev()));
+    for v in ll.iter_mut().rev().take(10) {
+        *v += 100;
+    }
+    assert!(ll.iter().copied().eq((0..90).chain(190..200)));
+
+    // Alternate between ends until they meet
+    let mut std: VecDeque<usize> = ll.iter().copied().collect();
+    let mut it = ll.iter_mut();
+    let mut front = true;
+    while !std.is_empty() {
+        assert_eq!(it.len(), std.len());
+        if front {
+            assert_eq!(it.next().copied(), std.pop_front());
+        } else {
+            assert_eq!(it.next_back().copied(), std.pop_back());
+        }
+        front = !front;
+    }
+    assert_eq!(it.next(), None);
+    assert_eq!(it.next_back(), None);
+
+    let mut it = ll.clone().into_iter();
+    assert_eq!(it.next_back(), Some(199));
+    assert_eq!(it.next(), Some(0));
+    assert_eq!(
+        it.rev().collect::<Vec<_>>(),
+        ll.iter()
+            .copied()
+            .rev()
+            .skip(1)
+            .take(98)
+            .collect::<Vec<_>>()
+    );
+
+    let mut ll = LinkedList::<usize, N>::new();
+    assert_eq!(ll.iter().next_back(), None);
+    assert_eq!(ll.iter_mut().next_back(), None);
+}
+
+gen_tests! {test_extend}
+fn test_extend<const N: usize>() {
+    let mut ll = LinkedList::<usize, N>::new();
+    ll.extend(0..10);
+    validate(&mut ll);
+    compare(&(0..10).collect(), &mut ll);
+
+    // Cursor position of the caller does not matter
+    ll.cursor_mut().next();
+    ll.extend_from_slice(&(10..50).collect::<Vec<_>>());
+    ll.extend(&[50, 51]);
+    ll.extend(std::iter::empty::<usize>());
+    validate(&mut ll);
+    compare(&(0..52).collect(), &mut ll);
+}
+
+gen_tests! {test_std_traits}
+fn test_std_traits<const N: usize>() {
+    let mut ll: LinkedList<usize, N> = (0..20).collect();
+    assert_eq!(
+        ll.iter().copied().collect::<Vec<_>>(),
+        (0..20).collect::<Vec<_>>()
+    );
+    assert_eq!(ll.iter().len(), 20);
+    assert_eq!(
+        format!("{:?}", ll),
+        format!("{:?}", (0..20).collect::<Vec<_>>())
+    );
+
+    let mut cloned = ll.clone();
+    validate(&mut cloned);
+    assert_eq!(cloned, ll);
+    assert_eq!(ll, (0..20).collect::<LinkedList<usize, 3>>());
+    assert_eq!(ll, &(0..20).collect::<Vec<_>>()[..]);
+    assert_eq!(ll, *(0..20).collect::<Vec<_>>().as_slice());
+
+    // Modifying the copy does not affect the original
+    *cloned.iter_mut().next().unwrap() = 100;
+    assert_ne!(cloned, ll);
+    cloned.cursor_mut().insert_after(1);
+    assert_ne!(cloned.len(), ll.len());
+    assert_ne!(ll, [0, 1, 2]);
+
+    let mut empty = LinkedList::<usize, N>::default();
+    validate(&mut empty);
+    assert_eq!(empty, []);
+    assert_eq!(format!("{:?}", empty), "[]");
+    assert_ne!(empty, ll);
+    compare(&(0..20).collect(), &mut ll);
+}
+
+#[test]
+fn send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<LinkedList<usize>>();
+    assert_send_sync::<super::NodeRef<usize>>();
+    assert_send_sync::<super::CursorMut<'static, usize, 8>>();
+    assert_send_sync::<super::IntoIter<usize>>();
+    assert_send_sync::<super::Iter<'static, usize>>();
+    assert_send_sync::<super::IterMut<'static, usize>>();
+    assert_send_sync::<crate::free_list::FreeList>();
+
+    // Lists can be moved to and shared with other threads
+    let ll: LinkedList<usize> = (0..100).collect();
+    let ll = std::sync::Arc::new(std::sync::Mutex::new(ll));
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let ll = ll.clone();
+            std::thread::spawn(move || {
+                let mut ll = ll.lock().unwrap();
+                ll.cursor_mut().insert_after(100 + i);
+                ll.iter().sum::<usize>()
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(ll.lock().unwrap().len(), 104);
+}
+
+gen_tests! {test_generations}
+fn test_generations<const N: usize>() {
+    let mut ll: LinkedList<usize, N> = (0..20).collect();
+    let first = ll.cursor_mut().reference().unwrap();
+    let mut refs = vec![first.clone()];
+    for _ in 0..10 {
+        let mut c = unsafe { refs.last().unwrap().cursor_mut(&mut ll) };
+        let (_, null_ref) = c.remove().unwrap();
+        assert!(null_ref.unwrap() == *refs.last().unwrap());
+
+        // Location of the removed value is reused with a new generation
+        let r = ll.cursor_mut().reference().unwrap();
+        assert!(r != *refs.last().unwrap());
+        assert!(unsafe { r.is_valid(&ll) });
+        refs.push(r);
+    }
+    for r in &refs[..refs.len() - 1] {
+        assert!(!unsafe { r.is_valid(&ll) });
+    }
+    validate(&mut ll);
+    compare(&(10..20).collect(), &mut ll);
+
+    // Retired locations of spliced lists are kept for checking
+    let mut other: LinkedList<usize, N> = (0..2).collect();
+    let r = other.cursor_mut().reference().unwrap();
+    unsafe { r.cursor_mut(&mut other).remove() };
+    ll.append(&mut other);
+    assert!(!unsafe { r.is_valid(&ll) });
+}
+
+gen_tests! {test_swap}
+fn test_swap<const N: usize>() {
+    const LEN: usize = 40;
+    let mut ll: LinkedList<usize, N> = (0..LEN).collect();
+    let mut std: VecDeque<usize> = (0..LEN).collect();
+    let refs: Vec<_> = {
+        let mut c = ll.cursor_mut();
+        let mut refs = vec![c.reference().unwrap()];
+        while c.next() {
+            refs.push(c.reference().unwrap());
+        }
+        refs
+    };
+
+    // Bubble the first value to the end
+    let mut c = ll.cursor_mut();
+    while c.swap_with_next() {
+        assert_eq!(c.value().copied(), Some(0));
+    }
+    std.rotate_left(1);
+    validate(&mut ll);
+    compare(&std, &mut ll);
+
+    for (a, b) in [(1, 39), (5, 6), (10, 10), (0, 20), (33, 2)] {
+        unsafe { ll.swap_values(&refs[a], &refs[b]) };
+        let a_pos = std.iter().position(|v| *v == a).unwrap();
+        let b_pos = std.iter().position(|v| *v == b).unwrap();
+        std.swap(a_pos, b_pos);
+        validate(&mut ll);
+        compare(&std, &mut ll);
+    }
+
+    for (i, r) in refs.iter().enumerate() {
+        let mut c = unsafe { r.cursor_mut(&mut ll) };
+        assert_eq!(c.value().copied(), Some(i));
+    }
+
+    let mut empty = LinkedList::<usize, N>::new();
+    assert!(!empty.cursor_mut().swap_with_next());
+}
+
+gen_tests! {test_peek}
+fn test_peek<const N: usize>() {
+    let mut ll: LinkedList<usize, N> = (0..40).collect();
+    let mut c = ll.cursor_mut();
+    assert_eq!(c.peek_prev(), None);
+    for i in 0..40 {
+        assert_eq!(c.value().copied(), Some(i));
+        assert_eq!(
+            c.peek_next().copied(),
+            if i < 39 { Some(i + 1) } else { None }
+        );
+        if i > 0 {
+            assert_eq!(c.peek_prev().copied(), Some(i - 1));
+        }
+        c.next();
+    }
+    assert_eq!(c.value().copied(), Some(39));
+
+    let mut empty = LinkedList::<usize, N>::new();
+    let c = empty.cursor_mut();
+    assert_eq!(c.peek_next(), None);
+    assert_eq!(c.peek_prev(), None);
+}
+
+gen_tests! {test_insert_sorted}
+fn test_insert_sorted<const N: usize>() {
+    let mut ll = LinkedList::<(usize, usize), N>::new();
+    let mut std = Vec::new();
+    for i in 0..200 {
+        let val = ((i * 7919) % 31, i);
+        if i % 3 == 0 {
+            ll.insert_sorted(val, |a, b| a.0.cmp(&b.0));
+        } else {
+            // Start from a cursor at an arbitrary position
+            let mut c = ll.cursor_mut();
+            for _ in 0..(i * 13) % (i + 1) {
+                c.next();
+            }
+            c.insert_sorted(val, |a, b| a.0.cmp(&b.0));
+            assert_eq!(c.value().copied(), Some(val));
+        }
+
+        // Stable, so equal keys are kept in insertion order
+        std.push(val);
+        std.sort_by_key(|v| v.0);
+    }
+    validate(&mut ll);
+    compare(&std.into_iter().collect(), &mut ll);
+}
+
+gen_tests! {test_position}
+fn test_position<const N: usize>() {
+    const LEN: usize = 100;
+    let mut ll: LinkedList<usize, N> = (0..LEN).collect();
+    let refs: Vec<_> = {
+        let mut c = ll.cursor_mut();
+        let mut refs = vec![c.reference().unwrap()];
+        while c.next() {
+            refs.push(c.reference().unwrap());
+        }
+        refs
+    };
+
+    // Counted once for cursors from references
+    for (i, r) in refs.iter().enumerate().step_by(7) {
+        let mut c = unsafe { r.cursor_mut(&mut ll) };
+        assert_eq!(c.position(), Some(i));
+    }
+
+    let mut c = ll.cursor_mut();
+    for n in [0, 99, 50, 49, 51, 10, 90, 3, 5] {
+        assert!(c.seek(n));
+        assert_eq!(c.position(), Some(n));
+        assert_eq!(c.value().copied(), Some(n));
+    }
+    assert!(!c.seek(LEN));
+    assert_eq!(c.position(), Some(5));
+
+    // Tracked through modifications
+    let mut std: VecDeque<usize> = (0..LEN).collect();
+    c.insert_before(1000);
+    std.insert(5, 1000);
+    assert_eq!(c.position(), Some(6));
+    c.insert_after(1001);
+    std.insert(7, 1001);
+    assert_eq!(c.position(), Some(6));
+    c.remove();
+    std.remove(6);
+    assert_eq!(c.position(), Some(5));
+    c.seek_start();
+    c.remove();
+    std.remove(0);
+    assert_eq!(c.position(), Some(0));
+    c.seek(20);
+    c.move_to_front();
+    let v = std.remove(20).unwrap();
+    std.push_front(v);
+    assert_eq!(c.position(), Some(0));
+    c.seek(30);
+    c.move_to_back();
+    let v = std.remove(30).unwrap();
+    std.push_back(v);
+    assert_eq!(c.position(), Some(std.len() - 1));
+    c.seek(40);
+    c.swap_with_next();
+    std.swap(40, 41);
+    assert_eq!(c.position(), Some(41));
+    c.seek_end();
+    assert_eq!(c.position(), Some(std.len() - 1));
+    for i in (0..std.len()).rev() {
+        assert_eq!(c.position(), Some(i));
+        assert_eq!(c.value().copied(), Some(std[i]));
+        c.previous();
+    }
+    compare(&std, &mut ll);
+
+    let mut empty = LinkedList::<usize, N>::new();
+    let mut c = empty.cursor_mut();
+    assert_eq!(c.position(), None);
+    assert!(!c.seek(0));
+    c.insert_before(1);
+    assert_eq!(c.position(), Some(0));
+}
+
+gen_tests! {test_to_vec}
+fn test_to_vec<const N: usize>() {
+    let ll: LinkedList<usize, N> = (0..30).collect();
+    assert_eq!(ll.to_vec(), (0..30).collect::<Vec<_>>());
+    assert_eq!(LinkedList::<usize, N>::new().to_vec(), Vec::<usize>::new());
+
+    let mut buf = [0; 40];
+    assert_eq!(ll.copy_into(&mut buf), 30);
+    assert_eq!(buf[..30], ll.to_vec()[..]);
+    assert_eq!(buf[30..], [0; 10]);
+
+    let mut buf = [0; 10];
+    assert_eq!(ll.copy_into(&mut buf), 10);
+    assert_eq!(buf[..], ll.to_vec()[..10]);
+}
+
+gen_tests! {test_checked_refs}
+fn test_checked_refs<const N: usize>() {
+    let mut ll: LinkedList<usize, N> = (0..30).collect();
+    let mut other: LinkedList<usize, N> = (0..30).collect();
+    let refs: Vec<_> = {
+        let mut c = ll.cursor_mut();
+        let mut refs = vec![c.checked_reference().unwrap()];
+        while c.next() {
+            refs.push(c.checked_reference().unwrap());
+        }
+        refs
+    };
+
+    // Not valid for other lists
+    assert!(refs[0].is_valid(&ll));
+    assert!(!refs[0].is_valid(&other));
+    assert!(refs[0].cursor_mut(&mut other).is_none());
+
+    let (_, null_ref) = refs[3].cursor_mut(&mut ll).unwrap().remove().unwrap();
+    assert!(refs[3] == null_ref.unwrap());
+    assert!(!refs[3].is_valid(&ll));
+    assert!(refs[3].cursor_mut(&mut ll).is_none());
+    ll.cursor_mut().reference();
+    assert!(!refs[3].is_valid(&ll));
+    assert_eq!(
+        refs[4].cursor_mut(&mut ll).unwrap().value().copied(),
+        Some(4)
+    );
+
+    // Moving values out of the list invalidates all references
+    let mut c = refs[20].cursor_mut(&mut ll).unwrap();
+    let mut split = c.split_off();
+    assert!(refs.iter().all(|r| !r.is_valid(&ll) && !r.is_valid(&split)));
+    drop(ll);
+    other.append(&mut split);
+    assert!(refs.iter().all(|r| !r.is_valid(&other)));
+}
+
+gen_tests! {test_remove_by_reference}
+fn test_remove_by_reference<const N: usize>() {
+    let mut ll: LinkedList<usize, N> = (0..50).collect();
+    let mut std: VecDeque<usize> = (0..50).collect();
+    let refs: Vec<_> = {
+        let mut c = ll.cursor_mut();
+        let mut refs = vec![c.checked_reference().unwrap()];
+        while c.next() {
+            refs.push(c.checked_reference().unwrap());
+        }
+        refs
+    };
+
+    for i in [0, 49, 25, 26, 24, 1, 48] {
+        assert_eq!(ll.remove(refs[i].clone()), Some(i));
+        assert_eq!(ll.remove(refs[i].clone()), None);
+        std.retain(|v| *v != i);
+        validate(&mut ll);
+        compare(&std, &mut ll);
+    }
+
+    let mut other: LinkedList<usize, N> = (0..50).collect();
+    assert_eq!(other.remove(refs[10].clone()), None);
+    assert_eq!(other.len(), 50);
+}
+
+gen_tests! {test_remove_runs}
+fn test_remove_runs<const N: usize>() {
+    const LEN: usize = 60;
+    for (start, n) in [(0, 5), (10, 20), (50, 100), (0, LEN), (30, 0), (59, 1)]
+    {
+        let mut ll: LinkedList<usize, N> = (0..LEN).collect();
+        let refs: Vec<_> = {
+            let mut c = ll.cursor_mut();
+            let mut refs = vec![c.checked_reference().unwrap()];
+            while c.next() {
+                refs.push(c.checked_reference().unwrap());
+            }
+            refs
+        };
+
+        let mut c = ll.cursor_mut();
+        c.seek(start);
+        let removed = c.remove_n(n);
+        let end = (start + n).min(LEN);
+        assert_eq!(removed, (start..end).collect::<Vec<_>>());
+
+        // Lands on the previous value, if any, or the next one otherwise
+        let expected = if n == 0 {
+            Some(start)
+        } else if start > 0 {
+            Some(start - 1)
+        } else if end < LEN {
+            Some(end)
+        } else {
+            None
+        };
+        assert_eq!(c.value().copied(), expected);
+        assert_eq!(c.position(), expected.map(|v| v.min(start)));
+
+        validate(&mut ll);
+        compare(&(0..start).chain(end..LEN).collect(), &mut ll);
+        for (i, r) in refs.iter().enumerate() {
+            assert_eq!(r.is_valid(&ll), !(start..end).contains(&i));
+        }
+    }
+
+    let mut ll: LinkedList<usize, N> = (0..LEN).collect();
+    let mut c = ll.cursor_mut();
+    c.seek(5);
+    let removed = c.remove_until(|v| *v % 17 == 0);
+    assert_eq!(removed, (5..17).collect::<Vec<_>>());
+    c.next();
+    assert_eq!(c.value().copied(), Some(17));
+    validate(&mut ll);
+    compare(&(0..5).chain(17..LEN).collect(), &mut ll);
+}
+
+gen_tests! {test_model}
+fn test_model<const N: usize>() {
+    // Each case runs up to 16 batches of 32 cursor operations
+    TestRunner::new(Config::with_cases(64))
+        .run(
+            &vec((vec(cursor_op(), 0..32), list_op()), 0..16),
+            |batches| {
+                run_model::<N>(batches);
+                Ok(())
+            },
+        )
+        .unwrap();
+}
+
+/// Operation on a list through a cursor. Positions and counts are reduced
+/// modulo the list length on application.
+#[derive(Clone, Debug)]
+enum CursorOp {
+    Next,
+    Previous,
+    InsertBefore(u8),
+    InsertAfter(u8),
+    Remove,
+    RemoveN(usize),
+    RemoveUntil(u8),
+    Seek(usize),
+    SeekStart,
+    SeekEnd,
+    MoveToFront,
+    MoveToBack,
+    SwapWithNext,
+    Reference,
+}
+
+/// Operation on the whole list, ending a batch of cursor operations
+#[derive(Clone, Debug)]
+enum ListOp {
+    None,
+    SplitOff(usize),
+    SpliceAfter { at: usize, into: usize },
+    Append(Vec<u8>),
+    Sort,
+    InsertSorted { key: u8, from: usize },
+    SwapValues(usize, usize),
+    RemoveReference(usize),
+}
+
+fn cursor_op() -> impl Strategy<Value = CursorOp> {
+    use CursorOp::*;
+
+    prop_oneof![
+        3 => Just(Next),
+        1 => Just(Previous),
+        3 => any::<u8>().prop_map(InsertBefore),
+        3 => any::<u8>().prop_map(InsertAfter),
+        2 => Just(Remove),
+        1 => (0..64usize).prop_map(RemoveN),
+        1 => any::<u8>().prop_map(RemoveUntil),
+        1 => (0..256usize).prop_map(Seek),
+        1 => Just(SeekStart),
+        1 => Just(SeekEnd),
+        1 => Just(MoveToFront),
+        1 => Just(MoveToBack),
+        1 => Just(SwapWithNext),
+        1 => Just(Reference),
+    ]
+}
+
+fn list_op() -> impl Strategy<Value = ListOp> {
+    use ListOp::*;
+
+    let i = || 0..256usize;
+    prop_oneof![
+        3 => Just(None),
+        1 => i().prop_map(SplitOff),
+        1 => (i(), i()).prop_map(|(at, into)| SpliceAfter { at, into }),
+        1 => vec(any::<u8>(), 0..32).prop_map(Append),
+        1 => Just(Sort),
+        1 => (any::<u8>(), i()).prop_map(|(key, from)| InsertSorted { key, from }),
+        1 => (i(), i()).prop_map(|(a, b)| SwapValues(a, b)),
+        1 => i().prop_map(RemoveReference),
+    ]
+}
+
+/// Value of the model test. Sorted and searched by the key only, while the
+/// unique ID tells apart values with equal keys.
+type Value = (u8, usize);
+
+/// Apply batches of operations to both a list and a VecDeque model of it and
+/// compare the results.
+///
+/// Cursor operations of a batch are applied through a single cursor. The list
+/// and the references taken during the batch are checked after each batch.
+fn run_model<const N: usize>(batches: Vec<(Vec<CursorOp>, ListOp)>) {
+    let mut ll = LinkedList::<Value, N>::new();
+    let mut std = VecDeque::new();
+    let mut refs = Vec::new();
+    let mut next_id = 0;
+    let mut value = |key| {
+        next_id += 1;
+        (key, next_id)
+    };
+    let mut pos = 0;
+    for (ops, list_op) in batches {
+        let mut c = ll.cursor_mut();
+        c.seek(pos);
+        let mut before_start = false;
+
+        for op in ops {
+            let len = std.len();
+            match op {
+                CursorOp::Next => {
+                    let moved = c.next();
+                    let expected = before_start || pos + 1 < len;
+                    assert_eq!(moved, expected);
+                    if expected && !before_start {
+                        pos += 1;
+                    }
+                    before_start = false;
+                }
+                CursorOp::Previous => {
+                    assert_eq!(c.previous(), pos > 0);
+                    pos = pos.saturating_sub(1);
+                    before_start = false;
+                }
+                CursorOp::InsertBefore(key) => {
+                    let val = value(key);
+                    c.insert_before(val);
+                    if len == 0 {
+                        std.push_back(val);
+                    } else {
+                        std.insert(pos, val);
+                        pos += 1;
+                    }
+                    before_start = false;
+                }
+                CursorOp::InsertAfter(key) => {
+                    let val = value(key);
+                    c.insert_after(val);
+                    if len == 0 {
+                        std.push_back(val);
+                    } else {
+                        std.insert(pos + 1, val);
+                    }
+                    before_start = false;
+                }
+                CursorOp::Remove => {
+                    assert_eq!(c.remove().map(|(v, _)| v), std.remove(pos));
+                    if pos > 0 {
+                        pos -= 1;
+                    } else {
+                        before_start = !std.is_empty();
+                    }
+                }
+                CursorOp::RemoveN(_) | CursorOp::RemoveUntil(_) => {
+                    let (removed, count) = match op {
+                        CursorOp::RemoveN(n) => {
+                            let n = n % (len + 1);
+                            (c.remove_n(n), n.min(len.saturating_sub(pos)))
+                        }
+                        CursorOp::RemoveUntil(key) => (
+                            c.remove_until(|v| v.0 >= key),
+                            std.iter()
+                                .skip(pos)
+                                .take_while(|v| v.0 < key)
+                                .count(),
+                        ),
+                        _ => unreachable!(),
+                    };
+                    assert_eq!(
+                        removed,
+                        std.drain(pos..pos + count).collect::<Vec<_>>()
+                    );
+                    if count != 0 {
+                        if pos > 0 {
+                            pos -= 1;
+                            before_start = false;
+                        } else {
+                            before_start = !std.is_empty();
+                        }
+                    }
+                }
+                CursorOp::Seek(n) => {
+                    let n = n % (len + 2);
+                    assert_eq!(c.seek(n), n < len);
+                    if n < len {
+                        pos = n;
+                        before_start = false;
+                    }
+                }
+                CursorOp::SeekStart => {
+                    c.seek_start();
+                    pos = 0;
+                    before_start = false;
+                }
+                CursorOp::SeekEnd => {
+                    c.seek_end();
+                    pos = len.saturating_sub(1);
+                    before_start = false;
+                }
+                CursorOp::MoveToFront => {
+                    c.move_to_front();
+                    if let Some(val) = std.remove(pos) {
+                        std.push_front(val);
+                    }
+                    pos = 0;
+                    before_start = false;
+                }
+                CursorOp::MoveToBack => {
+                    c.move_to_back();
+                    if let Some(val) = std.remove(pos) {
+                        std.push_back(val);
+                    }
+                    pos = len.saturating_sub(1);
+                    before_start = false;
+                }
+                CursorOp::SwapWithNext => {
+                    assert_eq!(c.swap_with_next(), pos + 1 < len);
+                    if pos + 1 < len {
+                        std.swap(pos, pos + 1);
+                        pos += 1;
+                    }
+                    before_start = false;
+                }
+                CursorOp::Reference => {
+                    if let Some(r) = c.checked_reference() {
+                        refs.push((r, std[pos]));
+                    }
+                }
+            }
+
+            assert_eq!(c.value().copied(), std.get(pos).copied());
+            assert_eq!(c.position(), (!std.is_empty()).then_some(pos));
+        }
+
+        let len = std.len();
+        match list_op {
+            ListOp::None => (),
+            ListOp::SplitOff(at) => {
+                let at = at % len.max(1);
+                let mut c = ll.cursor_mut();
+                c.seek(at);
+                let mut split = c.split_off();
+                let tail = std.split_off((at + 1).min(len));
+                validate(&mut split);
+                compare(&tail, &mut split);
+
+                // All CheckedRef to the list are invalidated
+                refs.clear();
+            }
+            ListOp::SpliceAfter { at, into } => {
+                let at = at % len.max(1);
+                let mut c = ll.cursor_mut();
+                c.seek(at);
+                let split = c.split_off();
+                let mut tail = std.split_off((at + 1).min(len));
+                refs.clear();
+
+                let into = into % std.len().max(1);
+                c.seek(into);
+                c.splice_after(split);
+                let into = (into + 1).min(std.len());
+                for val in tail.drain(..).rev() {
+                    std.insert(into, val);
+                }
+            }
+            ListOp::Append(keys) => {
+                let mut other: LinkedList<Value, N> =
+                    keys.into_iter().map(&mut value).collect();
+                std.extend(other.iter().copied());
+                ll.append(&mut other);
+                assert!(other.is_empty());
+            }
+            ListOp::Sort => {
+                ll.sort_by_key(|v| v.0);
+                std.make_contiguous().sort_by_key(|v| v.0);
+            }
+            ListOp::InsertSorted { key, from } => {
+                ll.sort_by_key(|v| v.0);
+                std.make_contiguous().sort_by_key(|v| v.0);
+
+                let val = value(key);
+                let mut c = ll.cursor_mut();
+                c.seek(from % len.max(1));
+                c.insert_sorted(val, |a, b| a.0.cmp(&b.0));
+                let i = std.partition_point(|v| v.0 <= key);
+                assert_eq!(c.position(), Some(i));
+                std.insert(i, val);
+            }
+            ListOp::SwapValues(a, b) => {
+                if len != 0 {
+                    let (a, b) = (a % len, b % len);
+                    let mut reference = |i| {
+                        let mut c = ll.cursor_mut();
+                        c.seek(i);
+                        c.reference().unwrap()
+                    };
+                    let (ra, rb) = (reference(a), reference(b));
+                    unsafe { ll.swap_values(&ra, &rb) };
+                    std.swap(a, b);
+                }
+            }
+            ListOp::RemoveReference(i) => {
+                if !refs.is_empty() {
+                    let (r, val) = refs.swap_remove(i % refs.len());
+                    let i = std.iter().position(|v| *v == val);
+                    assert_eq!(ll.remove(r), i.and_then(|i| std.remove(i)));
+                }
+            }
+        }
+
+        validate(&mut ll);
+        compare(&std, &mut ll);
+        for (r, val) in &refs {
+            assert_eq!(r.is_valid(&ll), std.contains(val));
+            if let Some(mut c) = r.cursor_mut(&mut ll) {
+                assert_eq!(c.value().copied(), Some(*val));
+            }
+        }
+        pos = pos.min(std.len().saturating_sub(1));
+    }
+}
+
+// TODO: 100% coverage
+
+/// Validate the various components of the list are consistent with each other
+fn validate<T, const N: usize>(ll: &mut LinkedList<T, N>)
+where
+    T: Sized + Clone + Eq + Debug + 'static,
+{
+    let mut iterations = 0;
+    let mut c = ll.cursor_mut();
+    if c.value().is_some() {
+        iterations = 1;
+    }
+    while c.next() {
+        iterations += 1;
+    }
+    assert_eq!(iterations, ll.len());
+
+    let it_len = ll.iter_mut().len();
+    assert_eq!(it_len, ll.len());
+
+    assert_ne!(ll.head, null_mut());
+    assert_ne!(ll.tail, null_mut());
+    if ll.is_empty() {
+        assert_eq!(ll.tail, ll.head);
+    }
+
+    let mut node_length = 0;
+    let mut node = ll.head;
+    let mut prev: *mut Node<T, N> = null_mut();
+    while !node.is_null() {
+        unsafe {
+            node_length += (*node).len();
+
+            if !prev.is_null() {
+                assert_eq!((*prev).next(), node);
+            }
+            assert_eq!((*node).previous(), prev);
+            prev = node;
+
+            if !ll.is_empty() {
+                assert_ne!((*node).len(), 0);
+            }
+            node = (*node).next();
+        }
+    }
+    assert_eq!(prev, ll.tail);
+    assert_eq!(node_length, ll.len());
+}
+
+/// Assert the list from standard library and allocator list are equal.
+// Also perform basic consistency validation.
+fn compare<T, const N: usize>(std: &VecDeque<T>, ll: &mut LinkedList<T, N>)
+where
+    T: Sized + Clone + Eq + Debug + 'static,
+{
+    macro_rules! compare_it {
+        ($expected:expr, $got:expr) => {
+            assert_eq!(
+                $expected.cloned().collect::<Vec<_>>(),
+                $got.map(|v| v.clone()).collect::<Vec<_>>(),
+            );
+        };
+    }
+
+    compare_it!(std.iter(), ll.iter_mut());
+    compare_it!(std.iter().rev(), ll.iter_mut().rev());
+}
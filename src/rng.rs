@@ -0,0 +1,33 @@
+//! Deterministic random numbers shared by the randomized tests
+
+/// Deterministic xorshift random number generator for reproducible tests
+pub struct Rng(u32);
+
+impl Rng {
+    /// Create a generator from a non-zero seed
+    pub fn new(seed: u32) -> Self {
+        assert_ne!(seed, 0, "xorshift seed must not be zero");
+        Self(seed)
+    }
+
+    /// Returns the next random number
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// Returns a random number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        self.next_u32() as usize % n
+    }
+
+    /// Fill a buffer with random bytes, that can not be compressed
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        for b in buf.iter_mut() {
+            *b = self.next_u32() as u8;
+        }
+    }
+}